@@ -0,0 +1,322 @@
+// A statistical test for timing leaks after dudect (Reparaz, Balasch and
+// Verbauwhede, "Dude, is my code constant time?"). An operation is timed
+// on inputs of two classes, one fixed input and fresh random ones, drawn
+// before timing starts and interleaved in random order, so that drift in
+// the machine's speed hits both classes alike. Welch's t-test then checks
+// whether the classes take different time on average. It is repeated on
+// the measurements below a few percentiles, since the long tail that
+// interrupts add can hide a leak, and |t| above `THRESHOLD` on any of them
+// counts as one. Without such a t, the verdict is only a pass if every
+// t-test could be run, on at least `MIN_SAMPLES` measurements.
+
+use rand::Rng;
+use std::fmt;
+use std::hint::black_box;
+use std::io::{self, Write};
+use std::time::Instant;
+
+/// The |t| dudect takes as evidence of a leak.
+pub const THRESHOLD: f64 = 4.5;
+
+/// The fewest measurements that a pass is drawn from.
+pub const MIN_SAMPLES: usize = 100;
+
+/// Fractions of the measurements, the fastest ones, that the t-test is
+/// repeated on.
+const CROPS: [f64; 3] = [1.0, 0.9, 0.75];
+
+/// Untimed runs before the measurements.
+const WARMUP: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Class {
+    Fixed,
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// |t| is above `THRESHOLD` on some crop.
+    Leak,
+    NoLeak,
+    /// Too few measurements, or a t-test that could not be computed.
+    Inconclusive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Measurement {
+    pub class: Class,
+    pub nanos: f64,
+}
+
+/// Welch's t-test on the measurements below a percentile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TTest {
+    /// Fraction of the measurements kept.
+    pub kept: f64,
+    pub fixed: usize,
+    pub random: usize,
+    pub t: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub name: String,
+    pub measurements: Vec<Measurement>,
+    pub tests: Vec<TTest>,
+}
+
+/// Welch's t-statistic of two samples, positive if `a` is slower.
+pub fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+    let moments = |x: &[f64]| {
+        let mean = x.iter().sum::<f64>() / x.len() as f64;
+        let variance = x.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (x.len() - 1) as f64;
+        (mean, variance)
+    };
+    let ((mean_a, var_a), (mean_b, var_b)) = (moments(a), moments(b));
+
+    (mean_a - mean_b) / (var_a / a.len() as f64 + var_b / b.len() as f64).sqrt()
+}
+
+/// Times `operation` on `samples` inputs, each `fixed` or one drawn with
+/// `random`, at random. Panics if `samples` is below `MIN_SAMPLES`.
+pub fn measure<I, R: Rng>(
+    name: &str,
+    samples: usize,
+    rng: &mut R,
+    fixed: I,
+    mut random: impl FnMut(&mut R) -> I,
+    operation: impl Fn(&I),
+) -> Report
+where
+    I: Clone,
+{
+    assert!(
+        samples >= MIN_SAMPLES,
+        "{} samples are fewer than the {} a verdict needs",
+        samples,
+        MIN_SAMPLES
+    );
+
+    let inputs = (0..samples)
+        .map(|_| match rng.gen::<bool>() {
+            true => (Class::Fixed, fixed.clone()),
+            false => (Class::Random, random(rng)),
+        })
+        .collect::<Vec<_>>();
+
+    for (_, input) in inputs.iter().take(WARMUP) {
+        operation(black_box(input));
+    }
+
+    let measurements = inputs
+        .iter()
+        .map(|(class, input)| {
+            let now = Instant::now();
+            operation(black_box(input));
+            Measurement {
+                class: *class,
+                nanos: now.elapsed().as_nanos() as f64,
+            }
+        })
+        .collect();
+
+    Report::new(name, measurements)
+}
+
+impl Report {
+    pub fn new(name: &str, measurements: Vec<Measurement>) -> Self {
+        let mut sorted = measurements.iter().map(|m| m.nanos).collect::<Vec<_>>();
+        sorted.sort_by(f64::total_cmp);
+
+        let tests = CROPS
+            .iter()
+            .filter_map(|&kept| {
+                let cutoff =
+                    *sorted.get(((sorted.len() as f64 * kept) as usize).saturating_sub(1))?;
+                let class = |class: Class| {
+                    measurements
+                        .iter()
+                        .filter(|m| m.class == class && m.nanos <= cutoff)
+                        .map(|m| m.nanos)
+                        .collect::<Vec<_>>()
+                };
+                let (fixed, random) = (class(Class::Fixed), class(Class::Random));
+
+                // a t-statistic needs two measurements of each class
+                (fixed.len() > 1 && random.len() > 1).then(|| TTest {
+                    kept,
+                    fixed: fixed.len(),
+                    random: random.len(),
+                    t: welch_t(&fixed, &random),
+                })
+            })
+            .collect();
+
+        Report {
+            name: name.to_string(),
+            measurements,
+            tests,
+        }
+    }
+
+    /// The largest |t| over the crops, ignoring those that are NaN.
+    pub fn max_t(&self) -> f64 {
+        self.tests
+            .iter()
+            .map(|test| test.t.abs())
+            .fold(0.0, f64::max)
+    }
+
+    pub fn verdict(&self) -> Verdict {
+        // equal times in a class make t NaN, or infinite if the means differ
+        let computed =
+            self.tests.len() == CROPS.len() && self.tests.iter().all(|test| !test.t.is_nan());
+
+        if self.max_t() > THRESHOLD {
+            Verdict::Leak
+        } else if self.measurements.len() < MIN_SAMPLES || !computed {
+            Verdict::Inconclusive
+        } else {
+            Verdict::NoLeak
+        }
+    }
+
+    pub fn leaks(&self) -> bool {
+        self.verdict() == Verdict::Leak
+    }
+
+    /// The measurements, one per line, in the order they were taken.
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "index,class,nanos")?;
+        for (idx, measurement) in self.measurements.iter().enumerate() {
+            let class = match measurement.class {
+                Class::Fixed => "fixed",
+                Class::Random => "random",
+            };
+            writeln!(writer, "{},{},{}", idx, class, measurement.nanos)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self.verdict() {
+            Verdict::Leak => "FAIL, timing depends on the input",
+            Verdict::NoLeak => "PASS, no leak found",
+            Verdict::Inconclusive if self.measurements.len() < MIN_SAMPLES => {
+                "INCONCLUSIVE, too few measurements"
+            }
+            Verdict::Inconclusive => "INCONCLUSIVE, a t-test could not be computed",
+        };
+        writeln!(f, "{}: {}", self.name, verdict)?;
+
+        for test in &self.tests {
+            writeln!(
+                f,
+                "  fastest {:>3.0}%: t = {:>8.2} ({} fixed, {} random)",
+                test.kept * 100.0,
+                test.t,
+                test.fixed,
+                test.random
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn measurements(fixed: &[f64], random: &[f64]) -> Vec<Measurement> {
+        let class = |class, times: &[f64]| {
+            times
+                .iter()
+                .map(|&nanos| Measurement { class, nanos })
+                .collect::<Vec<_>>()
+        };
+        [class(Class::Fixed, fixed), class(Class::Random, random)].concat()
+    }
+
+    #[test]
+    fn test_welch_t() {
+        // means 2 and 5, variances 1 and 2.5
+        let t = welch_t(&[1.0, 2.0, 3.0], &[3.0, 4.0, 5.0, 6.0, 7.0]);
+        assert!((t - -3.0 / (1.0f64 / 3.0 + 0.5).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_crops_drop_the_tail() {
+        // the same times but for one outlier in the fixed class
+        let mut fixed = vec![10.0, 11.0, 12.0, 10.0, 11.0, 12.0, 10.0, 11.0, 12.0];
+        let random = fixed.clone();
+        fixed.push(1e6);
+
+        let report = Report::new("outlier", measurements(&fixed, &random));
+        assert_eq!(report.tests.len(), 3);
+        assert_eq!((report.tests[1].fixed, report.tests[1].random), (9, 9));
+        assert_eq!(report.tests[1].t, 0.0);
+        assert!(!report.leaks());
+
+        // but too few measurements to pass
+        assert_eq!(report.verdict(), Verdict::Inconclusive);
+
+        let slower = random.iter().map(|t| t + 5.0).collect::<Vec<_>>();
+        assert!(Report::new("slower", measurements(&slower, &random)).leaks());
+    }
+
+    #[test]
+    fn test_inconclusive_verdicts() {
+        let report = Report::new("empty", Vec::new());
+        assert!(report.tests.is_empty());
+        assert_eq!(report.verdict(), Verdict::Inconclusive);
+        assert!(report.to_string().starts_with("empty: INCONCLUSIVE"));
+
+        // no variance makes every t NaN
+        let same = vec![10.0; MIN_SAMPLES];
+        let report = Report::new("same", measurements(&same, &same));
+        assert!(report.tests.iter().all(|test| test.t.is_nan()));
+        assert_eq!(report.verdict(), Verdict::Inconclusive);
+
+        let noisy = (0..MIN_SAMPLES).map(|i| (i % 7) as f64).collect::<Vec<_>>();
+        let report = Report::new("noisy", measurements(&noisy, &noisy));
+        assert_eq!(report.verdict(), Verdict::NoLeak);
+        assert!(report.to_string().starts_with("noisy: PASS"));
+    }
+
+    #[test]
+    #[should_panic(expected = "fewer than the 100")]
+    fn test_measure_wants_enough_samples() {
+        let mut rng = StdRng::seed_from_u64(0);
+        measure("few", 0, &mut rng, 0u64, |rng| rng.gen(), |_| {});
+    }
+
+    #[test]
+    fn test_measure_and_csv() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // a loop whose length is the input
+        let report = measure(
+            "loop",
+            400,
+            &mut rng,
+            0u64,
+            |rng| rng.gen_range(1..20000),
+            |&n| {
+                black_box((0..n).fold(0u64, |acc, x| acc ^ black_box(x)));
+            },
+        );
+        assert_eq!(report.measurements.len(), 400);
+        assert!(report.leaks(), "{}", report);
+        assert!(report.to_string().starts_with("loop: FAIL"));
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 401);
+        assert!(csv.lines().nth(1).unwrap().starts_with("0,"));
+    }
+}
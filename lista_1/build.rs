@@ -1,13 +1,58 @@
+#[cfg(feature = "cuda")]
 extern crate cc;
 
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "src/conditions/table.rs"]
+mod table;
+
+use table::ConditionTable;
+
 fn main() {
     println!("cargo::rerun-if-changed=kernel.cu");
+    println!("cargo::rerun-if-changed=src/conditions/table.rs");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    for path in ["first_block", "second_block", "md4"] {
+        let table = condition_table(path);
+
+        fs::write(
+            Path::new(&out_dir).join(format!("{}.rs", path)),
+            table.to_rust(),
+        )
+        .unwrap();
+        fs::write(
+            Path::new(&out_dir).join(format!("{}.h", path)),
+            table.to_c(),
+        )
+        .unwrap();
+    }
+
+    #[cfg(feature = "cuda")]
+    build_kernel(&out_dir);
+}
+
+/// Parses the sufficient conditions in paths/`name`.txt.
+fn condition_table(name: &str) -> ConditionTable {
+    let path = format!("paths/{}.txt", name);
+    println!("cargo::rerun-if-changed={}", path);
+
+    let text = fs::read_to_string(&path).unwrap();
+    ConditionTable::parse(&text).unwrap_or_else(|error| panic!("{}: {}", path, error))
+}
 
+#[cfg(feature = "cuda")]
+fn build_kernel(out_dir: &str) {
     cc::Build::new()
         .cuda(true)
         .flag("-cudart=shared")
         .flag("-gencode")
         .flag("arch=compute_75,code=sm_75")
+        .include(out_dir)
         .file("kernel.cu")
         .compile("libmd5.a");
 
@@ -11,75 +11,8 @@
 #define STATE_SIZE_WORDS 4
 #define CANDIDATES_SIZE_WORDS 16
 
-#define A1_ONE_BITS 0x84200000u
-#define A1_ZERO_BITS 0x0A000820u
-#define D1_ONE_BITS 0x8C000800u
-#define D1_ZERO_BITS 0x02208026u
-#define D1_A1_SAME_BITS 0x701F10C0u
-#define C1_ONE_BITS 0xBE1F0966u
-#define C1_ZERO_BITS 0x40201080u
-#define C1_D1_SAME_BITS 0x00000018u
-#define B1_ONE_BITS 0xBA040010u
-#define B1_ZERO_BITS 0x443B19EEu
-#define B1_C1_SAME_BITS 0x00000601u
-#define A2_ONE_BITS 0x482F0E50u
-#define A2_ZERO_BITS 0xB41011AFu
-#define D2_ONE_BITS 0x04220C56u
-#define D2_ZERO_BITS 0x9A1113A9u
-#define C2_ONE_BITS 0x96011E01u
-#define C2_ZERO_BITS 0x083201C0u
-#define C2_D2_SAME_BITS 0x01808000u
-#define B2_ONE_BITS 0x843283C0u
-#define B2_ZERO_BITS 0x1B810001u
-#define B2_C2_SAME_BITS 0x00000002u
-#define A3_ONE_BITS 0x9C0101C1u
-#define A3_ZERO_BITS 0x03828202u
-#define A3_B2_SAME_BITS 0x00001000u
-#define D3_ONE_BITS 0x878383C0u
-#define D3_ZERO_BITS 0x00041003u
-#define C3_ONE_BITS 0x800583C3u
-#define C3_ZERO_BITS 0x00021000u
-#define C3_D3_SAME_BITS 0x00086000u
-#define B3_ONE_BITS 0x80081080u
-#define B3_ZERO_BITS 0x0007E000u
-#define B3_C3_SAME_BITS 0x7F000000u
-#define A4_ONE_BITS 0x3F0FE008u
-#define A4_ZERO_BITS 0xC0000080u
-#define D4_ONE_BITS 0x400BE088u
-#define D4_ZERO_BITS 0xBF040000u
-#define C4_ONE_BITS 0x7D000000u
-#define C4_ZERO_BITS 0x82008008u
-#define B4_ONE_BITS 0x20000000u
-#define B4_ZERO_BITS 0x80000000u
-#define A5_ZERO_BITS 0x80020000u
-#define A5_B4_SAME_BITS 0x00008008u
-#define D5_ONE_BITS 0x00020000u
-#define D5_ZERO_BITS 0x80000000u
-#define D5_A5_SAME_BITS 0x20000000u
-#define C5_ZERO_BITS 0x80020000u
-#define B5_ZERO_BITS 0x80000000u
-#define A6_ZERO_BITS 0x80000000u
-#define A6_B5_SAME_BITS 0x00020000u
-#define D6_ZERO_BITS 0x80000000u
-#define C6_ZERO_BITS 0x80000000u
-#define B6_C6_DIFFERENT_BITS 0x80000000u
-#define B12_D12_SAME_BITS 0x80000000u
-#define A13_C12_SAME_BITS 0x80000000u
-#define D13_B12_DIFFERENT_BITS 0x80000000u
-#define C13_A13_SAME_BITS 0x80000000u
-#define B13_D13_SAME_BITS 0x80000000u
-#define A14_C13_SAME_BITS 0x80000000u
-#define D14_B13_SAME_BITS 0x80000000u
-#define C14_A14_SAME_BITS 0x80000000u
-#define B14_D14_SAME_BITS 0x80000000u
-#define A15_C14_SAME_BITS 0x80000000u
-#define D15_B14_SAME_BITS 0x80000000u
-#define C15_A15_SAME_BITS 0x80000000u
-#define B15_D15_DIFFERENT_BITS 0x80000000u
-#define A16_ONE_BITS 0x02000000u
-#define A16_C15_SAME_BITS 0x80000000u
-#define D16_ONE_BITS 0x02000000u
-#define D16_B15_SAME_BITS 0x80000000u
+// Generated by build.rs from paths/second_block.txt
+#include "second_block.h"
 
 __device__ uint32_t _rotl(const uint32_t value, uint32_t shift) {
   if ((shift &= sizeof(value) * 8 - 1) == 0)
@@ -164,10 +97,30 @@ __device__ uint32_t reverse_FF(uint32_t a, uint32_t b, uint32_t c, uint32_t d,
   return _rotr((a - b), s) - F(b, c, d) - orig - t;
 }
 
+// The conditions Table 6 leaves implicit on the chaining value: Q[-2] = d0,
+// Q[-1] = c0 and Q[0] = b0.
+__device__ bool verify_chaining_value(const uint32_t md5_state[STATE_SIZE_WORDS]) {
+  uint32_t b = md5_state[1];
+  uint32_t c = md5_state[2];
+  uint32_t d = md5_state[3];
+
+  return verify_zero_bits(d, D0_ZERO_BITS) && verify_one_bits(c, C0_ONE_BITS) &&
+         verify_zero_bits(c, C0_ZERO_BITS) &&
+         verify_same_bits(c, d, C0_D0_SAME_BITS) &&
+         verify_zero_bits(b, B0_ZERO_BITS) &&
+         verify_same_bits(b, c, B0_C0_SAME_BITS);
+}
+
 __global__ void generate_candidates(const uint32_t md5_state[STATE_SIZE_WORDS],
                                     uint32_t *candidates, bool *found,
                                     size_t iterations, size_t seed) {
   size_t idx = blockIdx.x * blockDim.x + threadIdx.x;
+  found[idx] = false;
+
+  // no block can follow this chaining value along the path
+  if (!verify_chaining_value(md5_state)) {
+    return;
+  }
 
   curandState random_state;
   curand_init(seed, idx, 0, &random_state);
@@ -385,6 +338,11 @@ __global__ void generate_candidates(const uint32_t md5_state[STATE_SIZE_WORDS],
 
     a = HH(a, b, c, d, words[5], 0xFFFA3942, 4);
     d = HH(d, a, b, c, words[8], 0x8771F681, 11);
+
+    // φ34, the H of the step computing c9
+    if (!verify_one_bits(H(d, a, b), PHI34_ONE_BITS)) {
+      continue;
+    }
     c = HH(c, d, a, b, words[11], 0x6D9D6122, 16);
     b = HH(b, c, d, a, words[14], 0xFDE5380C, 23);
 
@@ -500,6 +458,21 @@ __global__ void generate_candidates(const uint32_t md5_state[STATE_SIZE_WORDS],
       continue;
     }
 
+    // c16
+    c = II(c, d, a, b, words[2], 0x2AD7D2BB, 15);
+    if (!verify_one_bits(c, C16_ONE_BITS)) {
+      continue;
+    }
+    if (!verify_same_bits(c, a, C16_A16_SAME_BITS)) {
+      continue;
+    }
+
+    // b16
+    b = II(b, c, d, a, words[9], 0xEB86D391, 21);
+    if (!verify_one_bits(b, B16_ONE_BITS)) {
+      continue;
+    }
+
     for (size_t j = 0; j < 16; j++) {
       candidates[idx * 16 + j] = words[j];
     }
@@ -0,0 +1,395 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::strategy::{self, Strategy};
+use super::CandidateBackend;
+use crate::conditions::table::ConditionTable;
+use crate::conditions::verify_zero_bits;
+use crate::conditions::{first_block, second_block};
+use crate::family::{Block, Conditions, MdFamily};
+use crate::md5::Md5;
+use crate::simd::Kernel;
+use crate::telemetry::Survival;
+
+/// How often a round 1 step of the first block may redraw its message word
+/// before the trial is abandoned.
+const MAX_REDRAWS: usize = 1 << 10;
+
+/// Multithreaded pure-Rust port of the `generate_candidates` kernel, plus the
+/// first-block search the kernel does not cover.
+///
+/// Every thread runs up to `iterations` trials and stops at its first
+/// candidate, so one batch yields at most `threads` candidates. Second
+/// blocks are searched with `strategy`; `Strategy::kernel()` runs the exact
+/// port of the kernel. Candidates are verified `lanes` blocks at a time.
+#[derive(Debug, Clone)]
+pub struct CpuBackend {
+    pub threads: usize,
+    /// Trials per thread in one batch.
+    pub iterations: usize,
+    pub strategy: Strategy,
+    /// The lane kernel `compress_batch` runs.
+    pub lanes: Kernel,
+    /// How deep the trials since the last `take_survival` got, shared by
+    /// clones.
+    pub survival: Arc<Mutex<Survival>>,
+}
+
+impl Default for CpuBackend {
+    fn default() -> Self {
+        CpuBackend {
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            iterations: 1 << 22,
+            strategy: Strategy::default(),
+            lanes: Kernel::detect(),
+            survival: Arc::default(),
+        }
+    }
+}
+
+impl CpuBackend {
+    fn run_batch(
+        &self,
+        state: &[u32; 4],
+        seed: u64,
+        check: impl Fn(&[u32; 4], &mut [u32; 16], &mut StdRng, &mut Survival) -> bool + Sync,
+    ) -> Vec<[u32; 16]> {
+        let check = &check;
+
+        thread::scope(|scope| {
+            let workers = (0..self.threads)
+                .map(|idx| {
+                    let mut rng = StdRng::seed_from_u64(thread_seed(seed, idx));
+                    scope.spawn(move || search(state, &mut rng, self.iterations, check))
+                })
+                .collect::<Vec<_>>();
+
+            let mut candidates = Vec::new();
+            for worker in workers {
+                let (candidate, survival) = worker.join().unwrap();
+                candidates.extend(candidate);
+                self.survival.lock().unwrap().merge(&survival);
+            }
+
+            candidates
+        })
+    }
+}
+
+impl CandidateBackend for CpuBackend {
+    fn generate_candidates(&self, state: &[u32; 4], seed: u64) -> Vec<[u32; 16]> {
+        if self.strategy.is_kernel() {
+            let conditions = Conditions::new::<Md5>(&second_block::table());
+            return self.run_batch(state, seed, |state, words, _, survival| {
+                counted(check_candidate(state, words, &conditions), survival)
+            });
+        }
+
+        self.generate_path_candidates(state, &second_block::table(), seed)
+    }
+
+    fn generate_first_block_candidates(&self, state: &[u32; 4], seed: u64) -> Vec<[u32; 16]> {
+        let conditions = Conditions::new::<Md5>(&first_block::table());
+        self.run_batch(state, seed, |state, words, rng, survival| {
+            counted(check_first_block(state, words, &conditions, rng), survival)
+        })
+    }
+
+    /// Always searches with `strategy`, the kernel strategy being Wang's
+    /// second block only.
+    fn generate_path_candidates(
+        &self,
+        state: &[u32; 4],
+        table: &ConditionTable,
+        seed: u64,
+    ) -> Vec<[u32; 16]> {
+        let conditions = Conditions::new::<Md5>(table);
+        self.run_batch(state, seed, |state, words, _, survival| {
+            strategy::check_candidate(state, words, &self.strategy, &conditions, survival)
+        })
+    }
+
+    fn compress_batch(&self, states: &[[u32; 4]], blocks: &[[u32; 16]]) -> Vec<[u32; 4]> {
+        self.lanes.compress_many(states, blocks)
+    }
+
+    fn take_survival(&self) -> Survival {
+        std::mem::take(&mut self.survival.lock().unwrap())
+    }
+}
+
+/// Derives the seed of one worker thread, like `curand_init(seed, idx, ...)`
+/// gives every CUDA thread its own subsequence.
+fn thread_seed(seed: u64, idx: usize) -> u64 {
+    seed ^ (idx as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15)
+}
+
+fn search(
+    state: &[u32; 4],
+    rng: &mut StdRng,
+    iterations: usize,
+    check: impl Fn(&[u32; 4], &mut [u32; 16], &mut StdRng, &mut Survival) -> bool,
+) -> (Option<[u32; 16]>, Survival) {
+    let mut words = [0u32; 16];
+    let mut survival = Survival::default();
+
+    for _ in 0..iterations {
+        rng.fill(&mut words);
+
+        if check(state, &mut words, rng, &mut survival) {
+            return (Some(words), survival);
+        }
+    }
+
+    (None, survival)
+}
+
+/// Records a trial that got to step `depth` and tells whether it is a
+/// candidate.
+fn counted(depth: usize, survival: &mut Survival) -> bool {
+    survival.record(depth);
+    depth == Md5::STEPS
+}
+
+/// The algorithm of the kernel: single-message modification of round 1 on
+/// `words`, then the conditions of rounds 2 to 4 in order. Returns the last
+/// step up to which every condition holds, `Md5::STEPS` if `words` is a
+/// candidate.
+pub(crate) fn check_candidate(
+    state: &[u32; 4],
+    words: &mut [u32; 16],
+    conditions: &Conditions,
+) -> usize {
+    let mut block = Block::<Md5>::new(state, words);
+    block.round_1(conditions);
+    *words = block.m;
+
+    block.reach(17..=Md5::STEPS, conditions)
+}
+
+/// Round 1 step of the first block computing Q[t]. Forces the conditions on
+/// Q[t] and redraws its word until the rotation carries the difference the
+/// path expects. Returns `false` if no word did so within `MAX_REDRAWS`
+/// draws.
+fn modify_first_block_step(
+    block: &mut Block<Md5>,
+    t: usize,
+    conditions: &Conditions,
+    rng: &mut impl Rng,
+) -> bool {
+    let (delta_t, delta_r) = first_block::ROUND_1_ROTATIONS[t - 1];
+    let s = Md5::shift(t - 1);
+
+    for _ in 0..MAX_REDRAWS {
+        block.force(t, conditions);
+
+        let rotated = block.q(t).wrapping_sub(block.q(t - 1));
+        let delta = rotated
+            .rotate_right(s)
+            .wrapping_add(delta_t)
+            .rotate_left(s)
+            .wrapping_sub(rotated);
+
+        if delta == delta_r {
+            block.fix_word(t);
+            return true;
+        }
+
+        block.m[t - 1] = rng.gen();
+    }
+
+    false
+}
+
+/// Round 1 message modification of the first block. Returns the block up to
+/// b4, or `None` if some step could not be fixed.
+fn first_block_round_1(
+    state: &[u32; 4],
+    words: &mut [u32; 16],
+    conditions: &Conditions,
+    rng: &mut impl Rng,
+) -> Option<Block<Md5>> {
+    let mut block = Block::<Md5>::new(state, words);
+
+    for t in 1..=16 {
+        if !modify_first_block_step(&mut block, t, conditions, rng) {
+            return None;
+        }
+        if t == 12 && !verify_zero_bits(block.m[11], first_block::M11_ZERO_BITS) {
+            return None;
+        }
+    }
+
+    *words = block.m;
+    Some(block)
+}
+
+/// First-block counterpart of `check_candidate`: applies the round 1 message
+/// modification to `words`, drawing replacement words from `rng` where a
+/// rotation goes wrong, and checks the conditions of rounds 2 to 4. Returns
+/// 0 if round 1 could not be fixed.
+pub(crate) fn check_first_block(
+    state: &[u32; 4],
+    words: &mut [u32; 16],
+    conditions: &Conditions,
+    rng: &mut impl Rng,
+) -> usize {
+    match first_block_round_1(state, words, conditions, rng) {
+        Some(mut block) => block.reach(17..=Md5::STEPS, conditions),
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md5::{compress, INITIAL_STATE};
+    use crate::published::{blocks, wang};
+
+    #[test]
+    fn published_second_blocks_keep_their_words() {
+        let state = compress(&INITIAL_STATE, &wang().m0);
+        let conditions = Conditions::new::<Md5>(&second_block::table());
+
+        // the published second blocks break c16,26 = 1 and φ34,32 = 1 (see
+        // collisions/published.txt)
+        let m1s = [(wang().m1, 62), (blocks("wang-2004-2").m1, 34)];
+
+        for (m1, depth) in m1s {
+            // message modification must leave a block that already satisfies
+            // the round 1 conditions untouched
+            let mut words = m1;
+            assert_eq!(check_candidate(&state, &mut words, &conditions), depth);
+            assert_eq!(words, m1);
+        }
+    }
+
+    #[test]
+    fn message_modification_is_idempotent() {
+        let state = compress(&INITIAL_STATE, &wang().m0);
+        let conditions = Conditions::new::<Md5>(&second_block::table());
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..1000 {
+            let mut words = [0u32; 16];
+            rng.fill(&mut words);
+            check_candidate(&state, &mut words, &conditions);
+
+            // running the modification again must not change anything
+            let mut again = words;
+            check_candidate(&state, &mut again, &conditions);
+            assert_eq!(again, words);
+        }
+    }
+
+    #[test]
+    fn published_first_block_is_candidate() {
+        let conditions = Conditions::new::<Md5>(&first_block::table());
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // M0 follows the path, so message modification must neither touch it
+        // nor redraw any of its words
+        let mut words = wang().m0;
+        assert_eq!(
+            check_first_block(&INITIAL_STATE, &mut words, &conditions, &mut rng),
+            Md5::STEPS
+        );
+        assert_eq!(words, wang().m0);
+    }
+
+    #[test]
+    fn first_block_modification_is_idempotent() {
+        let conditions = Conditions::new::<Md5>(&first_block::table());
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..1000 {
+            let mut words = [0u32; 16];
+            rng.fill(&mut words);
+
+            let Some(block) =
+                first_block_round_1(&INITIAL_STATE, &mut words, &conditions, &mut rng)
+            else {
+                continue;
+            };
+
+            let mut again = words;
+            let block_again =
+                first_block_round_1(&INITIAL_STATE, &mut again, &conditions, &mut rng).unwrap();
+            assert_eq!(block_again.values(16), block.values(16));
+            assert_eq!(again, words);
+        }
+    }
+
+    #[test]
+    fn batches_are_reproducible() {
+        let state = compress(&INITIAL_STATE, &wang().m0);
+        let backend = CpuBackend {
+            threads: 4,
+            iterations: 1 << 12,
+            ..CpuBackend::default()
+        };
+
+        assert_eq!(
+            backend.generate_candidates(&state, 42),
+            backend.generate_candidates(&state, 42)
+        );
+        assert_eq!(
+            backend.generate_first_block_candidates(&INITIAL_STATE, 42),
+            backend.generate_first_block_candidates(&INITIAL_STATE, 42)
+        );
+    }
+
+    #[test]
+    fn batches_count_their_trials() {
+        let state = compress(&INITIAL_STATE, &wang().m0);
+
+        for strategy in [Strategy::kernel(), Strategy::default()] {
+            let backend = CpuBackend {
+                threads: 2,
+                iterations: 1 << 10,
+                strategy,
+                ..CpuBackend::default()
+            };
+            backend.generate_candidates(&state, 7);
+
+            // the kernel makes one trial per iteration, tunnels several
+            let survival = backend.take_survival();
+            assert!(survival.trials() >= 2 << 10);
+            assert!(0 < survival.reached(18) && survival.reached(18) < survival.trials());
+            assert_eq!(backend.take_survival().trials(), 0);
+        }
+    }
+
+    #[test]
+    fn batches_are_verified_like_compress() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let states = (0..19).map(|_| rng.gen()).collect::<Vec<[u32; 4]>>();
+        let blocks = (0..19).map(|_| rng.gen()).collect::<Vec<[u32; 16]>>();
+
+        for lanes in Kernel::available() {
+            let backend = CpuBackend {
+                lanes,
+                ..CpuBackend::default()
+            };
+
+            // every count, so that full sets of lanes and the rest both run
+            for count in 0..=blocks.len() {
+                let expected = (0..count)
+                    .map(|idx| compress(&states[idx], &blocks[idx]))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    backend.compress_batch(&states[..count], &blocks[..count]),
+                    expected,
+                    "{:?}, {} blocks",
+                    lanes,
+                    count
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use super::{CandidateBackend, CpuBackend};
+use crate::conditions::table::ConditionTable;
+
+const CANDIDATES_SIZE_WORDS: usize = 16;
+
+#[link(name = "md5", kind = "static")]
+extern "C" {
+    fn generate_candidates_cuda(
+        state: *const u32,
+        candidates: *mut u32,
+        found: *mut bool,
+        iterations: usize,
+        threads_per_block: usize,
+        block_dim: usize,
+        seed: usize,
+    );
+}
+
+/// Runs the candidate search of kernel.cu on the GPU.
+#[derive(Debug, Clone)]
+pub struct CudaBackend {
+    /// Trials per GPU thread in one batch.
+    pub iterations: usize,
+    pub threads_per_block: usize,
+    pub block_dim: usize,
+}
+
+impl Default for CudaBackend {
+    fn default() -> Self {
+        CudaBackend {
+            iterations: 5000000,
+            threads_per_block: 256,
+            block_dim: 352,
+        }
+    }
+}
+
+impl CandidateBackend for CudaBackend {
+    fn generate_candidates(&self, state: &[u32; 4], seed: u64) -> Vec<[u32; 16]> {
+        let batch_size = self.threads_per_block * self.block_dim;
+
+        let mut candidates = vec![0u32; CANDIDATES_SIZE_WORDS * batch_size];
+        let mut found = vec![false; batch_size];
+
+        unsafe {
+            generate_candidates_cuda(
+                state.as_ptr(),
+                candidates.as_mut_ptr(),
+                found.as_mut_ptr(),
+                self.iterations,
+                self.threads_per_block,
+                self.block_dim,
+                seed as usize,
+            );
+        }
+
+        found
+            .iter()
+            .zip(candidates.chunks_exact(CANDIDATES_SIZE_WORDS))
+            .filter(|(found, _)| **found)
+            .map(|(_, candidate)| candidate.try_into().unwrap())
+            .collect()
+    }
+
+    fn generate_first_block_candidates(&self, state: &[u32; 4], seed: u64) -> Vec<[u32; 16]> {
+        // kernel.cu only implements the second block
+        CpuBackend::default().generate_first_block_candidates(state, seed)
+    }
+
+    fn generate_path_candidates(
+        &self,
+        state: &[u32; 4],
+        table: &ConditionTable,
+        seed: u64,
+    ) -> Vec<[u32; 16]> {
+        // kernel.cu has Wang's second-block conditions compiled in
+        CpuBackend::default().generate_path_candidates(state, table, seed)
+    }
+}
@@ -0,0 +1,59 @@
+pub mod cpu;
+#[cfg(feature = "cuda")]
+pub mod cuda;
+pub mod strategy;
+
+pub use cpu::CpuBackend;
+#[cfg(feature = "cuda")]
+pub use cuda::CudaBackend;
+pub use strategy::{Strategy, Tunnel};
+
+use crate::conditions::table::ConditionTable;
+use crate::simd;
+use crate::telemetry::Survival;
+
+/// Backend used by `first_step` and `second_step` when none is given
+/// explicitly.
+#[cfg(not(feature = "cuda"))]
+pub type DefaultBackend = CpuBackend;
+#[cfg(feature = "cuda")]
+pub type DefaultBackend = CudaBackend;
+
+/// A source of first- and second-block candidates.
+///
+/// A candidate is a message block whose a1..b4 values were fixed by message
+/// modification and which passed the condition checks of the later rounds.
+/// It still has to be verified by hashing both sides of the pair.
+pub trait CandidateBackend {
+    /// Runs one batch of the second-block search from the chaining value
+    /// `state` and returns every candidate found in it. The same `seed`
+    /// always yields the same batch.
+    fn generate_candidates(&self, state: &[u32; 4], seed: u64) -> Vec<[u32; 16]>;
+
+    /// Like `generate_candidates`, but searches for a first block (M0) of
+    /// the path starting from the chaining value `state`.
+    fn generate_first_block_candidates(&self, state: &[u32; 4], seed: u64) -> Vec<[u32; 16]>;
+
+    /// Like `generate_candidates`, but for the differential path `table`
+    /// instead of Wang's second block, e.g. a path loaded from a file or a
+    /// near-collision block of a chosen-prefix collision. Backends that only
+    /// search Wang's paths fall back to `CpuBackend`.
+    fn generate_path_candidates(
+        &self,
+        state: &[u32; 4],
+        table: &ConditionTable,
+        seed: u64,
+    ) -> Vec<[u32; 16]>;
+
+    /// Compresses `blocks[i]` from `states[i]` for every i: verifies the
+    /// candidates of a batch, and their partners, by hashing them.
+    fn compress_batch(&self, states: &[[u32; 4]], blocks: &[[u32; 16]]) -> Vec<[u32; 4]> {
+        simd::compress_many(states, blocks)
+    }
+
+    /// How deep the trials of the batches since the last call got, for
+    /// telemetry. Backends that do not keep count report no trials.
+    fn take_survival(&self) -> Survival {
+        Survival::default()
+    }
+}
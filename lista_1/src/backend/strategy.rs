@@ -0,0 +1,283 @@
+// Second-block search on the Q values, with multi-message modification and
+// tunnels on top of the single-message modification of the kernel.
+//
+// * Multi-message modification (Wang): a broken a5, d5 or c5 is corrected
+//   by changing the round 1 word that feeds its step, m1, m6 or m11. That
+//   moves the round 1 value computed from the word (d1, c2 or b3), and the
+//   next four words are recomputed so that every other round 1 value stays
+//   put. None of those words is used in round 2 before the corrected step,
+//   so the correction holds as long as the moved value still meets its own
+//   conditions. b5..b6 are fed by words whose correction would disturb an
+//   earlier round 2 step and are left to chance.
+// * Tunnels (Klima): flipping bit i of Q[k] where Q[k+1],i = 0 and
+//   Q[k+2],i = 1 does not change the boolean functions of steps k+2 and
+//   k+3, so only words k-1, k and k+3 have to follow. For the Q9 tunnel
+//   those are m8, m9 and m12, which round 2 uses from a7 on: every subset of
+//   its bits is a new block with a1..b6 intact. The Q4 tunnel moves m4,
+//   used by b6, and the Q10 tunnel m10, used by d6, so their subsets are
+//   checked from round 2 on again.
+//
+// Both apply to second blocks and to the blocks of `--first-path` /
+// `--second-path` searches only. Wang's first block is searched by
+// `check_first_block`, whose round 1 modification redraws words instead:
+// its a5..c5 and tunnel conditions differ, and none of it is ported here.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::family::{Block, Conditions};
+use crate::md5::Md5;
+use crate::telemetry::Survival;
+
+/// Round 2 values corrected by multi-message modification.
+const MULTI_MESSAGE_STEPS: [usize; 3] = [17, 18, 19];
+
+/// A tunnel, named after the Q value whose bits it flips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tunnel {
+    Q4,
+    Q9,
+    Q10,
+}
+
+impl Tunnel {
+    fn q(&self) -> usize {
+        match self {
+            Tunnel::Q4 => 4,
+            Tunnel::Q9 => 9,
+            Tunnel::Q10 => 10,
+        }
+    }
+}
+
+impl fmt::Display for Tunnel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "q{}", self.q())
+    }
+}
+
+impl FromStr for Tunnel {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Tunnel, String> {
+        [Tunnel::Q4, Tunnel::Q9, Tunnel::Q10]
+            .into_iter()
+            .find(|tunnel| text.eq_ignore_ascii_case(&tunnel.to_string()))
+            .ok_or_else(|| format!("unknown tunnel '{}', expected q4, q9 or q10", text))
+    }
+}
+
+/// How `CpuBackend` searches for second blocks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Strategy {
+    /// Correct a5, d5 and c5 by multi-message modification instead of
+    /// drawing new blocks until they hold.
+    pub multi_message: bool,
+    /// Tunnels to enumerate once a block meets every condition up to b6,
+    /// outermost first.
+    pub tunnels: Vec<Tunnel>,
+}
+
+impl Strategy {
+    /// Single-message modification only, as in kernel.cu.
+    pub fn kernel() -> Strategy {
+        Strategy {
+            multi_message: false,
+            tunnels: Vec::new(),
+        }
+    }
+
+    pub(crate) fn is_kernel(&self) -> bool {
+        *self == Strategy::kernel()
+    }
+}
+
+impl Default for Strategy {
+    /// Every technique; the tunnels that recheck the most run outermost.
+    fn default() -> Self {
+        Strategy {
+            multi_message: true,
+            tunnels: vec![Tunnel::Q4, Tunnel::Q10, Tunnel::Q9],
+        }
+    }
+}
+
+impl Block<Md5> {
+    /// Enumerates every subset of the bits of `tunnels` and returns `true`
+    /// with the block set to the first one that meets all conditions. Every
+    /// subset tried counts as a trial of `survival`.
+    fn explore(
+        &mut self,
+        tunnels: &[Tunnel],
+        conditions: &Conditions,
+        survival: &mut Survival,
+    ) -> bool {
+        let Some((tunnel, rest)) = tunnels.split_first() else {
+            let depth = self.reach(17..=64, conditions);
+            survival.record(depth);
+            return depth == 64;
+        };
+
+        let k = tunnel.q();
+        let bits = conditions.free(k) & !self.q(k + 1) & self.q(k + 2);
+        let mut subset = 0u32;
+
+        loop {
+            let mut block = *self;
+            block.tunnel(k, subset);
+
+            if block.explore(rest, conditions, survival) {
+                *self = block;
+                return true;
+            }
+
+            // next subset of `bits` in counting order
+            subset = subset.wrapping_sub(bits) & bits;
+            if subset == 0 {
+                return false;
+            }
+        }
+    }
+}
+
+/// Searches from the random block `words` with `strategy`. Returns `true`
+/// with `words` set to the candidate if one is found. Records in `survival`
+/// how deep the trial, or each block its tunnels lead to, got.
+pub(crate) fn check_candidate(
+    state: &[u32; 4],
+    words: &mut [u32; 16],
+    strategy: &Strategy,
+    conditions: &Conditions,
+    survival: &mut Survival,
+) -> bool {
+    let mut block = Block::<Md5>::new(state, words);
+    block.round_1(conditions);
+
+    if strategy.multi_message {
+        if let Some(&t) = MULTI_MESSAGE_STEPS
+            .iter()
+            .find(|&&t| !block.correct(t, conditions))
+        {
+            survival.record(t - 1);
+            return false;
+        }
+    }
+
+    // a5..b6 must hold before the tunnels are worth enumerating
+    let depth = block.reach(17..=24, conditions);
+    if depth < 24 {
+        survival.record(depth);
+        return false;
+    }
+
+    if block.explore(&strategy.tunnels, conditions, survival) {
+        *words = block.m;
+        return true;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::conditions::second_block;
+    use crate::conditions::table::Variable;
+    use crate::md5::{compress, INITIAL_STATE};
+    use crate::published::wang;
+
+    /// Random blocks that meet every condition up to b6.
+    fn round_2_blocks(state: &[u32; 4], conditions: &Conditions) -> Vec<Block<Md5>> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut blocks = Vec::new();
+
+        while blocks.len() < 4 {
+            let mut block = Block::<Md5>::new(state, &rng.gen());
+            block.round_1(conditions);
+
+            if MULTI_MESSAGE_STEPS
+                .iter()
+                .all(|&t| block.correct(t, conditions))
+                && block.check(17..=24, conditions)
+            {
+                blocks.push(block);
+            }
+        }
+
+        blocks
+    }
+
+    #[test]
+    fn published_second_block_is_candidate() {
+        let state = compress(&INITIAL_STATE, &wang().m0);
+        // but for c16,26 = 1, which the block breaks (see
+        // collisions/published.txt)
+        let mut table = second_block::table();
+        table
+            .conditions
+            .retain(|condition| (condition.variable, condition.bit) != (Variable::Q(63), 25));
+        let conditions = Conditions::new::<Md5>(&table);
+
+        let mut words = wang().m1;
+        let mut survival = Survival::default();
+        assert!(check_candidate(
+            &state,
+            &mut words,
+            &Strategy::default(),
+            &conditions,
+            &mut survival
+        ));
+        assert_eq!(words, wang().m1);
+        assert_eq!(survival.reached(64), 1);
+    }
+
+    #[test]
+    fn multi_message_modification_keeps_round_1() {
+        let state = compress(&INITIAL_STATE, &wang().m0);
+        let conditions = Conditions::new::<Md5>(&second_block::table());
+
+        for block in round_2_blocks(&state, &conditions) {
+            // the words must still produce the Q values the conditions hold on
+            let mut again = Block::<Md5>::new(&state, &block.m);
+            assert!(again.check(1..=24, &conditions));
+            assert_eq!(again.values(24), block.values(24));
+        }
+    }
+
+    #[test]
+    fn q9_tunnel_keeps_round_2() {
+        let state = compress(&INITIAL_STATE, &wang().m0);
+        let conditions = Conditions::new::<Md5>(&second_block::table());
+
+        for block in round_2_blocks(&state, &conditions) {
+            let bits = conditions.free(9) & !block.q(10) & block.q(11);
+            assert_ne!(bits, 0);
+
+            let mut tunneled = block;
+            tunneled.tunnel(9, bits);
+            assert!(tunneled.check(1..=24, &conditions));
+
+            for t in (1..=24).filter(|&t| t != 9) {
+                assert_eq!(tunneled.q(t), block.q(t));
+            }
+            for w in 0..16 {
+                assert_eq!(tunneled.m[w] != block.m[w], [8, 9, 12].contains(&w));
+            }
+        }
+    }
+
+    #[test]
+    fn tunnels_parse_their_names() {
+        for tunnel in [Tunnel::Q4, Tunnel::Q9, Tunnel::Q10] {
+            assert_eq!(tunnel.to_string().parse(), Ok(tunnel));
+        }
+        assert_eq!("Q9".parse(), Ok(Tunnel::Q9));
+        assert!("q5".parse::<Tunnel>().is_err());
+    }
+}
@@ -0,0 +1,829 @@
+// Chosen-prefix collisions after Stevens, Lenstra and de Weger, "Chosen-prefix
+// collisions for MD5 and colliding X.509 certificates for different
+// identities".
+//
+// The two prefixes are zero-padded to the same length, 8 bytes short of a
+// block boundary. A birthday search picks the last 8 bytes of both blocks
+// such that `a` and `c - d` of the chaining values collide, leaving
+//
+//     δIHV = IHV' - IHV = (0, δb, δc, δc).
+//
+// Near-collision blocks remove the rest. A block pair that differs by
+// δm11 = ±2^(p-10) only can follow a path without differences from round 3
+// on up to step 61, where m11 adds ±2^p to Q62 and from there to Q63 and Q64:
+// the block adds ±2^p to b, c and d, and with a difference in the last
+// boolean function optionally ±2^(p+21) to b alone. `plan` writes δc and
+// δb - δc in non-adjacent form and turns every term into one or two blocks.
+//
+// Each block needs a differential path from the chaining values it starts
+// at, which HashClash's path construction builds; this module does not
+// construct paths and takes them from a `PathFamily` instead. A path file
+// gives its message difference and what the block adds to the chaining
+// values in `delta` lines (see conditions/table.rs), and the block pairs
+// follow those. After every block the difference left is planned again, so
+// a path may remove another difference than the one planned for it.
+//
+// The whole search is a `ChosenPrefix` value that `step`s through the
+// birthday search and the blocks and serializes to JSON, so it can be saved
+// after every step and resumed.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::CandidateBackend;
+use crate::conditions::table::{ConditionTable, ParseError};
+use crate::differential::Differential;
+use crate::md5::{bytes_to_words, chaining_value, compress, digest, words_to_bytes};
+use crate::search::save_json;
+
+/// Length of the birthday bytes that end the padded prefixes.
+const BIRTHDAY_BYTES: usize = 8;
+
+/// Trails every thread walks in one `Birthday::step`.
+const TRAILS_PER_THREAD: u64 = 1 << 10;
+
+/// Trails this many times longer than expected are assumed to be stuck in a
+/// cycle and dropped.
+const MAX_TRAIL_FACTOR: u64 = 20;
+
+/// One term of the difference a near-collision block removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Elimination {
+    /// The block adds `sign * 2^p` to b, c and d.
+    pub p: u32,
+    pub sign: i32,
+    /// `sign * 2^(p + 21)` the block adds to b alone, if any.
+    pub extra: Option<i32>,
+}
+
+impl Elimination {
+    /// Modular difference the block adds to the chaining values.
+    pub fn delta(&self) -> [u32; 4] {
+        let term = signed_power(self.p, self.sign);
+        let extra = self
+            .extra
+            .map_or(0, |sign| signed_power((self.p + 21) % 32, sign));
+
+        [0, term.wrapping_add(extra), term, term]
+    }
+
+    /// Modular difference of word 11 of the block pair, which the `delta`
+    /// line of its path gives.
+    pub fn message_delta(&self) -> u32 {
+        signed_power((self.p + 22) % 32, self.sign)
+    }
+}
+
+fn signed_power(bit: u32, sign: i32) -> u32 {
+    if sign < 0 {
+        (1u32 << bit).wrapping_neg()
+    } else {
+        1 << bit
+    }
+}
+
+/// Signed terms `(bit, sign)` of the non-adjacent form of `value` modulo
+/// 2^32.
+pub fn naf(value: u32) -> Vec<(u32, i32)> {
+    let mut terms = Vec::new();
+    let mut value = value as u64;
+
+    for bit in 0..32 {
+        if value >> bit & 1 == 1 {
+            if value >> (bit + 1) & 1 == 1 && bit < 31 {
+                terms.push((bit, -1));
+                value += 1 << bit;
+            } else {
+                terms.push((bit, 1));
+                value -= 1 << bit;
+            }
+        }
+    }
+
+    terms
+}
+
+/// The near-collision blocks that remove `delta`, or `None` if it is not of
+/// the form (0, δb, δc, δc).
+pub fn plan(delta: &[u32; 4]) -> Option<Vec<Elimination>> {
+    let [da, db, dc, dd] = *delta;
+    if da != 0 || dc != dd {
+        return None;
+    }
+
+    let mut extras = naf(db.wrapping_sub(dc));
+    let mut plan = Vec::new();
+
+    for (p, sign) in naf(dc) {
+        let paired = extras.iter().position(|&(q, _)| q == (p + 21) % 32);
+
+        plan.push(Elimination {
+            p,
+            sign: -sign,
+            extra: paired.map(|idx| -extras.remove(idx).1),
+        });
+    }
+
+    // a term of δb - δc without a partner in δc takes a block that carries
+    // it and a second one that takes back the difference the first adds to
+    // c and d
+    for (q, sign) in extras {
+        let p = (q + 11) % 32;
+
+        plan.push(Elimination {
+            p,
+            sign: 1,
+            extra: Some(-sign),
+        });
+        plan.push(Elimination {
+            p,
+            sign: -1,
+            extra: None,
+        });
+    }
+
+    Some(plan)
+}
+
+/// Parallel distinguished-point search (van Oorschot and Wiener) for two
+/// birthday suffixes, one per prefix, whose chaining values agree on `a`
+/// and `c - d`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Birthday {
+    ihv: [u32; 4],
+    ihv_prim: [u32; 4],
+    /// The first 14 words of the last block of either padded prefix.
+    tail: Vec<u32>,
+    tail_prim: Vec<u32>,
+    /// Low bits of `(a, c - d)` that have to agree; 64 in a real search.
+    pub bits: u32,
+    seed: u64,
+    /// Trails walked so far.
+    pub trails: u64,
+    /// Distinguished point -> (start, length) of the trail that reached it.
+    points: HashMap<u64, (u64, u64)>,
+}
+
+impl Birthday {
+    /// `padded` and `padded_prim` must have the same length, 8 bytes short
+    /// of a block boundary.
+    pub fn new(padded: &[u8], padded_prim: &[u8], bits: u32, seed: u64) -> Birthday {
+        assert!((4..=64).contains(&bits), "bits out of range");
+        assert_eq!(padded.len(), padded_prim.len());
+        assert_eq!((padded.len() + BIRTHDAY_BYTES) % 64, 0, "bad prefix length");
+
+        let split = padded.len() + BIRTHDAY_BYTES - 64;
+        let tail = |prefix: &[u8]| {
+            let mut block = prefix[split..].to_vec();
+            block.resize(64, 0);
+            bytes_to_words(&block)[..14].to_vec()
+        };
+
+        Birthday {
+            ihv: chaining_value(&padded[..split]),
+            ihv_prim: chaining_value(&padded_prim[..split]),
+            tail: tail(padded),
+            tail_prim: tail(padded_prim),
+            bits,
+            seed,
+            trails: 0,
+            points: HashMap::new(),
+        }
+    }
+
+    /// The words of the last block for the birthday value `x` and the
+    /// chaining value it starts from. The lowest bit of `x` selects the
+    /// prefix.
+    fn block(&self, x: u64) -> ([u32; 4], [u32; 16]) {
+        let (ihv, tail) = if x & 1 == 0 {
+            (self.ihv, &self.tail)
+        } else {
+            (self.ihv_prim, &self.tail_prim)
+        };
+
+        let mut words = [0; 16];
+        words[..14].copy_from_slice(tail);
+        words[14] = x as u32;
+        words[15] = (x >> 32) as u32;
+
+        (ihv, words)
+    }
+
+    /// Chaining value after the last block for `x`.
+    pub fn ihv(&self, x: u64) -> [u32; 4] {
+        let (ihv, words) = self.block(x);
+        compress(&ihv, &words)
+    }
+
+    /// The random function the search walks.
+    fn f(&self, x: u64) -> u64 {
+        let [a, _, c, d] = self.ihv(x);
+        let value = (a as u64) << 32 | c.wrapping_sub(d) as u64;
+
+        value & (u64::MAX >> (64 - self.bits))
+    }
+
+    fn distinguished_bits(&self) -> u32 {
+        self.bits / 4
+    }
+
+    fn is_distinguished(&self, x: u64) -> bool {
+        x >> (self.bits - self.distinguished_bits()) == 0
+    }
+
+    fn trail_start(&self, trail: u64) -> u64 {
+        let seed = self.seed ^ trail.wrapping_mul(0x9E3779B97F4A7C15);
+        StdRng::seed_from_u64(seed).gen::<u64>() & (u64::MAX >> (64 - self.bits))
+    }
+
+    /// Walks from `start` to a distinguished point; returns it and the
+    /// length of the walk.
+    fn walk(&self, start: u64) -> Option<(u64, u64)> {
+        let max_length = MAX_TRAIL_FACTOR << self.distinguished_bits();
+        let mut x = start;
+
+        for length in 1..=max_length {
+            x = self.f(x);
+            if self.is_distinguished(x) {
+                return Some((x, length));
+            }
+        }
+
+        None
+    }
+
+    /// Walks the trails `(start, length)` that end in the same
+    /// distinguished point to the values where they merge.
+    fn merge(&self, mut first: (u64, u64), mut second: (u64, u64)) -> Option<(u64, u64)> {
+        if first.1 < second.1 {
+            std::mem::swap(&mut first, &mut second);
+        }
+
+        let (mut x, mut y) = (first.0, second.0);
+        for _ in 0..first.1 - second.1 {
+            x = self.f(x);
+        }
+
+        // one trail started on the other
+        if x == y {
+            return None;
+        }
+
+        loop {
+            let (fx, fy) = (self.f(x), self.f(y));
+            if fx == fy {
+                return Some((x, y));
+            }
+            (x, y) = (fx, fy);
+        }
+    }
+
+    /// Walks the next `threads * TRAILS_PER_THREAD` trails and returns the
+    /// collisions `(x, x_prim)` between the two prefixes found on the way,
+    /// with `x` the birthday value of the first prefix. The same state
+    /// always yields the same collisions.
+    pub fn step(&mut self, threads: usize) -> Vec<(u64, u64)> {
+        let first = self.trails;
+        let count = threads as u64 * TRAILS_PER_THREAD;
+
+        let ends = thread::scope(|scope| {
+            let workers = (0..threads as u64)
+                .map(|idx| {
+                    let search = &*self;
+                    scope.spawn(move || {
+                        let trails = first + idx * TRAILS_PER_THREAD;
+                        (trails..trails + TRAILS_PER_THREAD)
+                            .map(|trail| {
+                                let start = search.trail_start(trail);
+                                search.walk(start).map(|(end, length)| (start, end, length))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let mut collisions = Vec::new();
+
+        for (start, end, length) in ends.into_iter().flatten() {
+            match self.points.get(&end) {
+                Some(&other) if other.0 != start => {
+                    if let Some((x, y)) = self.merge(other, (start, length)) {
+                        match (x & 1, y & 1) {
+                            (0, 1) => collisions.push((x, y)),
+                            (1, 0) => collisions.push((y, x)),
+                            _ => {}
+                        }
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    self.points.insert(end, (start, length));
+                }
+            }
+        }
+
+        self.trails += count;
+        collisions
+    }
+}
+
+/// Why a chosen-prefix search stopped.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// A path file does not parse.
+    Path(PathBuf, ParseError),
+    /// The path of this block does not start from its chaining value.
+    WrongStart(usize),
+    /// The path of this block lacks a `delta` line.
+    Differential(usize, String),
+    /// The saved search was started from other prefixes.
+    OtherPrefixes,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Path(path, error) => write!(f, "{}: {}", path.display(), error),
+            Error::WrongStart(block) => write!(
+                f,
+                "the path of block {} does not start from its chaining value",
+                block
+            ),
+            Error::Differential(block, error) => {
+                write!(f, "the path of block {}: {}", block, error)
+            }
+            Error::OtherPrefixes => write!(f, "the search was started from other files"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+/// A source of the differential paths of the near-collision blocks.
+pub trait PathFamily {
+    /// The path of near-collision block `block` of the search, which starts
+    /// from `ihv` and `ihv_prim` and should remove `elimination`, or some
+    /// of the difference if the rest of it is not of the form
+    /// (0, δb, δc, δc) and there is no plan. Returns `None` if the path is
+    /// not available (yet).
+    fn path(
+        &self,
+        block: usize,
+        ihv: &[u32; 4],
+        ihv_prim: &[u32; 4],
+        elimination: Option<&Elimination>,
+    ) -> Result<Option<ConditionTable>, Error>;
+}
+
+/// Paths constructed elsewhere and saved as `block<k>.txt` in a directory.
+#[derive(Debug, Clone)]
+pub struct PathDirectory(pub PathBuf);
+
+impl PathFamily for PathDirectory {
+    fn path(
+        &self,
+        block: usize,
+        _: &[u32; 4],
+        _: &[u32; 4],
+        _: Option<&Elimination>,
+    ) -> Result<Option<ConditionTable>, Error> {
+        let path = self.0.join(format!("block{}.txt", block));
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        match ConditionTable::parse(&text) {
+            Ok(table) => Ok(Some(table)),
+            Err(error) => Err(Error::Path(path, error)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Stage {
+    Birthday(Birthday),
+    NearCollisions {
+        ihv: [u32; 4],
+        ihv_prim: [u32; 4],
+        /// The blocks that remove the difference left, empty if it has no
+        /// plan.
+        plan: Vec<Elimination>,
+        /// Near-collision blocks found so far.
+        done: usize,
+        seed: u64,
+        /// Candidate batches tried for the current block.
+        batches: u64,
+    },
+    Done,
+}
+
+/// What `ChosenPrefix::step` got to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    Searching,
+    /// The path family has no path for this block yet.
+    NeedsPath(usize),
+    Done,
+}
+
+/// A resumable chosen-prefix collision search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChosenPrefix {
+    /// `identify` of the prefixes, checked when the search is resumed.
+    pub prefixes: [u8; 16],
+    /// What follows the first prefix so far: padding, birthday bytes and
+    /// near-collision blocks.
+    pub suffix: Vec<u8>,
+    pub suffix_prim: Vec<u8>,
+    /// Longest plan of near-collision blocks the birthday search accepts.
+    pub max_blocks: usize,
+    /// Threads of the birthday search, which walks trails in sets of
+    /// `threads * TRAILS_PER_THREAD`.
+    pub threads: usize,
+    pub stage: Stage,
+}
+
+impl ChosenPrefix {
+    pub fn new(prefix: &[u8], prefix_prim: &[u8], seed: u64, threads: usize) -> ChosenPrefix {
+        let len = (prefix.len().max(prefix_prim.len()) + BIRTHDAY_BYTES).div_ceil(64) * 64
+            - BIRTHDAY_BYTES;
+        let (mut padded, mut padded_prim) = (prefix.to_vec(), prefix_prim.to_vec());
+        padded.resize(len, 0);
+        padded_prim.resize(len, 0);
+
+        ChosenPrefix {
+            prefixes: identify(prefix, prefix_prim),
+            suffix: padded[prefix.len()..].to_vec(),
+            suffix_prim: padded_prim[prefix_prim.len()..].to_vec(),
+            max_blocks: 32,
+            threads,
+            stage: Stage::Birthday(Birthday::new(&padded, &padded_prim, 64, seed)),
+        }
+    }
+
+    /// The two suffixes that give the prefixes the same MD5, once done.
+    pub fn suffixes(&self) -> Option<(&[u8], &[u8])> {
+        matches!(self.stage, Stage::Done).then_some((&self.suffix, &self.suffix_prim))
+    }
+
+    /// Does one batch of work: walks birthday trails or searches candidates
+    /// for the next near-collision block.
+    pub fn step<B: CandidateBackend>(
+        &mut self,
+        backend: &B,
+        paths: &impl PathFamily,
+    ) -> Result<Progress, Error> {
+        match &mut self.stage {
+            Stage::Birthday(birthday) => {
+                for (x, x_prim) in birthday.step(self.threads) {
+                    let (ihv, ihv_prim) = (birthday.ihv(x), birthday.ihv(x_prim));
+                    let delta = difference(&ihv, &ihv_prim);
+
+                    let Some(plan) = plan(&delta).filter(|plan| plan.len() <= self.max_blocks)
+                    else {
+                        continue;
+                    };
+
+                    self.suffix.extend(x.to_le_bytes());
+                    self.suffix_prim.extend(x_prim.to_le_bytes());
+                    self.stage = Stage::NearCollisions {
+                        ihv,
+                        ihv_prim,
+                        plan,
+                        done: 0,
+                        seed: birthday.seed,
+                        batches: 0,
+                    };
+                    break;
+                }
+
+                Ok(Progress::Searching)
+            }
+            Stage::NearCollisions {
+                ihv,
+                ihv_prim,
+                plan,
+                done,
+                seed,
+                batches,
+            } => {
+                if difference(ihv, ihv_prim) == [0; 4] {
+                    self.stage = Stage::Done;
+                    return Ok(Progress::Done);
+                }
+
+                let Some(table) = paths.path(*done, ihv, ihv_prim, plan.first())? else {
+                    return Ok(Progress::NeedsPath(*done));
+                };
+                if !table.starts_from(ihv) {
+                    return Err(Error::WrongStart(*done));
+                }
+                let differential =
+                    Differential::of(&table).map_err(|error| Error::Differential(*done, error))?;
+
+                let batch_seed = *seed ^ (*done as u64) << 48 ^ *batches;
+                *batches += 1;
+
+                let expected = add(&difference(ihv, ihv_prim), &differential.ihv);
+
+                let candidates = backend.generate_path_candidates(ihv, &table, batch_seed);
+                let candidates_prim = candidates
+                    .iter()
+                    .map(|words| differential.apply(words))
+                    .collect::<Vec<_>>();
+
+                let count = candidates.len();
+                let next = backend.compress_batch(&vec![*ihv; count], &candidates);
+                let next_prim = backend.compress_batch(&vec![*ihv_prim; count], &candidates_prim);
+
+                if let Some(idx) =
+                    (0..count).find(|&idx| difference(&next[idx], &next_prim[idx]) == expected)
+                {
+                    self.suffix.extend(words_to_bytes(&candidates[idx]));
+                    self.suffix_prim
+                        .extend(words_to_bytes(&candidates_prim[idx]));
+                    (*ihv, *ihv_prim) = (next[idx], next_prim[idx]);
+                    *plan = self::plan(&expected).unwrap_or_default();
+                    *done += 1;
+                    *batches = 0;
+                }
+
+                Ok(Progress::Searching)
+            }
+            Stage::Done => Ok(Progress::Done),
+        }
+    }
+
+    /// Steps until the search is done or needs a path, saving it to
+    /// `checkpoint` after every step.
+    pub fn run<B: CandidateBackend>(
+        &mut self,
+        backend: &B,
+        paths: &impl PathFamily,
+        checkpoint: &Path,
+    ) -> Result<Progress, Error> {
+        loop {
+            let progress = self.step(backend, paths)?;
+            self.save(checkpoint)?;
+
+            if progress != Progress::Searching {
+                return Ok(progress);
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        save_json(self, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<ChosenPrefix> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Loads the search saved at `path`, which has to be the one of
+    /// `prefix` and `prefix_prim`.
+    pub fn resume(path: &Path, prefix: &[u8], prefix_prim: &[u8]) -> Result<ChosenPrefix, Error> {
+        let search = ChosenPrefix::load(path)?;
+        match search.prefixes == identify(prefix, prefix_prim) {
+            true => Ok(search),
+            false => Err(Error::OtherPrefixes),
+        }
+    }
+}
+
+/// MD5 of both prefixes, the first one's length in front so that no other
+/// split of the bytes gives the same.
+fn identify(prefix: &[u8], prefix_prim: &[u8]) -> [u8; 16] {
+    digest(&[&(prefix.len() as u64).to_le_bytes(), prefix, prefix_prim].concat())
+}
+
+fn difference(ihv: &[u32; 4], ihv_prim: &[u32; 4]) -> [u32; 4] {
+    [0, 1, 2, 3].map(|i| ihv_prim[i].wrapping_sub(ihv[i]))
+}
+
+fn add(x: &[u32; 4], y: &[u32; 4]) -> [u32; 4] {
+    [0, 1, 2, 3].map(|i| x[i].wrapping_add(y[i]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::CpuBackend;
+    use crate::published::{wang, Blocks, Published};
+
+    #[test]
+    fn plans_remove_the_difference() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..1000 {
+            let [b, c]: [u32; 2] = rng.gen();
+            let delta = [0, b, c, c];
+
+            let plan = plan(&delta).unwrap();
+            let removed = plan.iter().fold(delta, |delta, elimination| {
+                add(&delta, &elimination.delta())
+            });
+            assert_eq!(removed, [0; 4]);
+        }
+
+        assert_eq!(plan(&[1, 0, 0, 0]), None);
+        assert_eq!(plan(&[0, 0, 1, 2]), None);
+        assert_eq!(plan(&[0; 4]), Some(vec![]));
+    }
+
+    #[test]
+    fn naf_is_non_adjacent() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..1000 {
+            let value: u32 = rng.gen();
+            let terms = naf(value);
+
+            let sum = terms.iter().fold(0u32, |sum, &(bit, sign)| {
+                sum.wrapping_add(signed_power(bit, sign))
+            });
+            assert_eq!(sum, value);
+            assert!(terms.windows(2).all(|pair| pair[1].0 > pair[0].0 + 1));
+        }
+    }
+
+    fn birthday() -> Birthday {
+        let (mut prefix, mut prefix_prim) = (b"first".to_vec(), b"second prefix".to_vec());
+        prefix.resize(120, 0);
+        prefix_prim.resize(120, 0);
+
+        Birthday::new(&prefix, &prefix_prim, 24, 1)
+    }
+
+    #[test]
+    fn birthday_finds_collisions_between_the_prefixes() {
+        let mut search = birthday();
+
+        let (x, x_prim) = loop {
+            if let Some(&collision) = search.step(2).first() {
+                break collision;
+            }
+        };
+
+        assert_eq!((x & 1, x_prim & 1), (0, 1));
+        assert_eq!(search.f(x), search.f(x_prim));
+    }
+
+    #[test]
+    fn birthday_resumes_from_json() {
+        let mut search = birthday();
+        let mut resumed = birthday();
+
+        resumed.step(2);
+        let json = serde_json::to_string(&resumed).unwrap();
+        let mut resumed: Birthday = serde_json::from_str(&json).unwrap();
+
+        search.step(2);
+        for _ in 0..8 {
+            assert_eq!(search.step(2), resumed.step(2));
+        }
+    }
+
+    #[test]
+    fn waits_for_missing_paths() {
+        let mut search = ChosenPrefix::new(b"a", b"b", 0, 1);
+        search.stage = Stage::NearCollisions {
+            ihv: [0; 4],
+            ihv_prim: [0, 1, 1, 1],
+            plan: plan(&[0, 1, 1, 1]).unwrap(),
+            done: 0,
+            seed: 0,
+            batches: 0,
+        };
+
+        let paths = PathDirectory(PathBuf::from("no such directory"));
+        assert_eq!(
+            search.step(&CpuBackend::default(), &paths).unwrap(),
+            Progress::NeedsPath(0)
+        );
+        assert_eq!(search.suffixes(), None);
+        assert_eq!(search.suffix.len(), 55);
+    }
+
+    #[test]
+    fn rejects_bad_paths() {
+        let dir = std::env::temp_dir().join(format!("chosen-prefix-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths = PathDirectory(dir.clone());
+
+        let mut search = ChosenPrefix::new(b"a", b"b", 0, 1);
+        search.stage = Stage::NearCollisions {
+            ihv: [0; 4],
+            ihv_prim: [0, 1, 1, 1],
+            plan: plan(&[0, 1, 1, 1]).unwrap(),
+            done: 0,
+            seed: 0,
+            batches: 0,
+        };
+
+        fs::write(dir.join("block0.txt"), "c0,26 = maybe").unwrap();
+        let result = search.step(&CpuBackend::default(), &paths);
+        assert!(matches!(result, Err(Error::Path(..))), "{:?}", result);
+
+        // c0 of the chaining value is 0
+        fs::write(dir.join("block0.txt"), "c0,26 = 1").unwrap();
+        let result = search.step(&CpuBackend::default(), &paths);
+        assert!(matches!(result, Err(Error::WrongStart(0))), "{:?}", result);
+
+        // without its delta lines the path does not say how the blocks pair
+        fs::write(dir.join("block0.txt"), "c0,26 = 0").unwrap();
+        let result = search.step(&CpuBackend::default(), &paths);
+        assert!(
+            matches!(result, Err(Error::Differential(0, _))),
+            "{:?}",
+            result
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn blocks_follow_the_deltas_of_their_paths() {
+        let dir = std::env::temp_dir().join(format!("chosen-prefix-wang-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("block0.txt"),
+            include_str!("../paths/second_block.txt"),
+        )
+        .unwrap();
+
+        // the published first blocks as the prefixes: the difference they
+        // leave has no plan, but Wang's second-block path, with differences
+        // in m4, m11 and m14, removes it, and `Published` has its block
+        let Blocks { m0, m0_prim, .. } = wang();
+        let (prefix, prefix_prim) = (words_to_bytes(&m0), words_to_bytes(&m0_prim));
+        let mut search = ChosenPrefix {
+            prefixes: identify(&prefix, &prefix_prim),
+            suffix: Vec::new(),
+            suffix_prim: Vec::new(),
+            max_blocks: 32,
+            threads: 1,
+            stage: Stage::NearCollisions {
+                ihv: chaining_value(&prefix),
+                ihv_prim: chaining_value(&prefix_prim),
+                plan: Vec::new(),
+                done: 0,
+                seed: 0,
+                batches: 0,
+            },
+        };
+
+        let progress = search.run(
+            &Published,
+            &PathDirectory(dir.clone()),
+            &dir.join("search.json"),
+        );
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!(progress.unwrap(), Progress::Done);
+
+        let (suffix, suffix_prim) = search.suffixes().unwrap();
+        let first = [prefix, suffix.to_vec()].concat();
+        let second = [prefix_prim, suffix_prim.to_vec()].concat();
+        assert_ne!(first, second);
+        assert_eq!(digest(&first), digest(&second));
+    }
+
+    #[test]
+    fn resumes_only_its_own_prefixes() {
+        let path = std::env::temp_dir().join(format!("chosen-prefix-{}.json", std::process::id()));
+        ChosenPrefix::new(b"a", b"b", 0, 1).save(&path).unwrap();
+
+        let resumed = ChosenPrefix::resume(&path, b"a", b"b");
+        let swapped = ChosenPrefix::resume(&path, b"b", b"a");
+        let shifted = ChosenPrefix::resume(&path, b"ab", b"");
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(resumed.unwrap().prefixes, identify(b"a", b"b"));
+        assert!(matches!(swapped, Err(Error::OtherPrefixes)));
+        assert!(matches!(shifted, Err(Error::OtherPrefixes)));
+    }
+}
@@ -0,0 +1,40 @@
+// Sufficient conditions of the first-block differential path (Table 4 of
+// Wang & Yu, "How to Break MD5 and Other Hash Functions"), generated by
+// build.rs from paths/first_block.txt.
+
+use super::table::ConditionTable;
+
+include!(concat!(env!("OUT_DIR"), "/first_block.rs"));
+
+/// The conditions the masks were generated from, e.g. for `trace::trace`.
+pub fn table() -> ConditionTable {
+    ConditionTable::parse(include_str!("../../paths/first_block.txt")).unwrap()
+}
+
+// M0 and M0' differ by 2^15 in word 11 only if that bit is clear in M0;
+// otherwise the difference turns into -2^15 and the path breaks at b3.
+pub const M11_ZERO_BITS: u32 = 0x00008000;
+
+// The bit conditions only fix Q values. In round 1 the path also needs
+// every rotation to carry the modular difference of T (the sum fed into the
+// rotation) to the expected difference of RL(T, s); unlike the second block
+// that fails far too often to be left to chance. Entry t - 1 holds
+// (delta T, delta RL(T, s)) of step t.
+pub const ROUND_1_ROTATIONS: [(u32, u32); 16] = [
+    (0, 0),
+    (0, 0),
+    (0, 0),
+    (0, 0),
+    (0x80000000, 0xFFFFFFC0),
+    (0x00080800, 0x80800000),
+    (0xFFFFBC00, 0x77FFFFFF),
+    (0xF601041C, 0x06FD8042),
+    (0x7F010500, 0x80827FC0),
+    (0x03F00001, 0x0000103F),
+    (0xF8002000, 0x3FFFF000),
+    (0xFF7DFF00, 0xBFFFDF80),
+    (0x00020041, 0x01002080),
+    (0xFFFFF000, 0xFF000000),
+    (0xC0040000, 0xFFFF8008),
+    (0x01FFDF80, 0xE0007FF8),
+];
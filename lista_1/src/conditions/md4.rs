@@ -0,0 +1,14 @@
+// Sufficient conditions of Wang et al.'s MD4 collision path (Table 6 of
+// "Cryptanalysis of the Hash Functions MD4 and RIPEMD"), which md4.rs
+// searches with.
+//
+// The masks are generated by build.rs from paths/md4.txt.
+
+use super::table::ConditionTable;
+
+include!(concat!(env!("OUT_DIR"), "/md4.rs"));
+
+/// The conditions the masks were generated from.
+pub fn table() -> ConditionTable {
+    ConditionTable::parse(include_str!("../../paths/md4.txt")).unwrap()
+}
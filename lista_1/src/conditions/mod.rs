@@ -0,0 +1,36 @@
+pub mod first_block;
+pub mod md4;
+pub mod second_block;
+pub mod table;
+
+pub fn apply_one_bits(v: u32, mask: u32) -> u32 {
+    v | mask
+}
+
+pub fn apply_zero_bits(v: u32, mask: u32) -> u32 {
+    v & !mask
+}
+
+pub fn apply_same_bits(v: u32, u: u32, mask: u32) -> u32 {
+    (v | (u & mask)) & (u | !mask)
+}
+
+pub fn apply_different_bits(v: u32, u: u32, mask: u32) -> u32 {
+    apply_same_bits(v, !u, mask)
+}
+
+pub fn verify_one_bits(v: u32, mask: u32) -> bool {
+    (v & mask) == mask
+}
+
+pub fn verify_zero_bits(v: u32, mask: u32) -> bool {
+    (v & mask) == 0
+}
+
+pub fn verify_same_bits(v: u32, u: u32, mask: u32) -> bool {
+    (v & mask) == (u & mask)
+}
+
+pub fn verify_different_bits(v: u32, u: u32, mask: u32) -> bool {
+    (v & mask) != (u & mask)
+}
@@ -0,0 +1,34 @@
+// Sufficient conditions of the second-block differential path (Table 6 of
+// Wang & Yu, "How to Break MD5 and Other Hash Functions"), plus the ones on
+// the chaining value the table leaves implicit: they are what the first
+// block has to deliver for the a1..c1 conditions to be satisfiable.
+//
+// The masks are generated by build.rs from paths/second_block.txt; kernel.cu
+// includes the same masks as second_block.h, and a test checks that it uses
+// every one of them.
+
+use super::table::ConditionTable;
+
+include!(concat!(env!("OUT_DIR"), "/second_block.rs"));
+
+/// The conditions the masks were generated from, e.g. for `trace::trace`.
+pub fn table() -> ConditionTable {
+    ConditionTable::parse(include_str!("../../paths/second_block.txt")).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// kernel.cu is written by hand against second_block.h, so a condition
+    /// added to the path file must not go unchecked on the GPU.
+    #[test]
+    fn kernel_checks_every_mask() {
+        let kernel = include_str!("../../kernel.cu");
+
+        for line in table().to_c().lines() {
+            let name = line.split_whitespace().nth(1).unwrap();
+            assert!(kernel.contains(name), "kernel.cu does not check {}", name);
+        }
+    }
+}
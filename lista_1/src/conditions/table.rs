@@ -0,0 +1,621 @@
+// Bit conditions as data. A path's sufficient conditions are kept in a text
+// file (see paths/) and parsed into a `ConditionTable`. build.rs turns the
+// tables into the `*_BITS` mask constants used by the search and into the
+// `#define`s of kernel.cu, so the conditions only live in one place.
+//
+// Two textual formats are understood, and may be mixed:
+//
+// * Wang's, as in the tables of "How to Break MD5 and Other Hash Functions":
+//   comma-separated `q,i = 0`, `q,i = 1`, `q,i = r,i` and `q,i = r,i + 1`,
+//   where `q` and `r` are named `a1`, `d1`, `c1`, `b1`, `a2`, ... and `i` is
+//   a 1-based bit index. Conditions on the boolean function (`φ34,32 = 1`)
+//   are kept as well.
+// * Stevens', as printed by HashClash: `Q<t>: <32 characters>`, most
+//   significant bit first, with `.` free, `0`/`1` fixed (also `-`/`+`,
+//   which imply the value before the difference), `^`/`!` equal to/differing
+//   from Q[t-1] and `m`/`#` equal to/differing from Q[t-2].
+//
+// Lines starting with `#` are comments.
+//
+// A path file may also give the differences the path is for, as modular
+// sums of signed powers of two: `delta m4 = 2^31, m11 = -2^15, m14 = 2^31`
+// for the message words (words not named do not differ) and
+// `delta ihv = a, b, c, d` for what the block adds to the difference of the
+// chaining values.
+
+use std::fmt::{self, Display};
+
+/// A value a bit condition is put on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Variable {
+    /// The working state value Q[t]. Q[-3]..Q[0] is the chaining value.
+    Q(i32),
+    /// The output of the boolean function in step `t` (Wang's φ).
+    Phi(u32),
+}
+
+impl Variable {
+    /// Name in Wang's notation: `a1` is Q[1], `d1` Q[2], `c1` Q[3], `b1`
+    /// Q[4], `a2` Q[5] and so on, with `a0`, `d0`, `c0`, `b0` the chaining
+    /// value.
+    pub fn name(&self) -> String {
+        match *self {
+            Variable::Q(t) => {
+                let round = (t - 1).div_euclid(4) + 1;
+                let register = ['a', 'd', 'c', 'b'][(t - 1).rem_euclid(4) as usize];
+                format!("{}{}", register, round)
+            }
+            Variable::Phi(t) => format!("phi{}", t),
+        }
+    }
+
+    fn parse(name: &str) -> Option<Variable> {
+        if let Some(step) = name.strip_prefix('φ').or(name.strip_prefix("phi")) {
+            return step.parse().ok().map(Variable::Phi);
+        }
+
+        let mut chars = name.chars();
+        let register = "adcb".find(chars.next()?)? as i32;
+        let round: i32 = chars.as_str().parse().ok()?;
+
+        Some(Variable::Q(4 * (round - 1) + register + 1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitCondition {
+    Zero,
+    One,
+    /// The bit equals the same bit of another value.
+    Same(Variable),
+    /// The bit differs from the same bit of another value.
+    Different(Variable),
+}
+
+/// One condition: `bit` (0-based) of `variable` satisfies `condition`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub variable: Variable,
+    pub bit: u32,
+    pub condition: BitCondition,
+}
+
+impl Condition {
+    /// Checks that the condition is on Q[-3]..Q[64] or φ0..φ63 and relates
+    /// to those only, and that conditions on the chaining value relate to
+    /// the chaining value.
+    fn check(&self) -> Result<(), String> {
+        let in_range = |variable: Variable| match variable {
+            Variable::Q(t) => (-3..=64).contains(&t),
+            Variable::Phi(t) => t < 64,
+        };
+        let describe = |variable: Variable| match variable {
+            Variable::Q(t) => format!("Q[{}]", t),
+            Variable::Phi(t) => format!("φ{}", t),
+        };
+
+        let other = match self.condition {
+            BitCondition::Same(other) | BitCondition::Different(other) => Some(other),
+            BitCondition::Zero | BitCondition::One => None,
+        };
+
+        for variable in [self.variable].into_iter().chain(other) {
+            if !in_range(variable) {
+                return Err(format!(
+                    "{} is outside Q[-3]..Q[64] and φ0..φ63",
+                    describe(variable)
+                ));
+            }
+        }
+
+        match (self.variable, other) {
+            (Variable::Q(t), Some(other)) if t <= 0 && other > Variable::Q(0) => Err(format!(
+                "a condition on the chaining value relates to {}",
+                describe(other)
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// All conditions on one value, as masks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Masks {
+    pub one: u32,
+    pub zero: u32,
+    /// `(other, mask)`: the bits in `mask` equal those of `other`.
+    pub same: Vec<(Variable, u32)>,
+    /// `(other, mask)`: the bits in `mask` differ from those of `other`.
+    pub different: Vec<(Variable, u32)>,
+}
+
+impl Masks {
+    /// Forces the conditions onto `value`, looking the other values up with
+    /// `lookup`.
+    #[inline]
+    pub fn apply(&self, value: u32, lookup: impl Fn(Variable) -> u32) -> u32 {
+        let mut value = (value | self.one) & !self.zero;
+
+        for &(other, mask) in &self.same {
+            value = (value & !mask) | (lookup(other) & mask);
+        }
+        for &(other, mask) in &self.different {
+            value = (value & !mask) | (!lookup(other) & mask);
+        }
+
+        value
+    }
+
+    /// Returns the bits of `value` that break a condition.
+    #[inline]
+    pub fn broken_bits(&self, value: u32, lookup: impl Fn(Variable) -> u32) -> u32 {
+        self.apply(value, lookup) ^ value
+    }
+
+    #[inline]
+    pub fn verify(&self, value: u32, lookup: impl Fn(Variable) -> u32) -> bool {
+        self.broken_bits(value, lookup) == 0
+    }
+}
+
+/// The sufficient conditions of a differential path, in the order they were
+/// given, and the differences it is for if the file gives them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConditionTable {
+    pub conditions: Vec<Condition>,
+    /// `m_prim - m`, word by word.
+    pub message_delta: Option<[u32; 16]>,
+    /// What the block adds to `ihv_prim - ihv`, in the order a, b, c, d.
+    pub ihv_delta: Option<[u32; 4]>,
+}
+
+impl ConditionTable {
+    pub fn parse(text: &str) -> Result<ConditionTable, ParseError> {
+        let mut table = ConditionTable::default();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |message: String| ParseError {
+                line: idx + 1,
+                message,
+            };
+
+            if let Some(delta) = line.strip_prefix("delta ") {
+                table.parse_delta(delta).map_err(error)?;
+                continue;
+            }
+
+            let conditions = match parse_stevens_row(line) {
+                Some(row) => row.map_err(error)?,
+                None => parse_wang_line(line).map_err(error)?,
+            };
+            for condition in &conditions {
+                condition.check().map_err(error)?;
+            }
+            table.conditions.extend(conditions);
+        }
+
+        Ok(table)
+    }
+
+    fn parse_delta(&mut self, delta: &str) -> Result<(), String> {
+        if let Some(words) = delta.trim().strip_prefix("ihv") {
+            let words = words
+                .trim()
+                .strip_prefix('=')
+                .ok_or("expected 'delta ihv = a, b, c, d'")?
+                .split(',')
+                .map(parse_difference)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let ihv_delta = words
+                .try_into()
+                .map_err(|_| "the chaining value has 4 words")?;
+            self.ihv_delta = Some(ihv_delta);
+            return Ok(());
+        }
+
+        let message_delta = self.message_delta.get_or_insert([0; 16]);
+        for term in delta.split(',') {
+            let (word, value) = term
+                .split_once('=')
+                .ok_or_else(|| format!("'{}' is not 'm<i> = <difference>'", term.trim()))?;
+
+            let idx = word
+                .trim()
+                .strip_prefix('m')
+                .and_then(|idx| idx.parse::<usize>().ok())
+                .filter(|&idx| idx < 16)
+                .ok_or_else(|| format!("'{}' is not a message word", word.trim()))?;
+            message_delta[idx] = parse_difference(value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Every variable with at least one condition, in order of appearance.
+    pub fn variables(&self) -> Vec<Variable> {
+        let mut variables = Vec::new();
+
+        for condition in &self.conditions {
+            if !variables.contains(&condition.variable) {
+                variables.push(condition.variable);
+            }
+        }
+
+        variables
+    }
+
+    pub fn masks(&self, variable: Variable) -> Masks {
+        let mut masks = Masks::default();
+
+        for condition in self.conditions.iter().filter(|c| c.variable == variable) {
+            let bit = 1 << condition.bit;
+
+            match condition.condition {
+                BitCondition::Zero => masks.zero |= bit,
+                BitCondition::One => masks.one |= bit,
+                BitCondition::Same(other) => add_mask(&mut masks.same, other, bit),
+                BitCondition::Different(other) => add_mask(&mut masks.different, other, bit),
+            }
+        }
+
+        masks.same.sort();
+        masks.different.sort();
+
+        masks
+    }
+
+    /// Checks the conditions the path puts on the chaining value `ihv`.
+    pub fn starts_from(&self, ihv: &[u32; 4]) -> bool {
+        let q = [ihv[0], ihv[3], ihv[2], ihv[1]];
+
+        (-3..=0).all(|t| {
+            self.masks(Variable::Q(t))
+                .verify(q[(t + 3) as usize], |other| match other {
+                    Variable::Q(r) if r <= 0 => q[(r + 3) as usize],
+                    _ => unreachable!("chaining value conditions only refer to the chaining value"),
+                })
+        })
+    }
+
+    /// The masks as Rust constants, named like `D1_ONE_BITS` and
+    /// `D1_A1_SAME_BITS`.
+    pub fn to_rust(&self) -> String {
+        self.constants()
+            .into_iter()
+            .map(|(name, mask)| format!("pub const {}: u32 = 0x{:08X};\n", name, mask))
+            .collect()
+    }
+
+    /// The masks as C `#define`s, named like the Rust constants.
+    pub fn to_c(&self) -> String {
+        self.constants()
+            .into_iter()
+            .map(|(name, mask)| format!("#define {} 0x{:08X}u\n", name, mask))
+            .collect()
+    }
+
+    fn constants(&self) -> Vec<(String, u32)> {
+        let mut constants = Vec::new();
+
+        for variable in self.variables() {
+            let name = variable.name().to_uppercase();
+            let masks = self.masks(variable);
+
+            if masks.one != 0 {
+                constants.push((format!("{}_ONE_BITS", name), masks.one));
+            }
+            if masks.zero != 0 {
+                constants.push((format!("{}_ZERO_BITS", name), masks.zero));
+            }
+            for (other, mask) in masks.same {
+                let other = other.name().to_uppercase();
+                constants.push((format!("{}_{}_SAME_BITS", name, other), mask));
+            }
+            for (other, mask) in masks.different {
+                let other = other.name().to_uppercase();
+                constants.push((format!("{}_{}_DIFFERENT_BITS", name, other), mask));
+            }
+        }
+
+        constants
+    }
+}
+
+fn add_mask(masks: &mut Vec<(Variable, u32)>, other: Variable, bit: u32) {
+    match masks.iter_mut().find(|(variable, _)| *variable == other) {
+        Some((_, mask)) => *mask |= bit,
+        None => masks.push((other, bit)),
+    }
+}
+
+/// Parses a line of comma-separated `q,i = 0`, `q,i = 1`, `q,i = r,i` or
+/// `q,i = r,i + 1` conditions.
+fn parse_wang_line(line: &str) -> Result<Vec<Condition>, String> {
+    let tokens = line.split(',').map(str::trim).collect::<Vec<_>>();
+    let mut conditions = Vec::new();
+    let mut idx = 0;
+
+    while idx < tokens.len() {
+        // a trailing comma continues the list on the next line
+        if tokens[idx].is_empty() && idx == tokens.len() - 1 {
+            break;
+        }
+
+        let (index, right) = tokens
+            .get(idx + 1)
+            .and_then(|token| token.split_once('='))
+            .ok_or_else(|| format!("'{}' is not a condition", tokens[idx]))?;
+
+        let (variable, bit) = parse_wang_bit(tokens[idx], index)
+            .ok_or_else(|| format!("'{},{}' is not a bit", tokens[idx], index.trim()))?;
+
+        let condition = match right.trim() {
+            "0" => BitCondition::Zero,
+            "1" => BitCondition::One,
+            other => {
+                let other_index = tokens
+                    .get(idx + 2)
+                    .ok_or_else(|| format!("'{}' is not a bit", other))?;
+                idx += 1;
+
+                let (other_index, different) = match other_index.split_once('+') {
+                    Some((other_index, one)) if one.trim() == "1" => (other_index, true),
+                    _ => (*other_index, false),
+                };
+
+                let (other, other_bit) = parse_wang_bit(other, other_index)
+                    .ok_or_else(|| format!("'{},{}' is not a bit", other, other_index.trim()))?;
+
+                if other_bit != bit {
+                    return Err(format!(
+                        "{},{} is related to bit {} of {}",
+                        tokens[idx - 1],
+                        bit + 1,
+                        other_bit + 1,
+                        other.name()
+                    ));
+                }
+
+                if different {
+                    BitCondition::Different(other)
+                } else {
+                    BitCondition::Same(other)
+                }
+            }
+        };
+
+        conditions.push(Condition {
+            variable,
+            bit,
+            condition,
+        });
+        idx += 2;
+    }
+
+    Ok(conditions)
+}
+
+/// Parses a modular difference like `0`, `2^31` or `-2^31 + 2^25 - 2^3`.
+pub(crate) fn parse_difference(text: &str) -> Result<u32, String> {
+    let error = || format!("'{}' is not a sum of powers of two", text.trim());
+    let mut value = 0u32;
+
+    // split before every sign, keeping it with its term
+    let compact = text.split_whitespace().collect::<String>();
+    let spaced = compact.replace('+', " +").replace('-', " -");
+    if spaced.trim().is_empty() {
+        return Err(error());
+    }
+
+    for term in spaced.split_whitespace() {
+        let (negative, power) = match term.strip_prefix('-') {
+            Some(power) => (true, power),
+            None => (false, term.strip_prefix('+').unwrap_or(term)),
+        };
+
+        let power = match power {
+            "0" => 0,
+            power => {
+                let exponent: u32 = power
+                    .strip_prefix("2^")
+                    .and_then(|exponent| exponent.parse().ok())
+                    .filter(|&exponent| exponent < 32)
+                    .ok_or_else(error)?;
+                1 << exponent
+            }
+        };
+
+        value = if negative {
+            value.wrapping_sub(power)
+        } else {
+            value.wrapping_add(power)
+        };
+    }
+
+    Ok(value)
+}
+
+/// Parses the name and 1-based index of a bit into the variable and the
+/// 0-based bit index.
+fn parse_wang_bit(name: &str, index: &str) -> Option<(Variable, u32)> {
+    let index: u32 = index.trim().parse().ok()?;
+
+    if !(1..=32).contains(&index) {
+        return None;
+    }
+
+    Some((Variable::parse(name.trim())?, index - 1))
+}
+
+/// Parses a `Q<t>: <32 characters>` row, or returns `None` if `line` is not
+/// one.
+fn parse_stevens_row(line: &str) -> Option<Result<Vec<Condition>, String>> {
+    let (name, row) = line.split_once(':')?;
+    let t: i32 = name.trim().strip_prefix('Q')?.parse().ok()?;
+
+    let row = row.split_whitespace().collect::<String>();
+    if row.chars().count() != 32 {
+        return Some(Err(format!("Q{} has {} bits", t, row.chars().count())));
+    }
+
+    let mut conditions = Vec::new();
+
+    for (idx, symbol) in row.chars().enumerate() {
+        let condition = match symbol {
+            '.' => continue,
+            '0' | '+' => BitCondition::Zero,
+            '1' | '-' => BitCondition::One,
+            '^' => BitCondition::Same(Variable::Q(t - 1)),
+            '!' => BitCondition::Different(Variable::Q(t - 1)),
+            'm' => BitCondition::Same(Variable::Q(t - 2)),
+            '#' => BitCondition::Different(Variable::Q(t - 2)),
+            _ => return Some(Err(format!("unknown condition '{}'", symbol))),
+        };
+
+        conditions.push(Condition {
+            variable: Variable::Q(t),
+            bit: 31 - idx as u32,
+            condition,
+        });
+    }
+
+    Some(Ok(conditions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_follow_wang() {
+        for (t, name) in [
+            (-3, "a0"),
+            (0, "b0"),
+            (1, "a1"),
+            (2, "d1"),
+            (8, "b2"),
+            (64, "b16"),
+        ] {
+            assert_eq!(Variable::Q(t).name(), name);
+            assert_eq!(Variable::parse(name), Some(Variable::Q(t)));
+        }
+    }
+
+    #[test]
+    fn wang_and_stevens_formats_agree() {
+        let wang = ConditionTable::parse(
+            "# a2\n\
+             a2,1 = 0, a2,2 = 1, a2,3 = b1,3, a2,4 = b1,4 + 1,\n\
+             a2,5 = c1,5, a2,32 = c1,32 +1",
+        )
+        .unwrap();
+        let stevens = ConditionTable::parse("Q5: #....... ........ ........ ...m!^10").unwrap();
+
+        assert_eq!(wang.masks(Variable::Q(5)), stevens.masks(Variable::Q(5)));
+        assert_eq!(
+            wang.to_rust(),
+            "pub const A2_ONE_BITS: u32 = 0x00000002;\n\
+             pub const A2_ZERO_BITS: u32 = 0x00000001;\n\
+             pub const A2_C1_SAME_BITS: u32 = 0x00000010;\n\
+             pub const A2_B1_SAME_BITS: u32 = 0x00000004;\n\
+             pub const A2_C1_DIFFERENT_BITS: u32 = 0x80000000;\n\
+             pub const A2_B1_DIFFERENT_BITS: u32 = 0x00000008;\n"
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let error = ConditionTable::parse("a1,1 = 0\n\na1,2 = d1,3").unwrap_err();
+        assert_eq!(error.line, 3);
+
+        assert!(ConditionTable::parse("a1,33 = 0").is_err());
+        assert!(ConditionTable::parse("Q1: ....").is_err());
+        assert!(ConditionTable::parse("Q1: ....x... ........ ........ ........").is_err());
+    }
+
+    #[test]
+    fn rejects_values_outside_the_path() {
+        for text in [
+            "Q-3: ........ ........ ........ .......^",
+            "Q-2: ........ ........ ........ .......m",
+            "Q65: ........ ........ ........ .......0",
+            "b16,1 = 0\nQ66: ........ ........ ........ .......!",
+            "φ64,1 = 1",
+            "a17,1 = 0",
+            "b0,1 = a1,1",
+        ] {
+            let error = ConditionTable::parse(text).unwrap_err();
+            assert_eq!(error.line, text.lines().count(), "{}", text);
+        }
+
+        assert!(ConditionTable::parse("Q-1: ........ ........ ........ .......m").is_ok());
+        assert!(ConditionTable::parse("Q64: ........ ........ ........ .......^").is_ok());
+        assert!(ConditionTable::parse("φ63,1 = 1").is_ok());
+    }
+
+    #[test]
+    fn parses_deltas() {
+        let table = ConditionTable::parse(
+            "delta m4 = 2^31, m11 = -2^15\n\
+             delta m14 = 2^31\n\
+             delta ihv = 2^31, 2^31 + 2^25, -2^25 + 2^31, 0\n\
+             a1,1 = 0",
+        )
+        .unwrap();
+
+        let mut message = [0; 16];
+        (message[4], message[11], message[14]) = (1 << 31, 0xffff8000, 1 << 31);
+        assert_eq!(table.message_delta, Some(message));
+        assert_eq!(
+            table.ihv_delta,
+            Some([0x80000000, 0x82000000, 0x7e000000, 0])
+        );
+        assert_eq!(table.conditions.len(), 1);
+
+        for bad in [
+            "delta m16 = 2^3",
+            "delta m1 = 3",
+            "delta ihv = 0, 0",
+            "delta m1 =",
+        ] {
+            assert!(ConditionTable::parse(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(
+            ConditionTable::parse("a1,1 = 0").unwrap().message_delta,
+            None
+        );
+    }
+
+    #[test]
+    fn apply_then_verify() {
+        let table =
+            ConditionTable::parse("d1,1 = 1, d1,2 = 0, d1,3 = a1,3, d1,4 = a1,4 + 1").unwrap();
+        let masks = table.masks(Variable::Q(2));
+        let lookup = |_| 0b0100;
+
+        let value = masks.apply(0b0010, lookup);
+        assert_eq!(value, 0b1101);
+        assert!(masks.verify(value, lookup));
+        assert_eq!(masks.broken_bits(0b0010, lookup), 0b1111);
+    }
+}
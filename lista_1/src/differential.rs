@@ -0,0 +1,134 @@
+// The differential an identical-prefix collision follows, as data: for each
+// of the two blocks the message difference, the difference the block adds
+// to the chaining values and the sufficient conditions, all read from a path
+// file (see conditions/table.rs). Wang's paths in paths/ are the default;
+// other published paths, or the same ones with the signs flipped, can be
+// searched without touching the source.
+
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::conditions::table::{ConditionTable, ParseError};
+use crate::conditions::{first_block, second_block};
+
+/// The differences of one block of a path, both modular.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Differential {
+    /// `m_prim - m`, word by word.
+    pub message: [u32; 16],
+    /// What the block adds to `ihv_prim - ihv`.
+    pub ihv: [u32; 4],
+}
+
+impl Differential {
+    /// The differences `table` was given, or an error naming the missing
+    /// `delta` line.
+    pub fn of(table: &ConditionTable) -> Result<Differential, String> {
+        Ok(Differential {
+            message: table.message_delta.ok_or("no 'delta m<i> = ...' line")?,
+            ihv: table.ihv_delta.ok_or("no 'delta ihv = ...' line")?,
+        })
+    }
+
+    /// The message `m` pairs with.
+    pub fn apply(&self, m: &[u32; 16]) -> [u32; 16] {
+        std::array::from_fn(|i| m[i].wrapping_add(self.message[i]))
+    }
+
+    pub fn matches(&self, m: &[u32; 16], m_prim: &[u32; 16]) -> bool {
+        self.apply(m) == *m_prim
+    }
+}
+
+/// The paths of both blocks of a collision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paths {
+    pub first: ConditionTable,
+    pub second: ConditionTable,
+    pub first_delta: Differential,
+    pub second_delta: Differential,
+}
+
+impl Paths {
+    /// Wang's paths, which the backends' built-in searches are for. Built
+    /// on first use.
+    pub fn wang() -> &'static Paths {
+        static WANG: OnceLock<Paths> = OnceLock::new();
+
+        WANG.get_or_init(|| {
+            Paths::new(first_block::table(), second_block::table()).expect("paths/ is consistent")
+        })
+    }
+
+    /// Parses the path files of the first and the second block.
+    pub fn parse(first: &str, second: &str) -> Result<Paths, ParseError> {
+        let first = ConditionTable::parse(first)?;
+        let second = ConditionTable::parse(second)?;
+
+        Paths::new(first, second).map_err(|message| ParseError { line: 0, message })
+    }
+
+    /// Checks that both tables give their differences and that the second
+    /// block cancels what the first one leaves.
+    pub fn new(first: ConditionTable, second: ConditionTable) -> Result<Paths, String> {
+        let first_delta =
+            Differential::of(&first).map_err(|error| format!("first block: {}", error))?;
+        let second_delta =
+            Differential::of(&second).map_err(|error| format!("second block: {}", error))?;
+
+        let left = (0..4).map(|i| first_delta.ihv[i].wrapping_add(second_delta.ihv[i]));
+        if left.clone().any(|difference| difference != 0) {
+            return Err(format!(
+                "the blocks leave a difference of {:08x?}",
+                left.collect::<Vec<_>>()
+            ));
+        }
+
+        Ok(Paths {
+            first,
+            second,
+            first_delta,
+            second_delta,
+        })
+    }
+
+    /// Whether these are Wang's paths.
+    pub fn is_wang(&self) -> bool {
+        self == Paths::wang()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::published::wang;
+
+    #[test]
+    fn wang_deltas_have_opposite_signs_in_m11() {
+        let paths = Paths::wang();
+
+        assert_eq!(paths.first_delta.message[11], 1 << 15);
+        assert_eq!(paths.second_delta.message[11], (1u32 << 15).wrapping_neg());
+        assert_eq!(
+            paths.first_delta.ihv,
+            [0x80000000, 0x82000000, 0x82000000, 0x82000000]
+        );
+
+        // the published pair differs by exactly these
+        assert_eq!(paths.first_delta.apply(&wang().m0)[11], 0x5a41f125);
+        assert_eq!(paths.second_delta.apply(&wang().m1)[11], 0x5c154c79);
+        assert!(paths.is_wang());
+    }
+
+    #[test]
+    fn rejects_paths_that_do_not_cancel() {
+        let first = "delta m4 = 2^31\ndelta ihv = 2^31, 0, 0, 0";
+
+        assert!(Paths::parse(first, "delta m4 = 2^31\ndelta ihv = 2^31, 0, 0, 0").is_ok());
+        assert!(Paths::parse(first, "delta m4 = 2^31\ndelta ihv = 0, 0, 0, 0").is_err());
+
+        let error = Paths::parse(first, "delta ihv = 2^31, 0, 0, 0").unwrap_err();
+        assert!(error.message.contains("second block"), "{}", error);
+    }
+}
@@ -0,0 +1,370 @@
+// The MD family described one step at a time. MD4, MD5 and SHA-1 update
+// their working state the same way: every step computes one new value Q[t]
+// from the last few values and one message word, and can be run backwards to
+// find the word that yields a chosen Q[t]. `MdFamily` captures a hash as its
+// boolean functions, rotations, constants and message word order plus that
+// forward and inverse step, so that message modification (`Block`), tracing
+// (trace.rs) and condition checking work on any of them.
+//
+// Q values are indexed like in Wang's papers: Q[1 - REGISTERS]..Q[0] is the
+// chaining value, Q[t] is computed by step t - 1 (steps are 0-based, like
+// `word_index`).
+
+use std::marker::PhantomData;
+use std::ops::RangeInclusive;
+
+use crate::conditions::table::{ConditionTable, Masks, Variable};
+
+/// Most Q values of any family member: 80 SHA-1 steps after five chaining
+/// value words.
+const MAX_VALUES: usize = 85;
+
+/// A hash function of the MD family, as a sequence of invertible steps.
+pub trait MdFamily {
+    /// Number of steps of the compression function.
+    const STEPS: usize;
+    /// Number of Q values a step reads, which is also the number of words of
+    /// the chaining value.
+    const REGISTERS: usize;
+
+    /// Boolean function of step `t`.
+    fn boolean_function(t: usize) -> fn(u32, u32, u32) -> u32;
+
+    /// Additive constant of step `t`.
+    fn constant(t: usize) -> u32;
+
+    /// Rotation amount of step `t`.
+    fn shift(t: usize) -> u32;
+
+    /// Index of the message word step `t` adds, `None` if it adds an
+    /// expanded word (SHA-1 from step 16 on).
+    fn word_index(t: usize) -> Option<usize>;
+
+    /// The word step `t` adds when compressing `m`.
+    fn word(t: usize, m: &[u32; 16]) -> u32 {
+        m[Self::word_index(t).expect("the word of the step is a message word")]
+    }
+
+    /// Output of the boolean function of step `t` (Wang's φ), given the
+    /// values the step reads, Q[t + 1 - REGISTERS]..Q[t].
+    fn phi(t: usize, q: &[u32]) -> u32;
+
+    /// Q[t + 1], given Q[t + 1 - REGISTERS]..Q[t] and the word of step `t`.
+    fn step(t: usize, q: &[u32], word: u32) -> u32;
+
+    /// The word that makes step `t` yield `next` from the values `q`.
+    fn inverse_step(t: usize, q: &[u32], next: u32) -> u32;
+
+    /// Q[1 - REGISTERS]..Q[0] for the chaining value `state`.
+    fn initial_values(state: &[u32]) -> Vec<u32>;
+
+    /// The chaining value after the compression, given the one before it and
+    /// the last REGISTERS values.
+    fn output(state: &[u32], last: &[u32]) -> Vec<u32>;
+}
+
+/// The compression function of `F` on one block.
+pub fn compress<F: MdFamily>(state: &[u32], words: &[u32; 16]) -> Vec<u32> {
+    let mut q = F::initial_values(state);
+
+    for t in 0..F::STEPS {
+        let next = F::step(t, &q[t..], F::word(t, words));
+        q.push(next);
+    }
+
+    F::output(state, &q[F::STEPS..])
+}
+
+/// The conditions of a path indexed by Q value, plus the bits of every Q
+/// value no condition refers to.
+pub(crate) struct Conditions {
+    masks: Vec<Masks>,
+    /// The conditions on φ, by step, for the steps that have any.
+    phi: Vec<Option<Masks>>,
+    free: Vec<u32>,
+    /// Index of Q[0] in `masks` and `free`.
+    offset: usize,
+}
+
+impl Conditions {
+    pub(crate) fn new<F: MdFamily>(table: &ConditionTable) -> Conditions {
+        let offset = F::REGISTERS - 1;
+        let masks = (-(offset as i32)..=F::STEPS as i32)
+            .map(|t| table.masks(Variable::Q(t)))
+            .collect::<Vec<_>>();
+        let mut free = masks
+            .iter()
+            .map(|masks| {
+                let relations = masks.same.iter().chain(&masks.different);
+                !(masks.one | masks.zero | relations.fold(0, |bits, (_, mask)| bits | mask))
+            })
+            .collect::<Vec<_>>();
+
+        for masks in &masks {
+            for &(other, mask) in masks.same.iter().chain(&masks.different) {
+                if let Variable::Q(r) = other {
+                    free[(r + offset as i32) as usize] &= !mask;
+                }
+            }
+        }
+
+        let phi = (0..F::STEPS as u32)
+            .map(|t| Some(table.masks(Variable::Phi(t))).filter(|masks| *masks != Masks::default()))
+            .collect();
+
+        Conditions {
+            masks,
+            phi,
+            free,
+            offset,
+        }
+    }
+
+    /// The bits of Q[t] that no condition refers to.
+    pub(crate) fn free(&self, t: usize) -> u32 {
+        self.free[t + self.offset]
+    }
+}
+
+/// Q[1 - REGISTERS]..Q[STEPS] of one compression, next to the words that
+/// produce them.
+pub(crate) struct Block<F> {
+    q: [u32; MAX_VALUES],
+    pub(crate) m: [u32; 16],
+    family: PhantomData<fn() -> F>,
+}
+
+impl<F> Clone for Block<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for Block<F> {}
+
+impl<F: MdFamily> Block<F> {
+    pub(crate) fn new(state: &[u32], words: &[u32; 16]) -> Block<F> {
+        let mut q = [0; MAX_VALUES];
+        q[..F::REGISTERS].copy_from_slice(&F::initial_values(state));
+
+        Block {
+            q,
+            m: *words,
+            family: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn q(&self, t: usize) -> u32 {
+        self.q[t + F::REGISTERS - 1]
+    }
+
+    /// Q[1 - REGISTERS]..Q[t], as far as computed.
+    pub(crate) fn values(&self, t: usize) -> &[u32] {
+        &self.q[..t + F::REGISTERS]
+    }
+
+    /// Q[t - REGISTERS]..Q[t - 1]: the inputs of the step computing Q[t].
+    #[inline]
+    fn inputs(&self, t: usize) -> &[u32] {
+        &self.q[t - 1..t - 1 + F::REGISTERS]
+    }
+
+    /// Computes Q[t], 1 <= t <= STEPS, from its inputs and word.
+    #[inline]
+    pub(crate) fn compute(&mut self, t: usize) {
+        let next = F::step(t - 1, self.inputs(t), F::word(t - 1, &self.m));
+        self.q[t + F::REGISTERS - 1] = next;
+    }
+
+    /// Sets the word of the step computing Q[t] to the one that yields the
+    /// current Q[t].
+    #[inline]
+    pub(crate) fn fix_word(&mut self, t: usize) {
+        let word = F::word_index(t - 1).expect("only message words can be fixed");
+        self.m[word] = F::inverse_step(t - 1, self.inputs(t), self.q(t));
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, variable: Variable) -> u32 {
+        match variable {
+            Variable::Q(t) => self.q[(t + F::REGISTERS as i32 - 1) as usize],
+            Variable::Phi(t) => F::phi(t as usize, self.inputs(t as usize + 1)),
+        }
+    }
+
+    /// Q[t] with its conditions forced on.
+    #[inline]
+    pub(crate) fn apply(&self, t: usize, conditions: &Conditions) -> u32 {
+        conditions.masks[t + conditions.offset].apply(self.q(t), |other| self.get(other))
+    }
+
+    /// Checks the conditions on Q[t] and on φ of the step computing it.
+    #[inline]
+    pub(crate) fn holds(&self, t: usize, conditions: &Conditions) -> bool {
+        let phi = Variable::Phi(t as u32 - 1);
+
+        self.apply(t, conditions) == self.q(t)
+            && conditions.phi[t - 1]
+                .as_ref()
+                .is_none_or(|masks| masks.verify(self.get(phi), |other| self.get(other)))
+    }
+
+    /// Computes Q[t] and forces its conditions on, leaving the word of its
+    /// step as it was.
+    #[inline]
+    pub(crate) fn force(&mut self, t: usize, conditions: &Conditions) {
+        self.compute(t);
+        self.q[t + F::REGISTERS - 1] = self.apply(t, conditions);
+    }
+
+    /// Like `force`, then sets the word of the step to the one that yields
+    /// the forced value.
+    #[inline]
+    pub(crate) fn modify(&mut self, t: usize, conditions: &Conditions) {
+        self.force(t, conditions);
+        self.fix_word(t);
+    }
+
+    /// Single-message modification of the values computed from the 16
+    /// message words as they are.
+    pub(crate) fn round_1(&mut self, conditions: &Conditions) {
+        for t in 1..=16 {
+            self.modify(t, conditions);
+        }
+    }
+
+    /// Computes the value Q[t] after round 1 and corrects it by multi-message
+    /// modification if it breaks a condition. Leaves the block untouched
+    /// and returns `false` if the correction breaks round 1.
+    pub(crate) fn correct(&mut self, t: usize, conditions: &Conditions) -> bool {
+        self.compute(t);
+        if self.holds(t, conditions) {
+            return true;
+        }
+
+        let mut block = *self;
+        block.q[t + F::REGISTERS - 1] = block.apply(t, conditions);
+        block.fix_word(t);
+
+        // the round 1 value computed from the new word moves, the next
+        // REGISTERS stay where they are
+        let moved = F::word_index(t - 1).expect("round 2 adds message words") + 1;
+        block.compute(moved);
+        if !(moved..=moved + F::REGISTERS - 2).all(|u| block.holds(u, conditions)) {
+            return false;
+        }
+        for u in moved + 1..=moved + F::REGISTERS {
+            block.fix_word(u);
+        }
+        debug_assert_eq!(block.q(t), {
+            let mut check = block;
+            check.compute(t);
+            check.q(t)
+        });
+
+        *self = block;
+        true
+    }
+
+    /// Flips `bits` of Q[k] and recomputes the words of the steps that read
+    /// it, keeping every other round 1 value.
+    pub(crate) fn tunnel(&mut self, k: usize, bits: u32) {
+        self.q[k + F::REGISTERS - 1] ^= bits;
+        for u in k..=k + F::REGISTERS {
+            self.fix_word(u);
+        }
+    }
+
+    /// Computes the values of `steps` and checks their conditions.
+    pub(crate) fn check(&mut self, steps: RangeInclusive<usize>, conditions: &Conditions) -> bool {
+        let end = *steps.end();
+        self.reach(steps, conditions) == end
+    }
+
+    /// Computes the values of `steps` up to the first one that breaks a
+    /// condition and returns the step before it, the last step if none
+    /// does.
+    pub(crate) fn reach(&mut self, steps: RangeInclusive<usize>, conditions: &Conditions) -> usize {
+        let end = *steps.end();
+        steps
+            .into_iter()
+            .find(|&t| {
+                self.compute(t);
+                !self.holds(t, conditions)
+            })
+            .map_or(end, |t| t - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::md4::Md4;
+    use crate::md5::{self, Md5, INITIAL_STATE};
+    use crate::sha1::Sha1;
+
+    fn steps_invert<F: MdFamily>() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for t in 0..F::STEPS {
+            let q = (0..F::REGISTERS).map(|_| rng.gen()).collect::<Vec<u32>>();
+            let word = rng.gen();
+
+            let next = F::step(t, &q, word);
+            assert_eq!(F::inverse_step(t, &q, next), word, "step {}", t);
+        }
+    }
+
+    #[test]
+    fn inverse_steps_recover_the_word() {
+        steps_invert::<Md4>();
+        steps_invert::<Md5>();
+        steps_invert::<Sha1>();
+        steps_invert::<Sha1<20>>();
+    }
+
+    #[test]
+    fn compress_matches_the_hand_written_functions() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for _ in 0..16 {
+            let words = rng.gen();
+
+            assert_eq!(
+                compress::<Md5>(&INITIAL_STATE, &words),
+                md5::compress(&INITIAL_STATE, &words)
+            );
+            assert_eq!(
+                compress::<Md4>(&INITIAL_STATE, &words),
+                crate::md4::compress(&INITIAL_STATE, &words)
+            );
+        }
+    }
+
+    #[test]
+    fn fixed_words_reproduce_the_values() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let state: [u32; 5] = rng.gen();
+
+        // choose Q[1]..Q[16] freely and solve for the words
+        let mut block = Block::<Sha1<24>>::new(&state, &[0; 16]);
+        for t in 1..=16 {
+            block.q[t + 4] = rng.gen();
+            block.fix_word(t);
+        }
+
+        let mut again = Block::<Sha1<24>>::new(&state, &block.m);
+        for t in 1..=24 {
+            again.compute(t);
+        }
+        assert_eq!(again.values(16), block.values(16));
+        assert_eq!(
+            compress::<Sha1<24>>(&state, &block.m),
+            Sha1::<24>::output(&state, &again.values(24)[24..])
+        );
+    }
+}
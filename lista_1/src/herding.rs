@@ -0,0 +1,263 @@
+// The herding (Nostradamus) attack of Kelsey and Kohno on MD5 truncated to
+// `bits` bits of chaining value, small enough to run on a CPU.
+//
+// A diamond structure is a binary tree of chaining values: 2^depth leaves,
+// and for every pair of siblings two blocks that lead both to the same
+// parent, found by a birthday search from two different chaining values.
+// Every leaf thus reaches the root in `depth` blocks. Committing to the
+// hash of the root, finalized for a message length announced in advance,
+// lets us later produce a message with that hash for *any* prefix of that
+// length: one linking block takes the prefix to some leaf, which takes
+// about 2^(bits - depth) trials, and the tree does the rest.
+//
+// The truncated MD5 keeps the low `bits` bits of the first chaining value
+// word and runs the full MD5 compression from the IV with that word
+// replaced, so the collision machinery starts from arbitrary chaining
+// values like the full attack would.
+
+use std::collections::{HashMap, HashSet};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::length_extension::glue_padding;
+use crate::md5::{bytes_to_words, compress, words_to_bytes, INITIAL_STATE};
+use crate::search::batch_seed;
+
+/// MD5 with its chaining value truncated to `bits` bits, at most 32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Truncated {
+    pub bits: u32,
+}
+
+impl Truncated {
+    fn mask(&self) -> u32 {
+        u32::MAX >> (32 - self.bits)
+    }
+
+    pub fn iv(&self) -> u32 {
+        INITIAL_STATE[0] & self.mask()
+    }
+
+    pub fn compress(&self, h: u32, words: &[u32; 16]) -> u32 {
+        let mut state = INITIAL_STATE;
+        state[0] = h;
+
+        compress(&state, words)[0] & self.mask()
+    }
+
+    /// The chaining value after the block-aligned `data`.
+    pub fn chaining_value(&self, h: u32, data: &[u8]) -> u32 {
+        assert!(data.len().is_multiple_of(64), "data is not block-aligned");
+
+        data.chunks_exact(64)
+            .fold(h, |h, block| self.compress(h, &bytes_to_words(block)))
+    }
+
+    /// The digest of `data`, padded like MD5.
+    pub fn digest(&self, data: &[u8]) -> u32 {
+        let padded = [data, &glue_padding(data.len() as u64)].concat();
+        self.chaining_value(self.iv(), &padded)
+    }
+
+    /// Two blocks that lead `a` and `b` to the same chaining value, found
+    /// by a birthday search that grows one table per side.
+    fn collide(&self, a: u32, b: u32, rng: &mut impl Rng) -> ([u32; 16], [u32; 16], u32) {
+        let mut seen = [HashMap::new(), HashMap::new()];
+
+        for side in [0, 1].into_iter().cycle() {
+            let block: [u32; 16] = rng.gen();
+            let h = self.compress([a, b][side], &block);
+
+            if let Some(&other) = seen[1 - side].get(&h) {
+                return match side {
+                    0 => (block, other, h),
+                    _ => (other, block, h),
+                };
+            }
+            seen[side].insert(h, block);
+        }
+
+        unreachable!()
+    }
+}
+
+/// Deepest diamond `Diamond::build` makes: 2^24 leaves and as many birthday
+/// searches.
+pub const MAX_DEPTH: u32 = 24;
+
+/// A diamond structure: `levels[0]` are the leaves, `levels[depth]` is the
+/// root, and `blocks[l][i]` takes `levels[l][i]` to `levels[l + 1][i / 2]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diamond {
+    pub hash: Truncated,
+    pub levels: Vec<Vec<u32>>,
+    pub blocks: Vec<Vec<[u32; 16]>>,
+}
+
+impl Diamond {
+    /// Builds a diamond with 2^`depth` random leaves, deterministically for
+    /// a given `seed`. The leaves are distinct chaining values, and some
+    /// have to be left over for the linking block to miss, so `depth` has
+    /// to be below `hash.bits`, and at most `MAX_DEPTH`.
+    pub fn build(hash: Truncated, depth: u32, seed: u64) -> Result<Diamond, String> {
+        if depth >= hash.bits {
+            return Err(format!(
+                "a diamond of depth {} needs a chaining value of more than {} bits",
+                depth, hash.bits
+            ));
+        }
+        if depth > MAX_DEPTH {
+            return Err(format!("the depth is larger than {}", MAX_DEPTH));
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut leaves = Vec::new();
+        let mut seen = HashSet::new();
+        while leaves.len() < 1 << depth {
+            let leaf = rng.gen::<u32>() & hash.mask();
+            if seen.insert(leaf) {
+                leaves.push(leaf);
+            }
+        }
+
+        let mut diamond = Diamond {
+            hash,
+            levels: vec![leaves],
+            blocks: Vec::new(),
+        };
+
+        for level in 0..depth as usize {
+            let (mut parents, mut blocks) = (Vec::new(), Vec::new());
+
+            for (idx, pair) in diamond.levels[level].chunks_exact(2).enumerate() {
+                // one generator per pair, so that pairs could be built in
+                // any order
+                let seed = batch_seed(seed, ((level as u64) << 32) | idx as u64);
+                let (left, right, parent) =
+                    hash.collide(pair[0], pair[1], &mut StdRng::seed_from_u64(seed));
+
+                parents.push(parent);
+                blocks.extend([left, right]);
+            }
+
+            diamond.levels.push(parents);
+            diamond.blocks.push(blocks);
+        }
+
+        Ok(diamond)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn root(&self) -> u32 {
+        self.levels[self.depth()][0]
+    }
+
+    /// The blocks that take leaf `leaf` to the root.
+    pub fn path(&self, leaf: usize) -> Vec<[u32; 16]> {
+        (0..self.depth())
+            .map(|level| self.blocks[level][leaf >> level])
+            .collect()
+    }
+
+    /// The digest committed to for messages of `prefix_blocks` blocks of
+    /// chosen content, followed by the linking block and the path.
+    pub fn commit(&self, prefix_blocks: usize) -> u32 {
+        let len = (prefix_blocks + 1 + self.depth()) * 64;
+        self.hash
+            .chaining_value(self.root(), &glue_padding(len as u64))
+    }
+
+    /// Completes `prefix`, zero-padded to `prefix_blocks` blocks, to a
+    /// message whose digest is `commit(prefix_blocks)`.
+    pub fn herd(&self, prefix: &[u8], prefix_blocks: usize, seed: u64) -> Vec<u8> {
+        assert!(prefix.len() <= prefix_blocks * 64, "the prefix is too long");
+        let mut padded = prefix.to_vec();
+        padded.resize(prefix_blocks * 64, 0);
+
+        let h = self.hash.chaining_value(self.hash.iv(), &padded);
+
+        let leaves = self.levels[0]
+            .iter()
+            .enumerate()
+            .map(|(leaf, &value)| (value, leaf))
+            .collect::<HashMap<_, _>>();
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let (link, leaf) = loop {
+            let block: [u32; 16] = rng.gen();
+            if let Some(&leaf) = leaves.get(&self.hash.compress(h, &block)) {
+                break (block, leaf);
+            }
+        };
+
+        let mut message = padded;
+        message.extend(words_to_bytes(&link));
+        for block in self.path(leaf) {
+            message.extend(words_to_bytes(&block));
+        }
+
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: Truncated = Truncated { bits: 20 };
+
+    #[test]
+    fn every_leaf_reaches_the_root() {
+        let diamond = Diamond::build(HASH, 4, 0).unwrap();
+
+        assert_eq!(diamond.levels[0].len(), 16);
+        for (leaf, &value) in diamond.levels[0].iter().enumerate() {
+            let path = words_to_bytes(&diamond.path(leaf).concat());
+            assert_eq!(HASH.chaining_value(value, &path), diamond.root());
+        }
+    }
+
+    #[test]
+    fn herds_any_prefix_to_the_commitment() {
+        let diamond = Diamond::build(HASH, 6, 1).unwrap();
+        let digest = diamond.commit(2);
+
+        // the second prefix would fit a single block
+        for prefix in [
+            &b"The 2026 World Cup was won by Argentina, 3-1 after extra time."[..],
+            b"The 2026 World Cup was won by Spain.",
+        ] {
+            let message = diamond.herd(prefix, 2, 7);
+
+            assert!(message.starts_with(prefix));
+            assert_eq!(message.len(), (2 + 1 + 6) * 64);
+            assert_eq!(HASH.digest(&message), digest);
+        }
+    }
+
+    #[test]
+    fn truncated_md5_keeps_the_low_bits() {
+        let full = Truncated { bits: 32 };
+        assert_eq!(full.iv(), INITIAL_STATE[0]);
+        assert_eq!(
+            full.compress(full.iv(), &[0; 16]),
+            compress(&INITIAL_STATE, &[0; 16])[0]
+        );
+
+        assert_eq!(HASH.compress(1, &[0; 16]) >> 20, 0);
+        assert_eq!(Diamond::build(HASH, 3, 5), Diamond::build(HASH, 3, 5));
+        assert!(Diamond::build(HASH, 20, 0).is_err());
+        assert!(Diamond::build(Truncated { bits: 32 }, MAX_DEPTH + 1, 0).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "too long")]
+    fn rejects_a_prefix_longer_than_announced() {
+        Diamond::build(HASH, 1, 0).unwrap().herd(&[0; 65], 1, 0);
+    }
+}
@@ -0,0 +1,98 @@
+// Length extension: an MD5 digest is the complete chaining value after the
+// padded message, so whoever knows MD5(x) and the length of x can continue
+// hashing from it. For a secret-prefix MAC, tag = MD5(secret || msg), that
+// forges the tag of msg || glue || suffix, where glue is the padding MD5
+// appended to secret || msg, without knowing the secret.
+
+use crate::mac::Mac;
+use crate::md5::{digest, padding, Md5};
+
+/// The padding MD5 appends to a message of `len` bytes, length included.
+pub fn glue_padding(len: u64) -> Vec<u8> {
+    let mut glue = padding(len);
+    glue.extend(len.wrapping_mul(8).to_le_bytes());
+    glue
+}
+
+/// Given `known_digest` = MD5(x) for an unknown x of `original_len` bytes,
+/// returns MD5(x || glue || suffix) and glue.
+pub fn length_extend(
+    known_digest: &[u8; 16],
+    original_len: u64,
+    suffix: &[u8],
+) -> ([u8; 16], Vec<u8>) {
+    let glue = glue_padding(original_len);
+
+    let mut state = [0; 4];
+    for (word, bytes) in state.iter_mut().zip(known_digest.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+
+    let mut md5 = Md5::from_state(state, original_len + glue.len() as u64);
+    md5.update(suffix);
+
+    (md5.finalize(), glue)
+}
+
+/// The naive MAC `MD5(key || message)` that length extension breaks.
+#[derive(Debug, Clone)]
+pub struct SecretPrefixMac {
+    key: Vec<u8>,
+}
+
+impl SecretPrefixMac {
+    pub fn new(key: &[u8]) -> Self {
+        SecretPrefixMac { key: key.to_vec() }
+    }
+}
+
+impl Mac for SecretPrefixMac {
+    type Tag = [u8; 16];
+
+    fn tag(&self, message: &[u8]) -> [u8; 16] {
+        digest(&[&self.key, message].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] =
+        b"comment1=cooking%20MCs;userdata=foo;comment2=%20like%20a%20pound%20of%20bacon";
+    const SUFFIX: &[u8] = b";admin=true";
+
+    #[test]
+    fn forges_a_secret_prefix_mac() {
+        // key lengths around the block boundary the glue has to cross
+        for key_len in [0, 1, 16, 55, 56, 63, 64, 100] {
+            let mac = SecretPrefixMac::new(&vec![0x5a; key_len]);
+            let tag = mac.tag(MESSAGE);
+
+            let (forged, glue) = length_extend(&tag, (key_len + MESSAGE.len()) as u64, SUFFIX);
+            let message = [MESSAGE, &glue, SUFFIX].concat();
+
+            assert!(mac.verify(&message, &forged), "{}", key_len);
+        }
+    }
+
+    #[test]
+    fn wrong_length_guess_fails() {
+        let mac = SecretPrefixMac::new(b"YELLOW SUBMARINE");
+        let tag = mac.tag(MESSAGE);
+
+        let (forged, glue) = length_extend(&tag, MESSAGE.len() as u64 + 15, SUFFIX);
+        assert!(!mac.verify(&[MESSAGE, &glue, SUFFIX].concat(), &forged));
+    }
+
+    #[test]
+    fn glue_completes_the_padded_message() {
+        for len in [0u64, 55, 56, 64, 119] {
+            let glue = glue_padding(len);
+            assert!((len + glue.len() as u64).is_multiple_of(64), "{}", len);
+        }
+
+        let (forged, _) = length_extend(&digest(b""), 0, b"");
+        assert_eq!(forged, digest(&glue_padding(0)));
+    }
+}
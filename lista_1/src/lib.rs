@@ -0,0 +1,18 @@
+pub mod backend;
+pub mod chosen_prefix;
+pub mod conditions;
+pub mod differential;
+pub mod family;
+pub mod herding;
+pub mod length_extension;
+pub mod mac;
+pub mod md4;
+pub mod md5;
+pub mod multicollision;
+#[cfg(test)]
+mod published;
+pub mod search;
+pub mod sha1;
+pub mod simd;
+pub mod telemetry;
+pub mod trace;
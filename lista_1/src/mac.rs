@@ -0,0 +1,202 @@
+// Message authentication on top of the local hash functions: the `Mac`
+// abstraction, HMAC (RFC 2104) for any `BlockHash`, and a tag comparison
+// that takes the same time wherever the tags differ.
+//
+// Why HMAC survives length extension where `SecretPrefixMac` does not: the
+// tag is H(K ^ opad || H(K ^ ipad || message)). Extending it continues the
+// *outer* hash, giving H(K ^ opad || inner || glue || suffix). A verifier
+// never hashes anything but a fresh 16 byte inner digest after K ^ opad,
+// so that value is not the tag of any message. Extending the inner hash
+// instead would work, but its output is only ever seen through the outer
+// hash under the key.
+
+use std::marker::PhantomData;
+
+use crate::md5::Md5;
+
+/// A Merkle–Damgård hash as HMAC needs it: streaming, with a known block
+/// size.
+pub trait BlockHash: Default {
+    /// Block size in bytes.
+    const BLOCK_SIZE: usize;
+    type Output: AsRef<[u8]>;
+
+    fn update(&mut self, data: &[u8]);
+    fn finalize(self) -> Self::Output;
+
+    fn digest(data: &[u8]) -> Self::Output {
+        let mut hash = Self::default();
+        hash.update(data);
+        hash.finalize()
+    }
+}
+
+impl BlockHash for Md5 {
+    const BLOCK_SIZE: usize = 64;
+    type Output = [u8; 16];
+
+    fn update(&mut self, data: &[u8]) {
+        Md5::update(self, data)
+    }
+
+    fn finalize(self) -> [u8; 16] {
+        Md5::finalize(self)
+    }
+}
+
+/// A keyed tag over messages.
+pub trait Mac {
+    type Tag: AsRef<[u8]>;
+
+    fn tag(&self, message: &[u8]) -> Self::Tag;
+
+    /// Whether `tag` is the tag of `message`, compared in constant time.
+    fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+        constant_time_eq(self.tag(message).as_ref(), tag)
+    }
+}
+
+/// Compares `a` and `b` without branching on their contents, so the time
+/// taken does not tell how long a common prefix a forged tag has. Only the
+/// lengths, which are public, return early.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let difference = a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(difference) == 0
+}
+
+/// HMAC with the hash `H` under one key.
+#[derive(Debug, Clone)]
+pub struct Hmac<H> {
+    /// The key padded to a block, hashed first if it was longer.
+    key: Vec<u8>,
+    hash: PhantomData<H>,
+}
+
+impl<H: BlockHash> Hmac<H> {
+    pub fn new(key: &[u8]) -> Self {
+        let mut key = if key.len() > H::BLOCK_SIZE {
+            H::digest(key).as_ref().to_vec()
+        } else {
+            key.to_vec()
+        };
+        key.resize(H::BLOCK_SIZE, 0);
+
+        Hmac {
+            key,
+            hash: PhantomData,
+        }
+    }
+
+    fn keyed(&self, pad: u8) -> H {
+        let mut hash = H::default();
+        hash.update(&self.key.iter().map(|byte| byte ^ pad).collect::<Vec<_>>());
+        hash
+    }
+}
+
+impl<H: BlockHash> Mac for Hmac<H> {
+    type Tag = H::Output;
+
+    fn tag(&self, message: &[u8]) -> H::Output {
+        let mut inner = self.keyed(0x36);
+        inner.update(message);
+
+        let mut outer = self.keyed(0x5c);
+        outer.update(inner.finalize().as_ref());
+        outer.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::length_extension::{length_extend, SecretPrefixMac};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn rfc_2202_hmac_md5() {
+        let large_key = [0xaa; 80];
+        let suite: [(&[u8], &[u8], &str); 7] = [
+            (&[0x0b; 16], b"Hi There", "9294727a3638bb1c13f48ef8158bfc9d"),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "750c783e6ab0b503eaa86e310a5db738",
+            ),
+            (&[0xaa; 16], &[0xdd; 50], "56be34521d144c88dbb8c733f0e8b3f6"),
+            (
+                &(1..=25).collect::<Vec<u8>>(),
+                &[0xcd; 50],
+                "697eaf0aca3a3aea3a75164746ffaa79",
+            ),
+            (
+                &[0x0c; 16],
+                b"Test With Truncation",
+                "56461ef2342edc00f9bab995690efd4c",
+            ),
+            (
+                &large_key,
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "6b1ab7fe4bd7bf8f0b62e6ce61b9d0cd",
+            ),
+            (
+                &large_key,
+                b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data",
+                "6f630fad67cda0ee1fb1f562db3aa53e",
+            ),
+        ];
+
+        for (key, message, expected) in suite {
+            assert_eq!(hex(&Hmac::<Md5>::new(key).tag(message)), expected);
+        }
+    }
+
+    #[test]
+    fn constant_time_eq_compares() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"tag", b"tag"));
+        assert!(!constant_time_eq(b"tag", b"tab"));
+        assert!(!constant_time_eq(b"tag", b"tags"));
+    }
+
+    #[test]
+    fn hmac_resists_length_extension() {
+        let key = b"YELLOW SUBMARINE";
+        let message = b"user=bob";
+        let suffix = b";admin=true";
+
+        // the same forgery breaks MD5(key || message) ...
+        let naive = SecretPrefixMac::new(key);
+        let len = (key.len() + message.len()) as u64;
+        let (forged, glue) = length_extend(&naive.tag(message), len, suffix);
+        assert!(naive.verify(&[&message[..], &glue, suffix].concat(), &forged));
+
+        // ... but not HMAC, whatever length the attacker assumes for the
+        // outer hash: the outer key block plus one digest, or plus the
+        // message as if it were hashed directly
+        let hmac = Hmac::<Md5>::new(key);
+        let tag = hmac.tag(message);
+        for len in [64 + 16, 64 + message.len() as u64] {
+            let (forged, glue) = length_extend(&tag, len, suffix);
+            assert!(!hmac.verify(&[&message[..], &glue, suffix].concat(), &forged));
+        }
+
+        // the extension is a valid digest, just of a value only the outer
+        // hash ever sees
+        let mut inner = hmac.keyed(0x36);
+        inner.update(message);
+        let inner = inner.finalize();
+
+        let (forged, glue) = length_extend(&tag, 64 + 16, suffix);
+        let mut outer = hmac.keyed(0x5c);
+        outer.update(&[&inner[..], &glue, suffix].concat());
+        assert_eq!(outer.finalize(), forged);
+    }
+}
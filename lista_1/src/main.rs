@@ -1,30 +1,679 @@
-use md5::{hash, second_step, INITIAL_STATE};
-
-mod md5;
-
-fn main() {
-    let m0 = [
-        0x2dd31d1, 0xc4eee6c5, 0x69a3d69, 0x5cf9af98, 0x87b5ca2f, 0xab7e4612, 0x3e580440,
-        0x897ffbb8, 0x634ad55, 0x2b3f409, 0x8388e483, 0x5a417125, 0xe8255108, 0x9fc9cdf7,
-        0xf2bd1dd9, 0x5b3c3780,
-    ];
-    let m0_prim = [
-        0x2dd31d1, 0xc4eee6c5, 0x69a3d69, 0x5cf9af98, 0x7b5ca2f, 0xab7e4612, 0x3e580440,
-        0x897ffbb8, 0x634ad55, 0x2b3f409, 0x8388e483, 0x5a41f125, 0xe8255108, 0x9fc9cdf7,
-        0x72bd1dd9, 0x5b3c3780,
-    ];
-
-    let collision = second_step(m0, m0_prim);
-
-    println!("Collision found:");
-    println!("{}", collision);
-
-    // verify the collision
-    assert_eq!(
-        hash(&hash(&INITIAL_STATE, &collision.m0), &collision.m1),
-        hash(
-            &hash(&INITIAL_STATE, &collision.m0_prim),
-            &collision.m1_prim
-        )
-    );
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, Instant};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use lista_1::backend::{CandidateBackend, CpuBackend, Strategy, Tunnel};
+use lista_1::chosen_prefix::{ChosenPrefix, PathDirectory, Progress, Stage};
+use lista_1::conditions::table::ConditionTable;
+use lista_1::conditions::{first_block, second_block};
+use lista_1::herding::{Diamond, Truncated, MAX_DEPTH};
+use lista_1::length_extension::length_extend;
+use lista_1::md5::{chaining_value, colliding_files, digest, pad_prefix, Collision};
+use lista_1::search::{save_json, PathFiles, Search};
+use lista_1::telemetry::Telemetry;
+use lista_1::trace::trace;
+
+#[derive(Parser)]
+#[command(about = "MD5 collision finder")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Finds two messages with the same MD5 that start with a common prefix.
+    Find {
+        /// File the messages start with, zero-padded to a block boundary.
+        /// Without it the collision starts from the MD5 IV.
+        #[arg(long)]
+        prefix_file: Option<PathBuf>,
+        /// Seed of the search; the same seed and settings find the same
+        /// collision. Random if not given.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Worker threads of the CPU backend.
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        threads: Option<usize>,
+        /// Trials per thread in one batch of the search.
+        #[arg(long)]
+        max_iterations: Option<usize>,
+        /// How the second block gets through round 2. Wang's first block is
+        /// always searched with its own round 1 modification and no tunnels.
+        #[arg(long, value_enum, default_value_t = Modification::MultiMessage)]
+        strategy: Modification,
+        /// Tunnels to enumerate on second blocks, outermost first, e.g.
+        /// `q4,q10,q9`; `--tunnels` alone for none. Defaults to q4,q10,q9
+        /// with multi-message modification and none with `kernel`.
+        #[arg(long, value_delimiter = ',', num_args = 0..)]
+        tunnels: Option<Vec<Tunnel>>,
+        #[arg(long, value_enum, default_value_t = Format::Raw)]
+        format: Format,
+        /// Saves the search to this file now and then, to be continued with
+        /// `--resume`.
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+        /// Seconds between two checkpoints.
+        #[arg(long, default_value_t = 60)]
+        checkpoint_interval: u64,
+        /// Continues the search saved in this file, with the prefix, seed and
+        /// backend settings it was started with; keeps saving to it unless
+        /// `--checkpoint` is given.
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "prefix_file", "seed", "threads", "max_iterations", "strategy", "tunnels",
+                "first_path",
+            ]
+        )]
+        resume: Option<PathBuf>,
+        /// Differential path of the first block, in the format of
+        /// paths/first_block.txt with its `delta` lines; Wang's if not given.
+        #[arg(long, requires = "second_path")]
+        first_path: Option<PathBuf>,
+        /// Differential path of the second block, which has to cancel the
+        /// chaining value difference the first one leaves.
+        #[arg(long, requires = "first_path")]
+        second_path: Option<PathBuf>,
+        /// Where `--format raw` writes the two colliding files.
+        #[arg(long, num_args = 2, value_names = ["FILE1", "FILE2"])]
+        output: Vec<PathBuf>,
+        /// Appends a JSON report per batch to this file: throughput, the
+        /// fraction of trials surviving each step after b4 and the expected
+        /// time.
+        #[arg(long)]
+        log: Option<PathBuf>,
+        /// No status line on stderr.
+        #[arg(long)]
+        quiet: bool,
+    },
+    /// Finds suffixes that give two different files the same MD5. The
+    /// near-collision blocks need differential paths constructed elsewhere,
+    /// saved as `block<k>.txt` in `--paths`, with `delta` lines giving the
+    /// message and chaining value differences of the block; without one the
+    /// search stops and says which it needs.
+    ChosenPrefix {
+        file1: PathBuf,
+        file2: PathBuf,
+        /// Directory of the near-collision block paths.
+        #[arg(long)]
+        paths: PathBuf,
+        /// Saves the search after every step; continues the one saved there
+        /// if the file exists, which has to be a search of the same files.
+        #[arg(long)]
+        checkpoint: PathBuf,
+        /// Seed of the search. Random if not given.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Worker threads of the CPU backend; a continued search keeps
+        /// those it was started with.
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        threads: Option<usize>,
+        /// Where to write the two files with their suffixes.
+        #[arg(long, num_args = 2, value_names = ["FILE1", "FILE2"], required = true)]
+        output: Vec<PathBuf>,
+    },
+    /// Compares the second-block strategies of `find`: trials per second
+    /// and the expected time to a candidate of each.
+    Bench {
+        /// A file whose first block follows Wang's first-block path, e.g.
+        /// one of the files `find` writes; the strategies search for a
+        /// second block after it.
+        file: PathBuf,
+        /// Seconds to run each strategy for.
+        #[arg(long, default_value_t = 10)]
+        seconds: u64,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Worker threads of the CPU backend.
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        threads: Option<usize>,
+    },
+    /// Checks that two different files have the same MD5.
+    Verify { file1: PathBuf, file2: PathBuf },
+    /// Forges MD5(x || glue || suffix) from MD5(x) and the length of x, e.g.
+    /// the tag of a secret-prefix MAC, MD5(secret || message).
+    Extend {
+        /// The known digest, in hex.
+        #[arg(long)]
+        digest: String,
+        /// Length of x in bytes, secret included.
+        #[arg(long)]
+        length: u64,
+        /// Text to append.
+        #[arg(
+            long,
+            conflicts_with = "suffix_file",
+            required_unless_present = "suffix_file"
+        )]
+        suffix: Option<String>,
+        /// File whose contents to append.
+        #[arg(long)]
+        suffix_file: Option<PathBuf>,
+        /// Writes glue || suffix, what to append to the message, to this
+        /// file.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Builds a diamond structure on MD5 truncated to `--bits` bits and
+    /// prints the digest it commits to, for messages of `--blocks` blocks
+    /// of any content followed by the diamond's own blocks.
+    Predict {
+        #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u32).range(1..=32))]
+        bits: u32,
+        /// The diamond has 2^depth leaves, at most one per chaining value;
+        /// has to be below `--bits`.
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(0..=MAX_DEPTH as i64))]
+        depth: u32,
+        #[arg(long, default_value_t = 1)]
+        blocks: usize,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Where to save the diamond and the announced length.
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Completes a message to the digest `predict` committed to.
+    Herd {
+        /// The file `predict` saved.
+        prediction: PathBuf,
+        /// The message to start with, at most `--blocks` blocks long.
+        message: PathBuf,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Traces the differential path of one block of two colliding files.
+    Trace {
+        file1: PathBuf,
+        file2: PathBuf,
+        /// Index of the 64 byte block to trace; the first block where the
+        /// files differ if not given.
+        #[arg(long)]
+        block: Option<usize>,
+        /// `first`, `second` or a path file. Defaults to the first-block path
+        /// for the first block that differs and the second-block path after
+        /// it.
+        #[arg(long)]
+        path: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// The two colliding files, prefix included.
+    Raw,
+    /// The colliding blocks of either message, one line each.
+    Hex,
+    /// The fields of the collision and the common MD5.
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Modification {
+    /// Single-message modification of round 1, as kernel.cu does.
+    Kernel,
+    /// Also corrects a5, d5 and c5 by multi-message modification.
+    MultiMessage,
+}
+
+impl Modification {
+    fn strategy(self, tunnels: Option<Vec<Tunnel>>) -> Strategy {
+        let multi_message = matches!(self, Modification::MultiMessage);
+        let tunnels = tunnels.unwrap_or_else(|| match multi_message {
+            true => Strategy::default().tunnels,
+            false => Vec::new(),
+        });
+
+        Strategy {
+            multi_message,
+            tunnels,
+        }
+    }
+}
+
+/// What `find` saves: the search and everything needed to continue it the
+/// same way.
+#[derive(Clone, Serialize, Deserialize)]
+struct Checkpoint {
+    /// The zero-padded prefix.
+    prefix: Vec<u8>,
+    threads: usize,
+    iterations: usize,
+    /// Missing from checkpoints saved before the strategy was configurable.
+    #[serde(default)]
+    strategy: Strategy,
+    search: Search,
+}
+
+/// What `predict` saves for `herd`.
+#[derive(Serialize, Deserialize)]
+struct Prediction {
+    blocks: usize,
+    diamond: Diamond,
+}
+
+#[derive(Serialize)]
+struct Found<'a> {
+    #[serde(flatten)]
+    collision: &'a Collision,
+    md5: String,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    match Cli::parse().command {
+        Command::Find {
+            prefix_file,
+            seed,
+            threads,
+            max_iterations,
+            strategy,
+            tunnels,
+            format,
+            output,
+            checkpoint,
+            checkpoint_interval,
+            resume,
+            first_path,
+            second_path,
+            log,
+            quiet,
+        } => {
+            // a default value does not trigger clap's required_if_eq
+            if matches!(format, Format::Raw) && output.is_empty() {
+                return Err("--format raw needs --output FILE1 FILE2".into());
+            }
+
+            let started = match &resume {
+                Some(path) => serde_json::from_slice(&fs::read(path)?)?,
+                None => {
+                    let defaults = CpuBackend::default();
+                    let seed = seed.unwrap_or_else(rand::random);
+                    let prefix = pad_prefix(&match prefix_file {
+                        Some(path) => fs::read(path)?,
+                        None => Vec::new(),
+                    });
+
+                    let iv = chaining_value(&prefix);
+                    let search = match (first_path, second_path) {
+                        (Some(first), Some(second)) => {
+                            let files = PathFiles {
+                                first: fs::read_to_string(first)?,
+                                second: fs::read_to_string(second)?,
+                            };
+                            Search::with_paths(iv, seed, files)?
+                        }
+                        _ => Search::new(iv, seed),
+                    };
+
+                    Checkpoint {
+                        search,
+                        prefix,
+                        threads: threads.unwrap_or(defaults.threads),
+                        iterations: max_iterations.unwrap_or(defaults.iterations),
+                        strategy: strategy.strategy(tunnels),
+                    }
+                }
+            };
+            eprintln!("seed: {}", started.search.seed);
+
+            let backend = CpuBackend {
+                threads: started.threads,
+                iterations: started.iterations,
+                strategy: started.strategy.clone(),
+                ..CpuBackend::default()
+            };
+            let save_to = checkpoint.or(resume);
+
+            let mut search = started.search.clone();
+            let mut telemetry = Telemetry::new(!quiet, log.as_deref())?;
+            let collision = search.run(
+                &backend,
+                Duration::from_secs(checkpoint_interval),
+                &mut telemetry,
+                |search| match &save_to {
+                    Some(path) => save_json(
+                        &Checkpoint {
+                            search: search.clone(),
+                            ..started.clone()
+                        },
+                        path,
+                    ),
+                    None => Ok(()),
+                },
+            )?;
+
+            let files = colliding_files(&started.prefix, collision);
+            assert_eq!(digest(&files.first), digest(&files.second));
+
+            match format {
+                Format::Raw => {
+                    fs::write(&output[0], &files.first)?;
+                    fs::write(&output[1], &files.second)?;
+                }
+                Format::Hex => {
+                    let (blocks, blocks_prim) = files.collision.to_bytes();
+                    println!("{}", hex(&blocks));
+                    println!("{}", hex(&blocks_prim));
+                }
+                Format::Json => {
+                    let found = Found {
+                        collision: &files.collision,
+                        md5: hex(&digest(&files.first)),
+                    };
+                    println!("{}", serde_json::to_string_pretty(&found)?);
+                }
+            }
+        }
+        Command::ChosenPrefix {
+            file1,
+            file2,
+            paths,
+            checkpoint,
+            seed,
+            threads,
+            output,
+        } => {
+            let (first, second) = (fs::read(file1)?, fs::read(file2)?);
+            let mut search = if checkpoint.exists() {
+                ChosenPrefix::resume(&checkpoint, &first, &second)?
+            } else {
+                ChosenPrefix::new(
+                    &first,
+                    &second,
+                    seed.unwrap_or_else(rand::random),
+                    threads.unwrap_or(CpuBackend::default().threads),
+                )
+            };
+
+            let backend = CpuBackend {
+                threads: search.threads,
+                ..CpuBackend::default()
+            };
+
+            match search.run(&backend, &PathDirectory(paths.clone()), &checkpoint)? {
+                Progress::NeedsPath(block) => {
+                    let Stage::NearCollisions { ihv, ihv_prim, .. } = &search.stage else {
+                        unreachable!("only near-collision blocks need paths")
+                    };
+                    let words = |ihv: &[u32; 4]| hex(&ihv.map(u32::to_le_bytes).concat());
+
+                    return Err(format!(
+                        "block {} needs a path from {} and {}; save it as {} and rerun",
+                        block,
+                        words(ihv),
+                        words(ihv_prim),
+                        paths.join(format!("block{}.txt", block)).display()
+                    )
+                    .into());
+                }
+                Progress::Searching => unreachable!("run returns when the search stops"),
+                Progress::Done => {}
+            }
+
+            let (suffix, suffix_prim) = search.suffixes().expect("the search is done");
+            let (first, second) = (
+                [first, suffix.to_vec()].concat(),
+                [second, suffix_prim.to_vec()].concat(),
+            );
+            assert_eq!(digest(&first), digest(&second));
+
+            println!("{}", hex(&digest(&first)));
+            fs::write(&output[0], first)?;
+            fs::write(&output[1], second)?;
+        }
+        Command::Bench {
+            file,
+            seconds,
+            seed,
+            threads,
+        } => {
+            let data = fs::read(file)?;
+            let block = data.get(..64).ok_or("the file is shorter than a block")?;
+            let state = chaining_value(block);
+            if !second_block::table().starts_from(&state) {
+                return Err("the first block does not follow Wang's first-block path".into());
+            }
+
+            let strategies = [
+                ("kernel", Modification::Kernel.strategy(None)),
+                (
+                    "multi-message",
+                    Modification::MultiMessage.strategy(Some(Vec::new())),
+                ),
+                (
+                    "multi-message, q9",
+                    Modification::MultiMessage.strategy(Some(vec![Tunnel::Q9])),
+                ),
+                (
+                    "multi-message, q4,q10,q9",
+                    Modification::MultiMessage.strategy(None),
+                ),
+            ];
+
+            println!("strategy                  trials/s   candidates  expected s/candidate");
+            for (name, strategy) in strategies {
+                let backend = CpuBackend {
+                    threads: threads.unwrap_or(CpuBackend::default().threads),
+                    iterations: 1 << 12,
+                    strategy,
+                    ..CpuBackend::default()
+                };
+
+                let started = Instant::now();
+                let mut candidates = 0;
+                let mut batch = 0;
+                while started.elapsed() < Duration::from_secs(seconds) {
+                    candidates += backend.generate_candidates(&state, seed + batch).len();
+                    batch += 1;
+                }
+
+                let elapsed = started.elapsed().as_secs_f64();
+                let survival = backend.take_survival();
+                let rate = survival.trials() as f64 / elapsed;
+                let expected = survival
+                    .candidate_probability(&second_block::table())
+                    .map_or("-".to_string(), |p| format!("{:.3e}", 1.0 / (rate * p)));
+                println!(
+                    "{:<24} {:>10.0} {:>12} {:>21}",
+                    name, rate, candidates, expected
+                );
+            }
+        }
+        Command::Verify { file1, file2 } => {
+            let (first, second) = (fs::read(file1)?, fs::read(file2)?);
+            let (md5, md5_prim) = (digest(&first), digest(&second));
+
+            println!("{}", hex(&md5));
+            println!("{}", hex(&md5_prim));
+
+            if first == second {
+                println!("the files are identical");
+                process::exit(1);
+            }
+            if md5 != md5_prim {
+                println!("no collision");
+                process::exit(1);
+            }
+            println!("collision");
+        }
+        Command::Extend {
+            digest,
+            length,
+            suffix,
+            suffix_file,
+            output,
+        } => {
+            let known = unhex(&digest)?
+                .try_into()
+                .map_err(|_| "--digest is not 16 bytes")?;
+            let suffix = match suffix_file {
+                Some(path) => fs::read(path)?,
+                None => suffix.unwrap_or_default().into_bytes(),
+            };
+
+            let (forged, glue) = length_extend(&known, length, &suffix);
+            let extension = [glue, suffix].concat();
+
+            println!("{}", hex(&forged));
+            println!("{}", hex(&extension));
+
+            if let Some(path) = output {
+                fs::write(path, &extension)?;
+            }
+        }
+        Command::Predict {
+            bits,
+            depth,
+            blocks,
+            seed,
+            output,
+        } => {
+            if depth >= bits {
+                return Err("--depth has to be below --bits".into());
+            }
+            let diamond = Diamond::build(Truncated { bits }, depth, seed)?;
+            println!("{:01$x}", diamond.commit(blocks), bits.div_ceil(4) as usize);
+
+            save_json(&Prediction { blocks, diamond }, &output)?;
+        }
+        Command::Herd {
+            prediction,
+            message,
+            seed,
+            output,
+        } => {
+            let Prediction { blocks, diamond } = serde_json::from_slice(&fs::read(prediction)?)?;
+            let message = fs::read(message)?;
+            if message.len() > blocks * 64 {
+                return Err(format!("the message is longer than {} blocks", blocks).into());
+            }
+
+            let herded = diamond.herd(&message, blocks, seed);
+            let bits = diamond.hash.bits;
+            println!(
+                "{:01$x}",
+                diamond.hash.digest(&herded),
+                bits.div_ceil(4) as usize
+            );
+
+            fs::write(output, herded)?;
+        }
+        Command::Trace {
+            file1,
+            file2,
+            block,
+            path,
+            json,
+        } => {
+            let (first, second) = (fs::read(file1)?, fs::read(file2)?);
+            let differs = |idx: usize| first.chunks(64).nth(idx) != second.chunks(64).nth(idx);
+
+            let first_difference = (0..first.len().max(second.len()).div_ceil(64))
+                .find(|&idx| differs(idx))
+                .ok_or("the files are identical")?;
+            let block = block.unwrap_or(first_difference);
+
+            let end = (block + 1) * 64;
+            if first.len() < end || second.len() < end {
+                return Err(format!("block {} is not complete in both files", block).into());
+            }
+
+            let table = match path.as_deref() {
+                Some("first") => first_block::table(),
+                Some("second") => second_block::table(),
+                Some(file) => ConditionTable::parse(&fs::read_to_string(file)?)?,
+                None if block == first_difference => first_block::table(),
+                None => second_block::table(),
+            };
+
+            let words = |data: &[u8]| -> [u32; 16] {
+                let mut words = [0; 16];
+                for (word, bytes) in words.iter_mut().zip(data.chunks_exact(4)) {
+                    *word = u32::from_le_bytes(bytes.try_into().unwrap());
+                }
+                words
+            };
+
+            let start = block * 64;
+            let result = trace(
+                &chaining_value(&first[..start]),
+                &words(&first[start..end]),
+                &chaining_value(&second[..start]),
+                &words(&second[start..end]),
+                &table,
+            );
+
+            if json {
+                println!("{}", result.to_json());
+            } else {
+                println!("{}", result);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Result<Vec<u8>, String> {
+    if !text.is_ascii() {
+        return Err(format!("{:?} is not hex", text));
+    }
+    if !text.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits in {:?}", text));
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| {
+            u8::from_str_radix(&text[idx..idx + 2], 16)
+                .map_err(|_| format!("{:?} is not hex", text))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unhex_rejects_what_is_not_hex() {
+        assert_eq!(unhex("00ff7A").unwrap(), vec![0x00, 0xff, 0x7a]);
+        assert!(unhex("abc").is_err());
+        assert!(unhex("zz").is_err());
+        assert!(unhex("éa").is_err());
+    }
+
+    #[test]
+    fn strategies_default_their_tunnels() {
+        assert_eq!(Modification::Kernel.strategy(None), Strategy::kernel());
+        assert_eq!(
+            Modification::MultiMessage.strategy(None),
+            Strategy::default()
+        );
+
+        let cli = Cli::parse_from(["lista_1", "find", "--tunnels", "--format", "hex"]);
+        let Command::Find { tunnels, .. } = cli.command else {
+            unreachable!()
+        };
+        assert_eq!(tunnels, Some(Vec::new()));
+
+        let cli = Cli::parse_from(["lista_1", "find", "--tunnels", "q9,q4", "--format", "hex"]);
+        let Command::Find { tunnels, .. } = cli.command else {
+            unreachable!()
+        };
+        assert_eq!(tunnels, Some(vec![Tunnel::Q9, Tunnel::Q4]));
+    }
 }
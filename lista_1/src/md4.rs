@@ -0,0 +1,251 @@
+// MD4 (RFC 1320) and Wang et al.'s single-block collision attack on it, from
+// "Cryptanalysis of the Hash Functions MD4 and RIPEMD".
+//
+// The attack works like the second-block search of backend/strategy.rs, on a
+// smaller scale: random words are bent until a1..b4 meet the conditions of
+// paths/md4.txt (single-message modification), a5, d5 and c5 are corrected
+// through the round 1 words that feed them (multi-message modification) and
+// the remaining conditions, eleven bits in rounds 2 and 3, are left to
+// chance. A collision takes some 10^4 to 10^5 trials, a fraction of a second.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::conditions::md4;
+use crate::family::{self, Block, Conditions, MdFamily};
+use crate::md5::{bytes_to_words, padding, INITIAL_STATE};
+
+/// Modular message difference of the path, `m_prim - m`.
+pub const M_DELTA: [u32; 16] = [
+    0, 0x80000000, 0x70000000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xffff0000, 0, 0, 0,
+];
+
+/// Rotation amounts of the 48 steps.
+const SHIFTS: [u32; 48] = [
+    3, 7, 11, 19, 3, 7, 11, 19, 3, 7, 11, 19, 3, 7, 11, 19, 3, 5, 9, 13, 3, 5, 9, 13, 3, 5, 9, 13,
+    3, 5, 9, 13, 3, 9, 11, 15, 3, 9, 11, 15, 3, 9, 11, 15, 3, 9, 11, 15,
+];
+
+/// Round 2 values corrected by multi-message modification.
+const MULTI_MESSAGE_STEPS: [usize; 3] = [17, 18, 19];
+
+/// MD4 step by step, for `family::Block` and the tracer.
+pub struct Md4;
+
+impl MdFamily for Md4 {
+    const STEPS: usize = 48;
+    const REGISTERS: usize = 4;
+
+    fn boolean_function(t: usize) -> fn(u32, u32, u32) -> u32 {
+        match t / 16 {
+            0 => |x, y, z| (x & y) | (!x & z),
+            1 => |x, y, z| (x & y) | (x & z) | (y & z),
+            _ => |x, y, z| x ^ y ^ z,
+        }
+    }
+
+    fn constant(t: usize) -> u32 {
+        [0, 0x5A827999, 0x6ED9EBA1][t / 16]
+    }
+
+    fn shift(t: usize) -> u32 {
+        SHIFTS[t]
+    }
+
+    fn word_index(t: usize) -> Option<usize> {
+        const ROUND_2: [usize; 16] = [0, 4, 8, 12, 1, 5, 9, 13, 2, 6, 10, 14, 3, 7, 11, 15];
+        const ROUND_3: [usize; 16] = [0, 8, 4, 12, 2, 10, 6, 14, 1, 9, 5, 13, 3, 11, 7, 15];
+
+        Some(match t / 16 {
+            0 => t,
+            1 => ROUND_2[t % 16],
+            _ => ROUND_3[t % 16],
+        })
+    }
+
+    fn phi(t: usize, q: &[u32]) -> u32 {
+        Md4::boolean_function(t)(q[3], q[2], q[1])
+    }
+
+    fn step(t: usize, q: &[u32], word: u32) -> u32 {
+        q[0].wrapping_add(Md4::phi(t, q))
+            .wrapping_add(word)
+            .wrapping_add(Md4::constant(t))
+            .rotate_left(SHIFTS[t])
+    }
+
+    fn inverse_step(t: usize, q: &[u32], next: u32) -> u32 {
+        next.rotate_right(SHIFTS[t])
+            .wrapping_sub(q[0])
+            .wrapping_sub(Md4::phi(t, q))
+            .wrapping_sub(Md4::constant(t))
+    }
+
+    fn initial_values(state: &[u32]) -> Vec<u32> {
+        vec![state[0], state[3], state[2], state[1]]
+    }
+
+    fn output(state: &[u32], last: &[u32]) -> Vec<u32> {
+        vec![
+            state[0].wrapping_add(last[0]),
+            state[1].wrapping_add(last[3]),
+            state[2].wrapping_add(last[2]),
+            state[3].wrapping_add(last[1]),
+        ]
+    }
+}
+
+/// The MD4 compression function on one block of 16 little-endian words.
+pub fn compress(state: &[u32; 4], words: &[u32; 16]) -> [u32; 4] {
+    family::compress::<Md4>(state, words).try_into().unwrap()
+}
+
+/// MD4 digest of `data`. MD4 pads like MD5.
+pub fn digest(data: &[u8]) -> [u8; 16] {
+    let mut message = data.to_vec();
+    message.extend(padding(data.len() as u64));
+    message.extend((data.len() as u64).wrapping_mul(8).to_le_bytes());
+
+    let state = message
+        .chunks_exact(64)
+        .fold(INITIAL_STATE, |state, block| {
+            compress(&state, &bytes_to_words(block))
+        });
+
+    let mut digest = [0; 16];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+
+    digest
+}
+
+/// Two one-block messages with the same MD4 compression from the IV.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Collision {
+    pub m: [u32; 16],
+    pub m_prim: [u32; 16],
+    pub hash: [u32; 4],
+}
+
+/// `m + M_DELTA`, the block `m` collides with if it follows the path.
+pub fn apply_delta(m: &[u32; 16]) -> [u32; 16] {
+    let mut m_prim = *m;
+    for (word, delta) in m_prim.iter_mut().zip(M_DELTA) {
+        *word = word.wrapping_add(delta);
+    }
+
+    m_prim
+}
+
+/// Searches for a collision, deterministically for a given `seed`.
+pub fn find_collision(seed: u64) -> Collision {
+    let conditions = Conditions::new::<Md4>(&md4::table());
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    loop {
+        if let Some(collision) = try_block(rng.gen(), &conditions) {
+            return collision;
+        }
+    }
+}
+
+/// Bends the random block `words` towards the path and returns the
+/// collision if it follows it to the end.
+fn try_block(words: [u32; 16], conditions: &Conditions) -> Option<Collision> {
+    let mut block = Block::<Md4>::new(&INITIAL_STATE, &words);
+    block.round_1(conditions);
+
+    if !MULTI_MESSAGE_STEPS
+        .iter()
+        .all(|&t| block.correct(t, conditions))
+    {
+        return None;
+    }
+    if !block.check(20..=Md4::STEPS, conditions) {
+        return None;
+    }
+
+    let m_prim = apply_delta(&block.m);
+    let hash = compress(&INITIAL_STATE, &block.m);
+
+    (compress(&INITIAL_STATE, &m_prim) == hash).then_some(Collision {
+        m: block.m,
+        m_prim,
+        hash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conditions::table::ConditionTable;
+
+    /// The collision published by Wang et al.
+    const M: [u32; 16] = [
+        0x4d7a9c83, 0x56cb927a, 0xb9d5a578, 0x57a7a5ee, 0xde748a3c, 0xdcc366b3, 0xb683a020,
+        0x3b2a5d9f, 0xc69d71b3, 0xf9e99198, 0xd79f805e, 0xa63bb2e8, 0x45dd8e31, 0x97e31fe5,
+        0x2794bf08, 0xb9e8c3e9,
+    ];
+    const M_PRIM: [u32; 16] = [
+        0x4d7a9c83, 0xd6cb927a, 0x29d5a578, 0x57a7a5ee, 0xde748a3c, 0xdcc366b3, 0xb683a020,
+        0x3b2a5d9f, 0xc69d71b3, 0xf9e99198, 0xd79f805e, 0xa63bb2e8, 0x45dc8e31, 0x97e31fe5,
+        0x2794bf08, 0xb9e8c3e9,
+    ];
+
+    fn follows(table: &ConditionTable, words: &[u32; 16]) -> bool {
+        let conditions = Conditions::new::<Md4>(table);
+        Block::<Md4>::new(&INITIAL_STATE, words).check(1..=Md4::STEPS, &conditions)
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn rfc_1320_test_suite() {
+        let suite = [
+            ("", "31d6cfe0d16ae931b73c59d7e0c089c0"),
+            ("a", "bde52cb31de33e46245e05fbdbd6fb24"),
+            ("abc", "a448017aaf21d8525fc10ae87aa6729d"),
+            ("message digest", "d9130a8164549fe818874806e1c7014b"),
+            (
+                "abcdefghijklmnopqrstuvwxyz",
+                "d79e1c308aa5bbcdeea8ed63df412da9",
+            ),
+            (
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "043f8582f241db351ce627e153e7f0e4",
+            ),
+            (
+                "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "e33b4ddc9c38f2199c3e7b164fcc0536",
+            ),
+        ];
+
+        for (message, expected) in suite {
+            assert_eq!(hex(&digest(message.as_bytes())), expected, "{:?}", message);
+        }
+    }
+
+    #[test]
+    fn published_collision_follows_the_path() {
+        assert_eq!(apply_delta(&M), M_PRIM);
+        assert_eq!(
+            compress(&INITIAL_STATE, &M),
+            compress(&INITIAL_STATE, &M_PRIM)
+        );
+        assert!(follows(&md4::table(), &M));
+    }
+
+    #[test]
+    fn finds_a_collision() {
+        let collision = find_collision(0);
+
+        assert_ne!(collision.m, collision.m_prim);
+        assert_eq!(compress(&INITIAL_STATE, &collision.m_prim), collision.hash);
+        assert!(follows(&md4::table(), &collision.m));
+        assert_eq!(find_collision(0), collision);
+    }
+}
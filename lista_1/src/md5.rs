@@ -6,60 +6,82 @@
 // compile.
 
 use std::fmt::Display;
-use std::io::BufReader;
-use std::io::Read;
-use std::num::Wrapping;
-use std::time::Instant;
-use std::usize;
 use std::vec::Vec;
 
 use rand::random;
+use serde::{Deserialize, Serialize};
 
-const BLOCK_SIZE: usize = 512;
+use crate::backend::{CandidateBackend, DefaultBackend};
+use crate::differential::Paths;
+use crate::family::MdFamily;
+use crate::search::Search;
+
+const BLOCK_SIZE: usize = 64;
 pub const INITIAL_STATE: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
-const M0_DELTA: [u32; 16] = [
-    0,
-    0,
-    0,
-    0,
-    2_u32.pow(31),
-    0,
-    0,
-    0,
-    0,
-    0,
-    0,
-    2_u32.pow(15),
-    0,
-    0,
-    2_u32.pow(31),
-    0,
+/// Additive constants of the 64 steps.
+pub(crate) const K: [u32; 64] = [
+    0xD76AA478, 0xE8C7B756, 0x242070DB, 0xC1BDCEEE, 0xF57C0FAF, 0x4787C62A, 0xA8304613, 0xFD469501,
+    0x698098D8, 0x8B44F7AF, 0xFFFF5BB1, 0x895CD7BE, 0x6B901122, 0xFD987193, 0xA679438E, 0x49B40821,
+    0xF61E2562, 0xC040B340, 0x265E5A51, 0xE9B6C7AA, 0xD62F105D, 0x02441453, 0xD8A1E681, 0xE7D3FBC8,
+    0x21E1CDE6, 0xC33707D6, 0xF4D50D87, 0x455A14ED, 0xA9E3E905, 0xFCEFA3F8, 0x676F02D9, 0x8D2A4C8A,
+    0xFFFA3942, 0x8771F681, 0x6D9D6122, 0xFDE5380C, 0xA4BEEA44, 0x4BDECFA9, 0xF6BB4B60, 0xBEBFBC70,
+    0x289B7EC6, 0xEAA127FA, 0xD4EF3085, 0x04881D05, 0xD9D4D039, 0xE6DB99E5, 0x1FA27CF8, 0xC4AC5665,
+    0xF4292244, 0x432AFF97, 0xAB9423A7, 0xFC93A039, 0x655B59C3, 0x8F0CCC92, 0xFFEFF47D, 0x85845DD1,
+    0x6FA87E4F, 0xFE2CE6E0, 0xA3014314, 0x4E0811A1, 0xF7537E82, 0xBD3AF235, 0x2AD7D2BB, 0xEB86D391,
 ];
 
-struct InternalState {
-    a: u32,
-    b: u32,
-    c: u32,
-    d: u32,
+/// Rotation amounts of the 64 steps.
+pub(crate) const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+/// Index of the message word used in step `t` (0-based).
+pub(crate) fn word_index(t: usize) -> usize {
+    match t / 16 {
+        0 => t,
+        1 => (5 * t + 1) % 16,
+        2 => (3 * t + 5) % 16,
+        _ => (7 * t) % 16,
+    }
+}
+
+/// Boolean function of step `t` (0-based).
+pub(crate) fn step_function(t: usize) -> fn(u32, u32, u32) -> u32 {
+    [tr_f, tr_g, tr_h, tr_i][t / 16]
 }
 
-fn tr_f(x: u32, y: u32, z: u32) -> u32 {
+/// The boolean function of step `t` applied to `x`, `y` and `z`. Picks the
+/// function with a branch instead of calling it through a pointer, so that
+/// it can be inlined into the steps of `Md5` as `MdFamily`.
+fn boolean(t: usize, x: u32, y: u32, z: u32) -> u32 {
+    match t / 16 {
+        0 => tr_f(x, y, z),
+        1 => tr_g(x, y, z),
+        2 => tr_h(x, y, z),
+        _ => tr_i(x, y, z),
+    }
+}
+
+pub(crate) fn tr_f(x: u32, y: u32, z: u32) -> u32 {
     (x & y) | (!x & z)
 }
 
-fn tr_g(x: u32, y: u32, z: u32) -> u32 {
+pub(crate) fn tr_g(x: u32, y: u32, z: u32) -> u32 {
     (x & z) | (y & !z)
 }
 
-fn tr_h(x: u32, y: u32, z: u32) -> u32 {
+pub(crate) fn tr_h(x: u32, y: u32, z: u32) -> u32 {
     x ^ y ^ z
 }
 
-fn tr_i(x: u32, y: u32, z: u32) -> u32 {
+pub(crate) fn tr_i(x: u32, y: u32, z: u32) -> u32 {
     y ^ (x | !z)
 }
 
-fn transform(
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn transform(
     func: impl Fn(u32, u32, u32) -> u32,
     a: u32,
     b: u32,
@@ -74,24 +96,34 @@ fn transform(
     let mut temp = f.wrapping_add(word).wrapping_add(k);
 
     temp = temp.rotate_left(s);
-    return temp.wrapping_add(b);
+    temp.wrapping_add(b)
 }
 
-fn process_block(buf: &mut [u8], state: InternalState) -> InternalState {
-    let mut words: [u32; 16] = [0; 16];
-
-    // break chunk into 16 words (each word being 32 bits)
-    for i in 0..16 {
-        let start: usize = i * 4;
-        let stop: usize = i * 4 + 4;
-        let word: u32 = u32::from_le_bytes(buf[start..stop].try_into().unwrap());
-        words[i] = word;
-    }
+/// Inverse of `transform`: given the new value `a` and the value `orig` it
+/// replaced, recovers the message word used in the step.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn reverse_transform(
+    func: impl Fn(u32, u32, u32) -> u32,
+    a: u32,
+    b: u32,
+    c: u32,
+    d: u32,
+    k: u32,
+    s: u32,
+    orig: u32,
+) -> u32 {
+    a.wrapping_sub(b)
+        .rotate_right(s)
+        .wrapping_sub(func(b, c, d))
+        .wrapping_sub(orig)
+        .wrapping_sub(k)
+}
 
-    let mut a = state.a;
-    let mut b = state.b;
-    let mut c = state.c;
-    let mut d = state.d;
+/// The MD5 compression function: processes one block of 16 little-endian
+/// words starting from `state`. This is what the collision search works
+/// with; it does not add any padding.
+pub fn compress(state: &[u32; 4], words: &[u32; 16]) -> [u32; 4] {
+    let [mut a, mut b, mut c, mut d] = *state;
 
     //  round 1
     a = transform(tr_f, a, b, c, d, words[0], 0xD76AA478, 7);
@@ -177,33 +209,229 @@ fn process_block(buf: &mut [u8], state: InternalState) -> InternalState {
     c = transform(tr_i, c, d, a, b, words[2], 0x2AD7D2BB, 15);
     b = transform(tr_i, b, c, d, a, words[9], 0xEB86D391, 21);
 
-    return InternalState {
-        a: state.a.wrapping_add(a),
-        b: state.b.wrapping_add(b),
-        c: state.c.wrapping_add(c),
-        d: state.d.wrapping_add(d),
-    };
-}
-
-impl From<[u32; 4]> for InternalState {
-    fn from(state: [u32; 4]) -> Self {
-        InternalState {
-            a: state[0],
-            b: state[1],
-            c: state[2],
-            d: state[3],
+    [
+        state[0].wrapping_add(a),
+        state[1].wrapping_add(b),
+        state[2].wrapping_add(c),
+        state[3].wrapping_add(d),
+    ]
+}
+
+/// Streaming MD5 hasher (RFC 1321).
+#[derive(Debug, Clone)]
+pub struct Md5 {
+    state: [u32; 4],
+    /// Bytes of the current, incomplete block.
+    buffer: Vec<u8>,
+    /// Number of bytes hashed so far, modulo 2^64.
+    len: u64,
+}
+
+impl Md5 {
+    pub fn new() -> Self {
+        Md5::from_state(INITIAL_STATE, 0)
+    }
+
+    /// Resumes hashing from an intermediate `state`, reached after hashing
+    /// `len` bytes. `len` has to be a multiple of the block size.
+    pub fn from_state(state: [u32; 4], len: u64) -> Self {
+        assert!(
+            len.is_multiple_of(BLOCK_SIZE as u64),
+            "len is not block-aligned"
+        );
+
+        Md5 {
+            state,
+            buffer: Vec::with_capacity(BLOCK_SIZE),
+            len,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len = self.len.wrapping_add(data.len() as u64);
+
+        // complete the buffered block first
+        if !self.buffer.is_empty() {
+            let missing = (BLOCK_SIZE - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..missing]);
+            data = &data[missing..];
+
+            if self.buffer.len() < BLOCK_SIZE {
+                return;
+            }
+
+            self.state = compress(&self.state, &bytes_to_words(&self.buffer));
+            self.buffer.clear();
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            self.state = compress(&self.state, &bytes_to_words(block));
+        }
+
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+
+    pub fn finalize(mut self) -> [u8; 16] {
+        // the length is taken before padding, in *bits*, modulo 2^64
+        let bit_len = self.len.wrapping_mul(8);
+
+        self.update(&padding(self.len));
+        debug_assert!(self.buffer.len() == BLOCK_SIZE - 8);
+        self.update(&bit_len.to_le_bytes());
+
+        let mut digest = [0; 16];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_le_bytes());
         }
+
+        digest
     }
 }
 
-impl Into<[u32; 4]> for InternalState {
-    fn into(self) -> [u32; 4] {
-        [self.a, self.b, self.c, self.d]
+impl Default for Md5 {
+    fn default() -> Self {
+        Md5::new()
     }
 }
 
-#[derive(Debug, Clone)]
+/// MD5 step by step, for `family::Block` and the tracer.
+impl MdFamily for Md5 {
+    const STEPS: usize = 64;
+    const REGISTERS: usize = 4;
+
+    fn boolean_function(t: usize) -> fn(u32, u32, u32) -> u32 {
+        step_function(t)
+    }
+
+    fn constant(t: usize) -> u32 {
+        K[t]
+    }
+
+    fn shift(t: usize) -> u32 {
+        SHIFTS[t]
+    }
+
+    fn word_index(t: usize) -> Option<usize> {
+        Some(word_index(t))
+    }
+
+    fn phi(t: usize, q: &[u32]) -> u32 {
+        boolean(t, q[3], q[2], q[1])
+    }
+
+    fn step(t: usize, q: &[u32], word: u32) -> u32 {
+        transform(
+            |x, y, z| boolean(t, x, y, z),
+            q[0],
+            q[3],
+            q[2],
+            q[1],
+            word,
+            K[t],
+            SHIFTS[t],
+        )
+    }
+
+    fn inverse_step(t: usize, q: &[u32], next: u32) -> u32 {
+        reverse_transform(
+            |x, y, z| boolean(t, x, y, z),
+            next,
+            q[3],
+            q[2],
+            q[1],
+            K[t],
+            SHIFTS[t],
+            q[0],
+        )
+    }
+
+    fn initial_values(state: &[u32]) -> Vec<u32> {
+        vec![state[0], state[3], state[2], state[1]]
+    }
+
+    fn output(state: &[u32], last: &[u32]) -> Vec<u32> {
+        vec![
+            state[0].wrapping_add(last[0]),
+            state[1].wrapping_add(last[3]),
+            state[2].wrapping_add(last[2]),
+            state[3].wrapping_add(last[1]),
+        ]
+    }
+}
+
+/// The 0x80 byte and the zeros RFC 1321 appends to a message of `len` bytes,
+/// before the length itself: enough to leave the message 8 bytes short of a
+/// multiple of the block size.
+pub fn padding(len: u64) -> Vec<u8> {
+    let used = (len % BLOCK_SIZE as u64) as usize;
+    let zeros = (BLOCK_SIZE + BLOCK_SIZE - 8 - used - 1) % BLOCK_SIZE;
+
+    let mut padding = vec![0; zeros + 1];
+    padding[0] = 0x80;
+    padding
+}
+
+/// MD5 digest of `data`.
+pub fn digest(data: &[u8]) -> [u8; 16] {
+    let mut md5 = Md5::new();
+    md5.update(data);
+    md5.finalize()
+}
+
+/// Output of `first_step`: a first block pair and the chaining values it
+/// leads to, which differ as the first block's path says.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NearCollision {
+    pub iv: [u32; 4],
+    pub m0: [u32; 16],
+    pub m0_prim: [u32; 16],
+    pub state_m0: [u32; 4],
+    pub state_m0_prim: [u32; 4],
+}
+
+impl NearCollision {
+    /// Builds the near-collision for a known first block pair, checking that
+    /// the pair really follows Wang's path from `iv`.
+    pub fn new(iv: [u32; 4], m0: [u32; 16], m0_prim: [u32; 16]) -> Option<NearCollision> {
+        NearCollision::with_paths(iv, m0, m0_prim, Paths::wang())
+    }
+
+    /// Like `new`, for the first block of `paths`.
+    pub fn with_paths(
+        iv: [u32; 4],
+        m0: [u32; 16],
+        m0_prim: [u32; 16],
+        paths: &Paths,
+    ) -> Option<NearCollision> {
+        let near_collision = NearCollision {
+            iv,
+            m0,
+            m0_prim,
+            state_m0: compress(&iv, &m0),
+            state_m0_prim: compress(&iv, &m0_prim),
+        };
+
+        if paths.first_delta.matches(&m0, &m0_prim) && near_collision.is_valid(paths) {
+            Some(near_collision)
+        } else {
+            None
+        }
+    }
+
+    /// Checks the chaining value difference and the conditions the second
+    /// block needs on `state_m0`.
+    fn is_valid(&self, paths: &Paths) -> bool {
+        let difference: [u32; 4] =
+            std::array::from_fn(|i| self.state_m0_prim[i].wrapping_sub(self.state_m0[i]));
+
+        difference == paths.first_delta.ihv && paths.second.starts_from(&self.state_m0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Collision {
+    pub iv: [u32; 4],
     pub m0: [u32; 16],
     pub m1: [u32; 16],
     pub m0_prim: [u32; 16],
@@ -211,14 +439,39 @@ pub struct Collision {
     pub hash: [u32; 4],
 }
 
+impl Collision {
+    /// The two colliding messages `m0 || m1` and `m0_prim || m1_prim` as
+    /// little-endian bytes.
+    pub fn to_bytes(&self) -> (Vec<u8>, Vec<u8>) {
+        (
+            words_to_bytes(&[self.m0, self.m1].concat()),
+            words_to_bytes(&[self.m0_prim, self.m1_prim].concat()),
+        )
+    }
+}
+
+/// Two files that share a prefix and have the same MD5.
+#[derive(Debug, Clone)]
+pub struct CollidingFiles {
+    pub first: Vec<u8>,
+    pub second: Vec<u8>,
+    pub collision: Collision,
+}
+
 impl Display for Collision {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "iv: [{:#x}, {:#x}, {:#x}, {:#x}],",
+            self.iv[0], self.iv[1], self.iv[2], self.iv[3]
+        )?;
+
         write!(f, "m0: [")?;
         for i in 0..16 {
             if i != 15 {
                 write!(f, "{:#x}, ", self.m0[i])?;
             } else {
-                write!(f, "{:#x}],\n", self.m0[i])?;
+                writeln!(f, "{:#x}],", self.m0[i])?;
             }
         }
 
@@ -227,7 +480,7 @@ impl Display for Collision {
             if i != 15 {
                 write!(f, "{:#x}, ", self.m1[i])?;
             } else {
-                write!(f, "{:#x}],\n", self.m1[i])?;
+                writeln!(f, "{:#x}],", self.m1[i])?;
             }
         }
 
@@ -236,7 +489,7 @@ impl Display for Collision {
             if i != 15 {
                 write!(f, "{:#x}, ", self.m0_prim[i])?;
             } else {
-                write!(f, "{:#x}],\n", self.m0_prim[i])?;
+                writeln!(f, "{:#x}],", self.m0_prim[i])?;
             }
         }
 
@@ -245,7 +498,7 @@ impl Display for Collision {
             if i != 15 {
                 write!(f, "{:#x}, ", self.m1_prim[i])?;
             } else {
-                write!(f, "{:#x}],\n", self.m1_prim[i])?;
+                writeln!(f, "{:#x}],", self.m1_prim[i])?;
             }
         }
 
@@ -257,270 +510,288 @@ impl Display for Collision {
     }
 }
 
-#[link(name = "md5", kind = "static")]
-extern "C" {
-    fn generate_candidates_cuda(
-        state: *const u32,
-        candidates: *mut u32,
-        found: *mut bool,
-        iterations: usize,
-        threads_per_block: usize,
-        block_dim: usize,
-        seed: usize,
-    );
+pub(crate) fn words_to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|x| x.to_le_bytes()).collect()
 }
 
-pub fn second_step(m0: [u32; 16], m0_prim: [u32; 16]) -> Collision {
-    let mut delta_m0: [u32; 16] = [0; 16];
+pub(crate) fn bytes_to_words(block: &[u8]) -> [u32; 16] {
+    let mut words: [u32; 16] = [0; 16];
 
-    for i in 0..16 {
-        delta_m0[i] = m0_prim[i] ^ m0[i];
+    for (i, word) in words.iter_mut().enumerate() {
+        *word = u32::from_le_bytes(block[i * 4..i * 4 + 4].try_into().unwrap());
     }
 
-    assert!(delta_m0 == M0_DELTA);
-
-    let state_m0 = hash(&INITIAL_STATE, &m0);
-    let state_m0_prim = hash(&INITIAL_STATE, &m0_prim);
-
-    let start = Instant::now();
-
-    const ITERATIONS: usize = 5000000;
-    const THREADS_PER_BLOCK: usize = 256;
-    const BLOCK_DIM: usize = 352;
-    const BATCH_SIZE: usize = THREADS_PER_BLOCK * BLOCK_DIM;
-
-    let mut candidates: [u32; 16 * BATCH_SIZE] = [0; 16 * BATCH_SIZE];
-
-    let mut candidate_counter = 0;
-    let mut loop_counter = 0;
-
-    loop {
-        let mut found: [bool; BATCH_SIZE] = [false; BATCH_SIZE];
-
-        unsafe {
-            generate_candidates_cuda(
-                &state_m0[0],
-                &mut candidates[0],
-                &mut found[0],
-                ITERATIONS,
-                THREADS_PER_BLOCK,
-                BLOCK_DIM,
-                random(),
-            );
-        }
-
-        for i in 0..BATCH_SIZE {
-            if found[i] {
-                candidate_counter += 1;
-
-                let candidate: [u32; 16] = candidates[i * 16..(i + 1) * 16]
-                    .iter()
-                    .map(|x| *x)
-                    .collect::<Vec<u32>>()
-                    .try_into()
-                    .unwrap();
-
-                let candidate_prim: [u32; 16] = candidate
-                    .iter()
-                    .zip(delta_m0.iter())
-                    .map(|(x, y)| x ^ y)
-                    .collect::<Vec<u32>>()
-                    .try_into()
-                    .unwrap();
-
-                if hash(&state_m0, &candidate) == hash(&state_m0_prim, &candidate_prim) {
-                    return Collision {
-                        m0,
-                        m1: candidate,
-                        m0_prim,
-                        m1_prim: candidate_prim,
-                        hash: state_m0,
-                    };
-                }
-            }
-        }
-
-        println!(
-            "[{:#?}] Loop: {:#?}, Candidates: {:#?}",
-            start.elapsed(),
-            loop_counter,
-            candidate_counter
-        );
-
-        loop_counter += 1;
-    }
+    words
 }
 
-pub fn hash(state: &[u32; 4], input: &[u32]) -> [u32; 4] {
-    let mut state = InternalState::from(*state);
+/// Zero-pads `prefix` to a whole number of 64 byte blocks, like fastcoll
+/// does, so that the collision blocks can directly follow it.
+pub fn pad_prefix(prefix: &[u8]) -> Vec<u8> {
+    let mut padded = prefix.to_vec();
+    padded.resize(prefix.len().div_ceil(64) * 64, 0);
+    padded
+}
 
-    let binding = input
-        .iter()
-        .flat_map(|x| x.to_le_bytes().to_vec())
-        .collect::<Vec<u8>>();
-    binding.as_slice();
+/// Runs the block-aligned `data` through the compression function, starting
+/// from the MD5 IV, and returns the intermediate state. No padding is added.
+pub fn chaining_value(data: &[u8]) -> [u32; 4] {
+    assert!(data.len().is_multiple_of(64), "data is not block-aligned");
 
-    let mut reader = BufReader::new(binding.as_slice());
+    data.chunks_exact(64).fold(INITIAL_STATE, |state, block| {
+        compress(&state, &bytes_to_words(block))
+    })
+}
 
-    let mut buf = [0; BLOCK_SIZE];
-    let mut count = 0;
+/// Finds a complete two-block collision starting from the chaining value
+/// `iv`.
+pub fn find_collision(iv: [u32; 4]) -> Collision {
+    find_collision_with(&DefaultBackend::default(), iv)
+}
 
-    // https://www.ietf.org/rfc/rfc1321.txt
+pub fn find_collision_with<B: CandidateBackend>(backend: &B, iv: [u32; 4]) -> Collision {
+    let mut search = Search::new(iv, random());
+    while !search.step(backend) {}
 
-    loop {
-        // read block
-        let res = reader.read(&mut buf).unwrap();
+    search.collision.unwrap()
+}
 
-        if res == 0 {
-            break;
-        }
+/// Builds two files that start with `prefix` (zero-padded to a block
+/// boundary) and have the same MD5.
+pub fn collide_prefix(prefix: &[u8]) -> CollidingFiles {
+    collide_prefix_with(&DefaultBackend::default(), prefix)
+}
 
-        count += res;
+pub fn collide_prefix_with<B: CandidateBackend>(backend: &B, prefix: &[u8]) -> CollidingFiles {
+    let padded = pad_prefix(prefix);
+    let collision = find_collision_with(backend, chaining_value(&padded));
 
-        // this is the last block
-        if res != BLOCK_SIZE {
-            // how many bytes are missing from a complete 64 byte
-            // multiple?
-            let size = 64 - (res as i32 % 64);
-            let padding_to_add: usize;
+    colliding_files(&padded, collision)
+}
 
-            // we do like this b/c: "as many zeros as are required to
-            // bring the length of the message up to 64 bits (8
-            // bytes) fewer than a multiple of 512 (64 bytes)"
+/// The two files `padded` followed by either message of `collision`.
+pub fn colliding_files(padded: &[u8], collision: Collision) -> CollidingFiles {
+    let (blocks, blocks_prim) = collision.to_bytes();
 
-            if (size - 8) < 0 {
-                padding_to_add = size as usize + (64 - 8);
-            } else {
-                padding_to_add = size as usize - 8;
-            }
+    CollidingFiles {
+        first: [padded, &blocks].concat(),
+        second: [padded, &blocks_prim].concat(),
+        collision,
+    }
+}
 
-            let mut padbuf = vec![0; padding_to_add];
-            padbuf[0] = 0x80;
+pub fn first_step(iv: [u32; 4]) -> NearCollision {
+    first_step_with(&DefaultBackend::default(), iv)
+}
 
-            // The remaining bits are filled up with 64 bits
-            // representing the length of the original message in
-            // *BITS* (not bytes), modulo 2^64.
+/// Searches for a first block pair `m0`, `m0_prim = m0 + ΔM0` whose chaining
+/// values form the near-collision the second block starts from.
+pub fn first_step_with<B: CandidateBackend>(backend: &B, iv: [u32; 4]) -> NearCollision {
+    let mut search = Search::new(iv, random());
+    while search.near_collision.is_none() {
+        search.step(backend);
+    }
 
-            let bitsize = Wrapping(count as u64 * 8);
-            let orig_size: [u8; 8] = bitsize.0.to_le_bytes();
-            let mut orig_size = orig_size.to_vec();
-            padbuf.append(&mut orig_size);
+    search.near_collision.unwrap()
+}
 
-            let mut buf2: Vec<u8> = Vec::new();
+pub fn second_step(m0: [u32; 16], m0_prim: [u32; 16]) -> Collision {
+    let near_collision =
+        NearCollision::new(INITIAL_STATE, m0, m0_prim).expect("m0 is not a near-collision");
 
-            buf2.append(&mut buf[0..res].to_vec());
-            buf2.append(&mut padbuf.to_vec());
+    second_step_with(&DefaultBackend::default(), &near_collision)
+}
 
-            state = process_block(&mut buf2, state);
-        } else {
-            state = process_block(&mut buf, state);
-        }
-    }
+pub fn second_step_with<B: CandidateBackend>(
+    backend: &B,
+    near_collision: &NearCollision,
+) -> Collision {
+    let mut search = Search::after(near_collision.clone(), random());
+    while !search.step(backend) {}
 
-    return state.into();
+    search.collision.unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::published::{blocks, wang, Blocks};
 
     #[test]
     fn table_2_test_1() {
-        let m0 = [
-            0x2dd31d1, 0xc4eee6c5, 0x69a3d69, 0x5cf9af98, 0x87b5ca2f, 0xab7e4612, 0x3e580440,
-            0x897ffbb8, 0x634ad55, 0x2b3f409, 0x8388e483, 0x5a417125, 0xe8255108, 0x9fc9cdf7,
-            0xf2bd1dd9, 0x5b3c3780,
-        ];
+        let Blocks {
+            m0,
+            m1,
+            m0_prim,
+            m1_prim,
+        } = wang();
 
-        let m1 = [
-            0xd11d0b96, 0x9c7b41dc, 0xf497d8e4, 0xd555655a, 0xc79a7335, 0xcfdebf0, 0x66f12930,
-            0x8fb109d1, 0x797f2775, 0xeb5cd530, 0xbaade822, 0x5c15cc79, 0xddcb74ed, 0x6dd3c55f,
-            0xd80a9bb1, 0xe3a7cc35,
-        ];
+        let result = compress(&compress(&INITIAL_STATE, &m0), &m1);
+        let result_prim = compress(&compress(&INITIAL_STATE, &m0_prim), &m1_prim);
 
-        let result = hash(&hash(&INITIAL_STATE, &m0), &m1);
+        assert_eq!(result, result_prim);
+        assert_eq!(result, [0x9603161f, 0xa30f9dbf, 0x9f65ffbc, 0xf41fc7ef]);
 
-        let m0_prim = [
-            0x2dd31d1, 0xc4eee6c5, 0x69a3d69, 0x5cf9af98, 0x7b5ca2f, 0xab7e4612, 0x3e580440,
-            0x897ffbb8, 0x634ad55, 0x2b3f409, 0x8388e483, 0x5a41f125, 0xe8255108, 0x9fc9cdf7,
-            0x72bd1dd9, 0x5b3c3780,
-        ];
+        let paths = Paths::wang();
+        assert!(paths.first_delta.matches(&m0, &m0_prim));
+        assert!(paths.second_delta.matches(&m1, &m1_prim));
+    }
 
-        let m1_prim = [
-            0xd11d0b96, 0x9c7b41dc, 0xf497d8e4, 0xd555655a, 0x479a7335, 0xcfdebf0, 0x66f12930,
-            0x8fb109d1, 0x797f2775, 0xeb5cd530, 0xbaade822, 0x5c154c79, 0xddcb74ed, 0x6dd3c55f,
-            0x580a9bb1, 0xe3a7cc35,
-        ];
+    #[test]
+    fn table_2_test_2() {
+        let Blocks {
+            m0,
+            m1,
+            m0_prim,
+            m1_prim,
+        } = blocks("wang-2004-2");
 
-        let result_prim = hash(&hash(&INITIAL_STATE, &m0_prim), &m1_prim);
+        let result = compress(&compress(&INITIAL_STATE, &m0), &m1);
+        let result_prim = compress(&compress(&INITIAL_STATE, &m0_prim), &m1_prim);
 
         assert_eq!(result, result_prim);
-        assert_eq!(result, [0x9603161f, 0xa30f9dbf, 0x9f65ffbc, 0xf41fc7ef]);
+        assert_eq!(result, [0x8d5e7019, 0x61804e08, 0x715d6b58, 0x6324c015]);
 
-        let mut delta_m0: [u32; 16] = [0; 16];
+        let paths = Paths::wang();
+        assert!(paths.first_delta.matches(&m0, &m0_prim));
+        assert!(paths.second_delta.matches(&m1, &m1_prim));
+    }
 
-        for i in 0..16 {
-            delta_m0[i] = m0_prim[i] ^ m0[i];
-        }
+    #[test]
+    fn table_2_first_block_is_near_collision() {
+        let m0 = wang().m0;
 
-        assert_eq!(delta_m0, M0_DELTA);
+        let near_collision =
+            NearCollision::new(INITIAL_STATE, m0, Paths::wang().first_delta.apply(&m0)).unwrap();
 
-        let mut m1_prim_from_m1: [u32; 16] = [0; 16];
+        assert_eq!(
+            near_collision.state_m0,
+            [0x52589324, 0x3093d7ca, 0x2a06dc54, 0x20c5be06]
+        );
+        assert_eq!(
+            near_collision.state_m0_prim,
+            [0xd2589324, 0xb293d7ca, 0xac06dc54, 0xa2c5be06]
+        );
 
-        for i in 0..16 {
-            m1_prim_from_m1[i] = m1[i] ^ M0_DELTA[i];
+        // any single bit flip breaks the path
+        for bit in 0..512 {
+            let mut m0_broken = m0;
+            m0_broken[bit / 32] ^= 1 << (bit % 32);
+            assert!(
+                NearCollision::new(
+                    INITIAL_STATE,
+                    m0_broken,
+                    Paths::wang().first_delta.apply(&m0_broken)
+                )
+                .is_none(),
+                "flipping bit {} keeps the path",
+                bit
+            );
         }
-
-        assert_eq!(m1_prim_from_m1, m1_prim);
     }
 
     #[test]
-    fn table_2_test_2() {
-        let m0 = [
-            0x2dd31d1, 0xc4eee6c5, 0x69a3d69, 0x5cf9af98, 0x87b5ca2f, 0xab7e4612, 0x3e580440,
-            0x897ffbb8, 0x634ad55, 0x2b3f409, 0x8388e483, 0x5a417125, 0xe8255108, 0x9fc9cdf7,
-            0xf2bd1dd9, 0x5b3c3780,
-        ];
+    fn prefix_is_padded_to_blocks() {
+        assert_eq!(pad_prefix(b""), b"");
+        assert_eq!(pad_prefix(b"abc").len(), 64);
+        assert_eq!(pad_prefix(&[1; 64]), [1; 64]);
+        assert_eq!(pad_prefix(&[1; 65]).len(), 128);
+        assert_eq!(chaining_value(b""), INITIAL_STATE);
+    }
 
-        let m1 = [
-            0x313e82d8, 0x5b8f3456, 0xd4ac6dae, 0xc619c936, 0xb4e253dd, 0xfd03da87, 0x6633902,
-            0xa0cd48d2, 0x42339fe9, 0xe87e570f, 0x70b654ce, 0x1e0da880, 0xbc2198c6, 0x9383a8b6,
-            0x2b65f996, 0x702af76f,
-        ];
+    #[test]
+    fn table_2_colliding_files() {
+        let Blocks { m0, m1, .. } = wang();
+
+        let collision = Collision {
+            iv: INITIAL_STATE,
+            m0,
+            m1,
+            m0_prim: Paths::wang().first_delta.apply(&m0),
+            m1_prim: Paths::wang().second_delta.apply(&m1),
+            hash: compress(&INITIAL_STATE, &m0),
+        };
+
+        let files = colliding_files(&[], collision);
+
+        assert_ne!(files.first, files.second);
+        assert_eq!(files.first.len(), 128);
+        assert_eq!(chaining_value(&files.first), chaining_value(&files.second));
+        assert_eq!(digest(&files.first), digest(&files.second));
+        assert_eq!(
+            chaining_value(&files.first),
+            [0x9603161f, 0xa30f9dbf, 0x9f65ffbc, 0xf41fc7ef]
+        );
+    }
 
-        let result = hash(&hash(&INITIAL_STATE, &m0), &m1);
+    fn to_hex(digest: &[u8]) -> String {
+        digest.iter().map(|x| format!("{:02x}", x)).collect()
+    }
 
-        let m0_prim = [
-            0x2dd31d1, 0xc4eee6c5, 0x69a3d69, 0x5cf9af98, 0x7b5ca2f, 0xab7e4612, 0x3e580440,
-            0x897ffbb8, 0x634ad55, 0x2b3f409, 0x8388e483, 0x5a41f125, 0xe8255108, 0x9fc9cdf7,
-            0x72bd1dd9, 0x5b3c3780,
+    #[test]
+    fn rfc_1321_test_suite() {
+        let suite = [
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("a", "0cc175b9c0f1b6a831c399e269772661"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (
+                "abcdefghijklmnopqrstuvwxyz",
+                "c3fcd3d76192e4007dfb496cca67e13b",
+            ),
+            (
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "d174ab98d277d9f5a5611c2c9f419d9f",
+            ),
+            (
+                "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
         ];
 
-        let m1_prim = [
-            0x313e82d8, 0x5b8f3456, 0xd4ac6dae, 0xc619c936, 0x34e253dd, 0xfd03da87, 0x6633902,
-            0xa0cd48d2, 0x42339fe9, 0xe87e570f, 0x70b654ce, 0x1e0d2880, 0xbc2198c6, 0x9383a8b6,
-            0xab65f996, 0x702af76f,
-        ];
+        for (input, expected) in suite {
+            assert_eq!(to_hex(&digest(input.as_bytes())), expected, "{:?}", input);
+        }
+    }
 
-        let result_prim = hash(&hash(&INITIAL_STATE, &m0_prim), &m1_prim);
+    #[test]
+    fn padding_boundaries() {
+        // lengths around the point where the length no longer fits into the
+        // last block
+        for len in [55, 56, 57, 63, 64, 65, 119, 120, 128] {
+            let padded = len + padding(len as u64).len() + 8;
+            assert!(padded.is_multiple_of(BLOCK_SIZE), "{}", len);
+            assert!(padded - len <= BLOCK_SIZE + 8, "{}", len);
+        }
 
-        assert_eq!(result, result_prim);
-        assert_eq!(result, [0x8d5e7019, 0x61804e08, 0x715d6b58, 0x6324c015]);
+        assert_eq!(
+            to_hex(&digest(&[b'a'; 1000])),
+            "cabe45dcc9ae5b66ba86600cca6b8ba8"
+        );
+    }
 
-        let mut delta_m0: [u32; 16] = [0; 16];
+    #[test]
+    fn streaming_matches_one_shot() {
+        let data = (0..1000u32).map(|x| x as u8).collect::<Vec<u8>>();
 
-        for i in 0..16 {
-            delta_m0[i] = m0_prim[i] ^ m0[i];
+        for chunk_size in [1, 3, 63, 64, 65, 200] {
+            let mut md5 = Md5::new();
+            for chunk in data.chunks(chunk_size) {
+                md5.update(chunk);
+            }
+            assert_eq!(md5.finalize(), digest(&data), "{}", chunk_size);
         }
+    }
 
-        assert_eq!(delta_m0, M0_DELTA);
-
-        let mut m1_prim_from_m1: [u32; 16] = [0; 16];
+    #[test]
+    fn resume_from_state() {
+        let data = (0..300u32).map(|x| x as u8).collect::<Vec<u8>>();
 
-        for i in 0..16 {
-            m1_prim_from_m1[i] = m1[i] ^ M0_DELTA[i];
-        }
+        let state = (0..2).fold(INITIAL_STATE, |state, i| {
+            compress(&state, &bytes_to_words(&data[i * 64..i * 64 + 64]))
+        });
 
-        assert_eq!(m1_prim_from_m1, m1_prim);
+        let mut md5 = Md5::from_state(state, 128);
+        md5.update(&data[128..]);
+        assert_eq!(md5.finalize(), digest(&data));
     }
 }
@@ -0,0 +1,233 @@
+// Joux multicollisions: t collisions chained one after the other, each from
+// the chaining value the previous one ends in. Either message of every
+// collision leads to the same next chaining value, so any of the 2^t
+// combinations has the same MD5 — for the price of t collision searches.
+//
+// With 2^(n/2) messages that collide in MD5 a birthday collision in a
+// second n-bit hash comes for free, which is why concatenating MD5 with
+// another iterated hash is barely stronger than the stronger of the two
+// (`Multicollision::find_pair_by`).
+
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::CandidateBackend;
+use crate::md5::{chaining_value, compress, pad_prefix, Collision};
+use crate::search::{batch_seed, Search};
+
+/// 2^t messages with the same MD5, given by t chained collisions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Multicollision {
+    /// The zero-padded prefix every message starts with.
+    pub prefix: Vec<u8>,
+    pub collisions: Vec<Collision>,
+}
+
+impl Multicollision {
+    /// An empty multicollision after `prefix`, zero-padded to a block
+    /// boundary; it holds the single message `prefix` until collisions are
+    /// pushed.
+    pub fn new(prefix: &[u8]) -> Self {
+        Multicollision {
+            prefix: pad_prefix(prefix),
+            collisions: Vec::new(),
+        }
+    }
+
+    /// Searches for `t` collisions, deterministically for a given `seed` and
+    /// backend.
+    pub fn build_with<B: CandidateBackend>(
+        backend: &B,
+        prefix: &[u8],
+        t: usize,
+        seed: u64,
+    ) -> Self {
+        let mut multicollision = Multicollision::new(prefix);
+
+        for idx in 0..t {
+            let mut search = Search::new(multicollision.hash(), batch_seed(seed, idx as u64));
+            while !search.step(backend) {}
+
+            multicollision.push(search.collision.unwrap());
+        }
+
+        multicollision
+    }
+
+    /// Chains `collision`, which has to start from `hash()`.
+    pub fn push(&mut self, collision: Collision) {
+        assert_eq!(collision.iv, self.hash(), "the collision does not chain");
+
+        let (m0, m0_prim) = (collision.m0, collision.m0_prim);
+        let (m1, m1_prim) = (collision.m1, collision.m1_prim);
+        assert_eq!(
+            compress(&compress(&collision.iv, &m0), &m1),
+            compress(&compress(&collision.iv, &m0_prim), &m1_prim),
+            "the blocks do not collide"
+        );
+
+        self.collisions.push(collision);
+    }
+
+    /// Number of chained collisions.
+    pub fn t(&self) -> usize {
+        self.collisions.len()
+    }
+
+    /// The chaining value every message ends in.
+    pub fn hash(&self) -> [u32; 4] {
+        match self.collisions.last() {
+            Some(collision) => compress(&compress(&collision.iv, &collision.m0), &collision.m1),
+            None => chaining_value(&self.prefix),
+        }
+    }
+
+    /// Message `index`, `0 <= index < 2^t`: bit i of `index` picks the
+    /// second message of collision i.
+    pub fn message(&self, index: u64) -> Vec<u8> {
+        let mut message = self.prefix.clone();
+
+        for (idx, collision) in self.collisions.iter().enumerate() {
+            let (blocks, blocks_prim) = collision.to_bytes();
+            if index >> idx & 1 == 0 {
+                message.extend(blocks);
+            } else {
+                message.extend(blocks_prim);
+            }
+        }
+
+        message
+    }
+
+    /// Every message, built one at a time as the iterator advances.
+    pub fn messages(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        assert!(self.t() < 64, "too many messages to index");
+        (0..1u64 << self.t()).map(|index| self.message(index))
+    }
+
+    /// Writes every message to `dir`, named after the collision sides it
+    /// takes, e.g. `0110.bin` for the second message of collisions 1 and 2
+    /// out of four.
+    pub fn export(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        for (index, message) in self.messages().enumerate() {
+            let sides = (0..self.t())
+                .map(|idx| if index >> idx & 1 == 0 { '0' } else { '1' })
+                .collect::<String>();
+            fs::write(dir.join(format!("{}.bin", sides)), message)?;
+        }
+
+        Ok(())
+    }
+
+    /// Indices of the first two messages `key` maps to the same value, e.g.
+    /// the same digest under a second hash function; `None` if all keys
+    /// differ. Messages are enumerated lazily and only the keys are kept.
+    pub fn find_pair_by<K: Hash + Eq>(&self, key: impl Fn(&[u8]) -> K) -> Option<(u64, u64)> {
+        let mut seen = HashMap::new();
+
+        for (index, message) in self.messages().enumerate() {
+            let key = key(&message);
+            if let Some(&first) = seen.get(&key) {
+                return Some((first, index as u64));
+            }
+            seen.insert(key, index as u64);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md4;
+    use crate::md5::{digest, INITIAL_STATE};
+    use crate::published::Published;
+
+    /// `t` chained placeholders that do not collide, to check the
+    /// enumeration without t real collision searches.
+    fn placeholders(t: usize) -> Multicollision {
+        let collision = |idx: u32| Collision {
+            iv: INITIAL_STATE,
+            m0: [2 * idx; 16],
+            m1: [2 * idx; 16],
+            m0_prim: [2 * idx + 1; 16],
+            m1_prim: [2 * idx + 1; 16],
+            hash: INITIAL_STATE,
+        };
+
+        Multicollision {
+            prefix: Vec::new(),
+            collisions: (0..t as u32).map(collision).collect(),
+        }
+    }
+
+    #[test]
+    fn builds_on_the_published_collision() {
+        let multicollision = Multicollision::build_with(&Published, b"", 1, 0);
+        let messages = multicollision.messages().collect::<Vec<_>>();
+
+        assert_eq!(messages.len(), 2);
+        assert_ne!(messages[0], messages[1]);
+        assert_eq!(digest(&messages[0]), digest(&messages[1]));
+        assert_eq!(
+            multicollision.hash(),
+            [0x9603161f, 0xa30f9dbf, 0x9f65ffbc, 0xf41fc7ef]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "does not chain")]
+    fn rejects_a_collision_from_elsewhere() {
+        let mut multicollision = Multicollision::build_with(&Published, b"", 1, 0);
+        let collision = multicollision.collisions[0].clone();
+
+        multicollision.push(collision);
+    }
+
+    #[test]
+    fn messages_take_one_side_of_every_collision() {
+        let multicollision = placeholders(3);
+        let messages = multicollision.messages().collect::<Vec<_>>();
+
+        assert_eq!(messages.len(), 8);
+        for (index, message) in messages.iter().enumerate() {
+            assert_eq!(message.len(), 3 * 128);
+
+            for idx in 0..3 {
+                let side = message[idx * 128] as usize % 2;
+                assert_eq!(side, index >> idx & 1, "{} {}", index, idx);
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("multicollision-{}", std::process::id()));
+        multicollision.export(&dir).unwrap();
+        assert_eq!(fs::read(dir.join("011.bin")).unwrap(), messages[6]);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 8);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn pairs_up_messages_under_a_second_hash() {
+        // a collision of MD5 || (one bit of MD4), the combiner Joux attacks:
+        // 2^t messages that collide in MD5 contain a collision in t bits
+        // of a second hash with good odds, here with two messages and luck
+        let multicollision = Multicollision::build_with(&Published, b"", 1, 0);
+        let combined = |message: &[u8]| (digest(message), md4::digest(message)[0] & 1);
+
+        assert_eq!(multicollision.find_pair_by(combined), Some((0, 1)));
+
+        // pairs are only reported for equal keys
+        assert_eq!(
+            placeholders(4).find_pair_by(|message| message.to_vec()),
+            None
+        );
+    }
+}
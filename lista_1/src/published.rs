@@ -0,0 +1,292 @@
+// Regression tests against published MD5 collisions, kept in
+// collisions/published.txt: the digests of both messages, the difference of
+// the chaining values after every block, and for pairs along Wang's paths
+// the sufficient conditions of every block, as the path files give them and
+// as the ports of kernel.cu check them.
+//
+// The other tests take their published blocks from here as well, through
+// `wang` and the `Published` backend.
+
+use crate::backend::CandidateBackend;
+use crate::conditions::table::{parse_difference, ConditionTable};
+use crate::md5::{bytes_to_words, compress, INITIAL_STATE};
+
+/// One entry of collisions/published.txt.
+struct Entry {
+    name: String,
+    path: String,
+    m: Vec<u8>,
+    m_prim: Vec<u8>,
+    md5: String,
+    /// IHV' - IHV after every block.
+    ihv: Vec<[u32; 4]>,
+    /// Sufficient conditions of the path files that the pair does without.
+    breaks: ConditionTable,
+}
+
+impl Entry {
+    fn blocks(message: &[u8]) -> Vec<[u32; 16]> {
+        message.chunks_exact(64).map(bytes_to_words).collect()
+    }
+
+    /// The chaining values before every block and after the last one.
+    fn chaining_values(message: &[u8]) -> Vec<[u32; 4]> {
+        Entry::blocks(message)
+            .iter()
+            .fold(vec![INITIAL_STATE], |mut values, block| {
+                values.push(compress(values.last().unwrap(), block));
+                values
+            })
+    }
+}
+
+fn published() -> Vec<Entry> {
+    let text = include_str!("../collisions/published.txt");
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut last_key = "";
+
+    for line in text.lines() {
+        if line.trim_start().starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[') {
+            entries.push(Entry {
+                name: name.trim_end_matches(']').to_string(),
+                path: String::new(),
+                m: Vec::new(),
+                m_prim: Vec::new(),
+                md5: String::new(),
+                ihv: Vec::new(),
+                breaks: ConditionTable::default(),
+            });
+            continue;
+        }
+
+        let entry = entries.last_mut().expect("an entry starts with [name]");
+        // lines starting with whitespace continue the messages
+        let (key, value) = match line.starts_with(char::is_whitespace) {
+            true => (last_key, line),
+            false => line.split_once('=').expect("key = value"),
+        };
+        last_key = key;
+
+        match key.trim() {
+            "path" => entry.path = value.trim().to_string(),
+            "m" => entry.m.extend(from_hex(value)),
+            "m'" => entry.m_prim.extend(from_hex(value)),
+            "md5" => entry.md5 = value.trim().to_string(),
+            "ihv" => {
+                let words = value
+                    .split(',')
+                    .map(|word| parse_difference(word).unwrap())
+                    .collect::<Vec<_>>();
+                entry.ihv.push(words.try_into().unwrap());
+            }
+            "breaks" => {
+                let table = ConditionTable::parse(value).unwrap();
+                entry.breaks.conditions.extend(table.conditions);
+            }
+            key => panic!("unknown key '{}'", key),
+        }
+    }
+
+    entries
+}
+
+/// The blocks of a published two-block collision.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Blocks {
+    pub m0: [u32; 16],
+    pub m1: [u32; 16],
+    pub m0_prim: [u32; 16],
+    pub m1_prim: [u32; 16],
+}
+
+/// The blocks of the entry `name`.
+pub(crate) fn blocks(name: &str) -> Blocks {
+    let entry = published()
+        .into_iter()
+        .find(|entry| entry.name == name)
+        .unwrap_or_else(|| panic!("no entry [{}]", name));
+
+    let [m0, m1] = Entry::blocks(&entry.m)[..] else {
+        panic!("{} has two blocks", name);
+    };
+    let [m0_prim, m1_prim] = Entry::blocks(&entry.m_prim)[..] else {
+        panic!("{} has two blocks", name);
+    };
+
+    Blocks {
+        m0,
+        m1,
+        m0_prim,
+        m1_prim,
+    }
+}
+
+/// Wang and Yu's first collision, the one the tests start from.
+pub(crate) fn wang() -> Blocks {
+    blocks("wang-2004-1")
+}
+
+/// Stands in for a search: returns the blocks of `wang()` for one seed in
+/// eight, and blocks that do not collide otherwise.
+pub(crate) struct Published;
+
+impl CandidateBackend for Published {
+    fn generate_candidates(&self, _: &[u32; 4], seed: u64) -> Vec<[u32; 16]> {
+        if seed.is_multiple_of(8) {
+            vec![[seed as u32; 16], wang().m1]
+        } else {
+            vec![[seed as u32; 16]]
+        }
+    }
+
+    fn generate_first_block_candidates(&self, _: &[u32; 4], seed: u64) -> Vec<[u32; 16]> {
+        if seed.is_multiple_of(8) {
+            vec![wang().m0]
+        } else {
+            vec![[seed as u32; 16]; 2]
+        }
+    }
+
+    fn generate_path_candidates(
+        &self,
+        state: &[u32; 4],
+        table: &ConditionTable,
+        seed: u64,
+    ) -> Vec<[u32; 16]> {
+        // the seeds differ from the built-in searches', so that a test
+        // notices which one ran
+        match *table == crate::conditions::first_block::table() {
+            true => self.generate_first_block_candidates(state, seed + 1),
+            false => self.generate_candidates(state, seed + 1),
+        }
+    }
+}
+
+fn from_hex(text: &str) -> Vec<u8> {
+    let text = text.trim();
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&text[idx..idx + 2], 16).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::cpu::{check_candidate, check_first_block};
+    use crate::differential::Paths;
+    use crate::family::{Block, Conditions};
+    use crate::md5::{digest, Md5, NearCollision};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|x| format!("{:02x}", x)).collect()
+    }
+
+    #[test]
+    fn published_pairs_collide() {
+        let entries = published();
+        assert_eq!(entries.len(), 3);
+
+        for entry in entries {
+            assert_ne!(entry.m, entry.m_prim, "{}", entry.name);
+            assert_eq!(entry.m.len(), entry.m_prim.len(), "{}", entry.name);
+
+            for message in [&entry.m, &entry.m_prim] {
+                assert_eq!(to_hex(&digest(message)), entry.md5, "{}", entry.name);
+
+                // the streaming hasher, fed in pieces that straddle the blocks
+                let mut md5 = Md5::new();
+                for piece in message.chunks(37) {
+                    md5.update(piece);
+                }
+                assert_eq!(to_hex(&md5.finalize()), entry.md5, "{}", entry.name);
+            }
+        }
+    }
+
+    #[test]
+    fn chaining_values_differ_block_by_block() {
+        for entry in published() {
+            let values = Entry::chaining_values(&entry.m);
+            let values_prim = Entry::chaining_values(&entry.m_prim);
+            assert_eq!(entry.ihv.len(), values.len() - 1, "{}", entry.name);
+
+            for (block, expected) in entry.ihv.iter().enumerate() {
+                let difference: [u32; 4] = std::array::from_fn(|i| {
+                    values_prim[block + 1][i].wrapping_sub(values[block + 1][i])
+                });
+                assert_eq!(difference, *expected, "{} block {}", entry.name, block);
+            }
+        }
+    }
+
+    #[test]
+    fn wang_pairs_meet_the_sufficient_conditions() {
+        let paths = Paths::wang();
+        let first = Conditions::new::<Md5>(&paths.first);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let entries = published().into_iter().filter(|entry| entry.path == "wang");
+        for entry in entries {
+            let [m0, m1] = Entry::blocks(&entry.m)[..] else {
+                panic!("{} has two blocks", entry.name);
+            };
+            let [m0_prim, m1_prim] = Entry::blocks(&entry.m_prim)[..] else {
+                panic!("{} has two blocks", entry.name);
+            };
+
+            assert!(paths.first_delta.matches(&m0, &m0_prim), "{}", entry.name);
+            assert!(paths.second_delta.matches(&m1, &m1_prim), "{}", entry.name);
+
+            // the conditions of the path files, but for those the pair is known
+            // to break, each of which it must really break
+            let state = compress(&INITIAL_STATE, &m0);
+            assert!(Block::<Md5>::new(&INITIAL_STATE, &m0).check(1..=64, &first));
+            assert!(paths.second.starts_from(&state), "{}", entry.name);
+
+            let mut kept = paths.second.clone();
+            kept.conditions
+                .retain(|condition| !entry.breaks.conditions.contains(condition));
+            let second = Conditions::new::<Md5>(&kept);
+            assert!(Block::<Md5>::new(&state, &m1).check(1..=64, &second));
+
+            for &condition in &entry.breaks.conditions {
+                assert!(
+                    paths.second.conditions.contains(&condition),
+                    "{}",
+                    entry.name
+                );
+                let table = ConditionTable {
+                    conditions: vec![condition],
+                    ..ConditionTable::default()
+                };
+                let broken = Conditions::new::<Md5>(&table);
+                assert!(!Block::<Md5>::new(&state, &m1).check(1..=64, &broken));
+            }
+
+            // and the same conditions checked the way kernel.cu checks them,
+            // which must not modify the blocks
+            let (mut words, mut words_1) = (m0, m1);
+            assert_eq!(
+                check_first_block(&INITIAL_STATE, &mut words, &first, &mut rng),
+                64
+            );
+            assert_eq!(
+                check_candidate(&state, &mut words_1, &second),
+                64,
+                "{}",
+                entry.name
+            );
+            assert_eq!((words, words_1), (m0, m1), "{}", entry.name);
+
+            assert!(NearCollision::new(INITIAL_STATE, m0, m0_prim).is_some());
+        }
+    }
+}
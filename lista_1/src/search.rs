@@ -0,0 +1,399 @@
+// The identical-prefix collision search as a value: everything the search
+// has found and tried so far, which serializes to JSON so that a long search
+// can be saved and resumed.
+//
+// Batch i of a search runs with `batch_seed(seed, i)`, so the batches and
+// with them the collision found depend on the seed and the backend settings
+// only, not on when the search was interrupted.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::CandidateBackend;
+use crate::conditions::table::ParseError;
+use crate::differential::Paths;
+use crate::md5::{Collision, NearCollision};
+use crate::telemetry::{Report, Survival, Telemetry};
+
+/// Seed of batch `batch` of a search seeded with `seed` (SplitMix64).
+pub fn batch_seed(seed: u64, batch: u64) -> u64 {
+    let mut z = seed.wrapping_add((batch + 1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// The path files of both blocks, kept as text so that a checkpoint
+/// carries the paths its search follows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathFiles {
+    pub first: String,
+    pub second: String,
+}
+
+/// `PathFiles` and what they parse to. Saved as the files and parsed again
+/// on loading, so that a checkpoint whose paths do not parse fails to load.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "PathFiles", into = "PathFiles")]
+struct ParsedPaths {
+    files: PathFiles,
+    parsed: Paths,
+}
+
+impl TryFrom<PathFiles> for ParsedPaths {
+    type Error = ParseError;
+
+    fn try_from(files: PathFiles) -> Result<ParsedPaths, ParseError> {
+        Ok(ParsedPaths {
+            parsed: Paths::parse(&files.first, &files.second)?,
+            files,
+        })
+    }
+}
+
+impl From<ParsedPaths> for PathFiles {
+    fn from(paths: ParsedPaths) -> PathFiles {
+        paths.files
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Search {
+    pub iv: [u32; 4],
+    pub seed: u64,
+    /// Batches run so far; their seeds are `batch_seed(seed, 0..batches)`.
+    pub batches: u64,
+    /// Candidates returned by the backend so far.
+    pub candidates: u64,
+    /// Time spent in the search, over all runs.
+    pub elapsed: Duration,
+    /// The paths searched along; Wang's, with the backend's built-in
+    /// searches, if `None`.
+    #[serde(default)]
+    paths: Option<ParsedPaths>,
+    /// How deep the trials for the block searched for now got, as far as
+    /// the backend keeps count.
+    #[serde(default)]
+    pub survival: Survival,
+    /// Duration of the last batch and its trials past round 1.
+    #[serde(default)]
+    pub last_batch: (Duration, u64),
+    pub near_collision: Option<NearCollision>,
+    pub collision: Option<Collision>,
+}
+
+impl Search {
+    /// A search for a complete two-block collision from `iv`.
+    pub fn new(iv: [u32; 4], seed: u64) -> Search {
+        Search {
+            iv,
+            seed,
+            batches: 0,
+            candidates: 0,
+            elapsed: Duration::ZERO,
+            paths: None,
+            survival: Survival::default(),
+            last_batch: (Duration::ZERO, 0),
+            near_collision: None,
+            collision: None,
+        }
+    }
+
+    /// A search for the second block only.
+    pub fn after(near_collision: NearCollision, seed: u64) -> Search {
+        Search {
+            near_collision: Some(near_collision.clone()),
+            ..Search::new(near_collision.iv, seed)
+        }
+    }
+
+    /// A search along the paths in `files` instead of Wang's.
+    pub fn with_paths(iv: [u32; 4], seed: u64, files: PathFiles) -> Result<Search, ParseError> {
+        Ok(Search {
+            paths: Some(files.try_into()?),
+            ..Search::new(iv, seed)
+        })
+    }
+
+    /// The paths searched along.
+    pub fn paths(&self) -> &Paths {
+        paths(&self.paths)
+    }
+
+    /// Runs the next batch. Returns `true` once the collision is found.
+    pub fn step<B: CandidateBackend>(&mut self, backend: &B) -> bool {
+        if self.collision.is_some() {
+            return true;
+        }
+
+        let start = Instant::now();
+        let seed = batch_seed(self.seed, self.batches);
+        let searching_first_block = self.near_collision.is_none();
+        // borrows the field only, as the rest of the search changes
+        let paths = paths(&self.paths);
+
+        match &self.near_collision {
+            None => {
+                let candidates = match self.paths {
+                    Some(_) => backend.generate_path_candidates(&self.iv, &paths.first, seed),
+                    None => backend.generate_first_block_candidates(&self.iv, seed),
+                };
+
+                for candidate in candidates {
+                    self.candidates += 1;
+
+                    let candidate_prim = paths.first_delta.apply(&candidate);
+                    if let Some(near_collision) =
+                        NearCollision::with_paths(self.iv, candidate, candidate_prim, paths)
+                    {
+                        self.near_collision = Some(near_collision);
+                        break;
+                    }
+                }
+            }
+            Some(near_collision) => {
+                let state = near_collision.state_m0;
+                let candidates = match self.paths {
+                    Some(_) => backend.generate_path_candidates(&state, &paths.second, seed),
+                    None => backend.generate_candidates(&state, seed),
+                };
+                let candidates_prim = candidates
+                    .iter()
+                    .map(|candidate| paths.second_delta.apply(candidate))
+                    .collect::<Vec<_>>();
+
+                // both sides of the whole batch at once, a block per lane
+                let count = candidates.len();
+                let hashes = backend.compress_batch(&vec![state; count], &candidates);
+                let hashes_prim = backend
+                    .compress_batch(&vec![near_collision.state_m0_prim; count], &candidates_prim);
+
+                match (0..count).find(|&idx| hashes[idx] == hashes_prim[idx]) {
+                    Some(idx) => {
+                        self.candidates += idx as u64 + 1;
+                        self.collision = Some(Collision {
+                            iv: self.iv,
+                            m0: near_collision.m0,
+                            m1: candidates[idx],
+                            m0_prim: near_collision.m0_prim,
+                            m1_prim: candidates_prim[idx],
+                            hash: state,
+                        });
+                    }
+                    None => self.candidates += count as u64,
+                }
+            }
+        }
+
+        let survival = backend.take_survival();
+        self.last_batch = (start.elapsed(), survival.trials());
+        if searching_first_block && self.near_collision.is_some() {
+            self.survival = Survival::default();
+        } else {
+            self.survival.merge(&survival);
+        }
+
+        self.batches += 1;
+        self.elapsed += start.elapsed();
+
+        self.collision.is_some()
+    }
+
+    /// Throughput, survival rates and the expected time of the block
+    /// searched for now.
+    pub fn report(&self) -> Report {
+        let paths = self.paths();
+        let (block, table) = match self.near_collision {
+            None => (1, &paths.first),
+            Some(_) => (2, &paths.second),
+        };
+
+        let per_second = |count: u64, elapsed: Duration| {
+            if elapsed.is_zero() {
+                0.0
+            } else {
+                count as f64 / elapsed.as_secs_f64()
+            }
+        };
+        let trials_per_second = per_second(self.last_batch.1, self.last_batch.0);
+
+        let expected = match self.survival.candidate_probability(table) {
+            Some(probability) if trials_per_second > 0.0 && self.collision.is_none() => {
+                Some(1.0 / (probability * trials_per_second))
+            }
+            _ => None,
+        };
+
+        Report {
+            block,
+            batches: self.batches,
+            elapsed: self.elapsed.as_secs_f64(),
+            candidates: self.candidates,
+            candidates_per_second: per_second(self.candidates, self.elapsed),
+            trials: self.survival.trials(),
+            trials_per_second,
+            survival: self.survival.fractions(),
+            expected,
+            found: self.collision.is_some(),
+        }
+    }
+
+    /// Steps until the collision is found, reporting every batch to
+    /// `telemetry` and handing the search to `checkpoint` every `interval`
+    /// and at the end.
+    pub fn run<B: CandidateBackend>(
+        &mut self,
+        backend: &B,
+        interval: Duration,
+        telemetry: &mut Telemetry,
+        mut checkpoint: impl FnMut(&Search) -> io::Result<()>,
+    ) -> io::Result<Collision> {
+        let mut last = Instant::now();
+
+        loop {
+            let found = self.step(backend);
+            telemetry.record(&self.report())?;
+            if found {
+                break;
+            }
+
+            if last.elapsed() >= interval {
+                checkpoint(self)?;
+                last = Instant::now();
+            }
+        }
+
+        checkpoint(self)?;
+        Ok(self.collision.clone().unwrap())
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        save_json(self, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Search> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+fn paths(paths: &Option<ParsedPaths>) -> &Paths {
+    match paths {
+        Some(paths) => &paths.parsed,
+        None => Paths::wang(),
+    }
+}
+
+/// Writes `value` as JSON to a temporary file and renames it to `path`, so
+/// that a kill during the write keeps the previous checkpoint intact.
+pub fn save_json(value: &impl Serialize, path: &Path) -> io::Result<()> {
+    let partial = path.with_extension("partial");
+    fs::write(&partial, serde_json::to_vec(value)?)?;
+    fs::rename(partial, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md5::INITIAL_STATE;
+    use crate::published::{wang, Published};
+
+    const FIRST_PATH: &str = include_str!("../paths/first_block.txt");
+    const SECOND_PATH: &str = include_str!("../paths/second_block.txt");
+
+    fn finished(mut search: Search) -> Search {
+        while !search.step(&Published) {}
+        search.elapsed = Duration::ZERO;
+        search.last_batch = (Duration::ZERO, 0);
+        search
+    }
+
+    #[test]
+    fn finds_the_published_collision() {
+        let search = finished(Search::new(INITIAL_STATE, 1));
+        let collision = search.collision.unwrap();
+
+        assert_eq!((collision.m0, collision.m1), (wang().m0, wang().m1));
+        assert!(search.batches >= 2);
+    }
+
+    #[test]
+    fn resumed_search_matches_uninterrupted() {
+        let uninterrupted = finished(Search::new(INITIAL_STATE, 7));
+
+        for stop in 0..uninterrupted.batches {
+            let mut search = Search::new(INITIAL_STATE, 7);
+            for _ in 0..stop {
+                search.step(&Published);
+            }
+
+            let json = serde_json::to_string(&search).unwrap();
+            let resumed = serde_json::from_str(&json).unwrap();
+            assert_eq!(finished(resumed), uninterrupted);
+        }
+    }
+
+    #[test]
+    fn follows_paths_loaded_from_files() {
+        let files = PathFiles {
+            first: FIRST_PATH.to_string(),
+            second: SECOND_PATH.to_string(),
+        };
+        let search = Search::with_paths(INITIAL_STATE, 1, files).unwrap();
+        assert!(search.paths().is_wang());
+
+        // the paths survive a checkpoint
+        let json = serde_json::to_string(&search).unwrap();
+        let search = finished(serde_json::from_str(&json).unwrap());
+        let collision = search.collision.unwrap();
+
+        assert_eq!((collision.m0, collision.m1), (wang().m0, wang().m1));
+        assert_ne!(
+            search.batches,
+            finished(Search::new(INITIAL_STATE, 1)).batches
+        );
+    }
+
+    #[test]
+    fn corrupt_checkpoint_paths_fail_to_load() {
+        let files = PathFiles {
+            first: FIRST_PATH.to_string(),
+            second: SECOND_PATH.to_string(),
+        };
+        let search = Search::with_paths(INITIAL_STATE, 1, files).unwrap();
+
+        let mut json: serde_json::Value = serde_json::to_value(&search).unwrap();
+        json["paths"]["second"] = "delta m4 = 2^31\nQ-4: ........".into();
+        let path = std::env::temp_dir().join(format!("corrupt-{}.json", std::process::id()));
+        fs::write(&path, json.to_string()).unwrap();
+
+        let error = Search::load(&path).unwrap_err();
+        fs::remove_file(path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 2"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_paths_of_the_same_sign() {
+        // the second block has to cancel the first's difference, not double it
+        let files = PathFiles {
+            first: FIRST_PATH.to_string(),
+            second: FIRST_PATH.to_string(),
+        };
+        let error = Search::with_paths(INITIAL_STATE, 1, files).unwrap_err();
+
+        assert!(error.message.contains("leave a difference"), "{}", error);
+    }
+
+    #[test]
+    fn batch_seeds_differ() {
+        let seeds = (0..1000).map(|batch| batch_seed(0, batch));
+        let unique = seeds.collect::<std::collections::HashSet<_>>();
+
+        assert_eq!(unique.len(), 1000);
+        assert_ne!(batch_seed(0, 0), batch_seed(1, 0));
+    }
+}
@@ -0,0 +1,164 @@
+// SHA-1 (FIPS 180-4) as a member of the MD family, reduced to its first
+// `STEPS` steps, for studying differential paths on the round-reduced
+// function with the same tools as MD4 and MD5.
+//
+// In the Q notation of Stevens' thesis the state update is
+//
+//   Q[t + 1] = RL(Q[t], 5) + f(Q[t - 1], RL(Q[t - 2], 30), RL(Q[t - 3], 30))
+//              + RL(Q[t - 4], 30) + K + W[t],
+//
+// with Q[0] = A, Q[-1] = B, Q[-2] = RR(C, 30), Q[-3] = RR(D, 30) and
+// Q[-4] = RR(E, 30). Only W[0..16] are message words; the rest are expanded
+// from them, so message modification is limited to the first 16 steps.
+
+use crate::family::{self, MdFamily};
+
+pub const INITIAL_STATE: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+/// SHA-1 reduced to its first `STEPS` steps, at most 80.
+pub struct Sha1<const STEPS: usize = 80>;
+
+/// W[0..=t]: the message words followed by the expanded ones.
+fn schedule(t: usize, m: &[u32; 16]) -> Vec<u32> {
+    let mut w = m.to_vec();
+
+    for u in 16..=t {
+        w.push((w[u - 3] ^ w[u - 8] ^ w[u - 14] ^ w[u - 16]).rotate_left(1));
+    }
+
+    w
+}
+
+impl<const STEPS: usize> MdFamily for Sha1<STEPS> {
+    const STEPS: usize = STEPS;
+    const REGISTERS: usize = 5;
+
+    fn boolean_function(t: usize) -> fn(u32, u32, u32) -> u32 {
+        match t / 20 {
+            0 => |x, y, z| (x & y) | (!x & z),
+            2 => |x, y, z| (x & y) | (x & z) | (y & z),
+            _ => |x, y, z| x ^ y ^ z,
+        }
+    }
+
+    fn constant(t: usize) -> u32 {
+        [0x5A827999, 0x6ED9EBA1, 0x8F1BBCDC, 0xCA62C1D6][t / 20]
+    }
+
+    /// The rotation of Q[t]; the older values are rotated by 30.
+    fn shift(_: usize) -> u32 {
+        5
+    }
+
+    fn word_index(t: usize) -> Option<usize> {
+        (t < 16).then_some(t)
+    }
+
+    fn word(t: usize, m: &[u32; 16]) -> u32 {
+        match t {
+            0..16 => m[t],
+            _ => schedule(t, m)[t],
+        }
+    }
+
+    fn phi(t: usize, q: &[u32]) -> u32 {
+        Self::boolean_function(t)(q[3], q[2].rotate_left(30), q[1].rotate_left(30))
+    }
+
+    fn step(t: usize, q: &[u32], word: u32) -> u32 {
+        q[4].rotate_left(5)
+            .wrapping_add(Self::phi(t, q))
+            .wrapping_add(q[0].rotate_left(30))
+            .wrapping_add(Self::constant(t))
+            .wrapping_add(word)
+    }
+
+    fn inverse_step(t: usize, q: &[u32], next: u32) -> u32 {
+        next.wrapping_sub(q[4].rotate_left(5))
+            .wrapping_sub(Self::phi(t, q))
+            .wrapping_sub(q[0].rotate_left(30))
+            .wrapping_sub(Self::constant(t))
+    }
+
+    fn initial_values(state: &[u32]) -> Vec<u32> {
+        vec![
+            state[4].rotate_right(30),
+            state[3].rotate_right(30),
+            state[2].rotate_right(30),
+            state[1],
+            state[0],
+        ]
+    }
+
+    fn output(state: &[u32], last: &[u32]) -> Vec<u32> {
+        vec![
+            state[0].wrapping_add(last[4]),
+            state[1].wrapping_add(last[3]),
+            state[2].wrapping_add(last[2].rotate_left(30)),
+            state[3].wrapping_add(last[1].rotate_left(30)),
+            state[4].wrapping_add(last[0].rotate_left(30)),
+        ]
+    }
+}
+
+/// SHA-1 digest of `data`, or of its reduced variant. Unlike the MD
+/// functions SHA-1 reads its words and appends the length big-endian.
+pub fn digest<const STEPS: usize>(data: &[u8]) -> [u8; 20] {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    message.resize(message.len() + (119 - data.len() % 64) % 64, 0);
+    message.extend((data.len() as u64).wrapping_mul(8).to_be_bytes());
+
+    let state = message
+        .chunks_exact(64)
+        .fold(INITIAL_STATE, |state, block| {
+            let mut words = [0; 16];
+            for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+                *word = u32::from_be_bytes(bytes.try_into().unwrap());
+            }
+
+            family::compress::<Sha1<STEPS>>(&state, &words)
+                .try_into()
+                .unwrap()
+        });
+
+    let mut digest = [0; 20];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn fips_180_examples() {
+        assert_eq!(
+            hex(&digest::<80>(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&digest::<80>(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        assert_eq!(
+            hex(&digest::<80>(b"")),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        );
+    }
+
+    #[test]
+    fn reduced_variants_differ() {
+        assert_ne!(digest::<20>(b"abc"), digest::<80>(b"abc"));
+        assert_ne!(digest::<20>(b"abc"), digest::<24>(b"abc"));
+    }
+}
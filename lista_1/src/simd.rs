@@ -0,0 +1,341 @@
+// MD5 compression of several blocks at once, one block per 32-bit lane of
+// an SSE2 (4 lanes) or AVX2 (8 lanes) register. Which one runs is decided
+// at runtime; without either, or for the blocks left over after the last
+// full set of lanes, `md5::compress` does the work.
+//
+// The step loop is written once over the `Lanes` operations and inlined
+// into one function per instruction set, so each copy is compiled with its
+// target feature enabled.
+//
+// It hashes whole blocks: the candidates of a batch and their partners,
+// which `Search::step` and `ChosenPrefix::step` verify through
+// `CandidateBackend::compress_batch`; `CpuBackend` runs the kernel it was
+// configured with. The trials themselves stay scalar: they check the
+// conditions step by step and most are abandoned in round 2, which a lane
+// that always runs all 64 steps cannot do.
+
+use crate::md5::{self, word_index, K, SHIFTS};
+
+/// An implementation of `compress_many`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    Scalar,
+    Sse2,
+    Avx2,
+}
+
+impl Kernel {
+    /// The widest kernel this CPU runs.
+    pub fn detect() -> Kernel {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Kernel::Avx2;
+            }
+            if is_x86_feature_detected!("sse2") {
+                return Kernel::Sse2;
+            }
+        }
+
+        Kernel::Scalar
+    }
+
+    /// Every kernel this CPU runs, the scalar one included.
+    pub fn available() -> Vec<Kernel> {
+        [Kernel::Scalar, Kernel::Sse2, Kernel::Avx2]
+            .into_iter()
+            .filter(|&kernel| kernel.lanes() <= Kernel::detect().lanes())
+            .collect()
+    }
+
+    /// Blocks compressed side by side.
+    pub fn lanes(self) -> usize {
+        match self {
+            Kernel::Scalar => 1,
+            Kernel::Sse2 => 4,
+            Kernel::Avx2 => 8,
+        }
+    }
+
+    /// Compresses `blocks[i]` from `states[i]` for every i, like
+    /// `md5::compress`.
+    pub fn compress_many(self, states: &[[u32; 4]], blocks: &[[u32; 16]]) -> Vec<[u32; 4]> {
+        assert_eq!(states.len(), blocks.len(), "one state per block");
+        assert!(
+            self.lanes() <= Kernel::detect().lanes(),
+            "{:?} is not supported",
+            self
+        );
+
+        let lanes = self.lanes();
+        let full = blocks.len() / lanes * lanes;
+        let mut output = vec![[0; 4]; blocks.len()];
+
+        for ((states, blocks), output) in states[..full]
+            .chunks_exact(lanes)
+            .zip(blocks[..full].chunks_exact(lanes))
+            .zip(output.chunks_exact_mut(lanes))
+        {
+            match self {
+                Kernel::Scalar => output[0] = md5::compress(&states[0], &blocks[0]),
+                // SAFETY: the assertion above checked that the CPU has the
+                // instruction set
+                #[cfg(target_arch = "x86_64")]
+                Kernel::Sse2 => unsafe { x86::compress_sse2(states, blocks, output) },
+                #[cfg(target_arch = "x86_64")]
+                Kernel::Avx2 => unsafe { x86::compress_avx2(states, blocks, output) },
+                #[cfg(not(target_arch = "x86_64"))]
+                _ => unreachable!(),
+            }
+        }
+
+        for idx in full..blocks.len() {
+            output[idx] = md5::compress(&states[idx], &blocks[idx]);
+        }
+
+        output
+    }
+}
+
+/// Compresses `blocks[i]` from `states[i]` for every i with the widest
+/// kernel this CPU runs.
+pub fn compress_many(states: &[[u32; 4]], blocks: &[[u32; 16]]) -> Vec<[u32; 4]> {
+    Kernel::detect().compress_many(states, blocks)
+}
+
+/// One 32-bit value per lane.
+trait Lanes: Copy {
+    const LANES: usize;
+
+    unsafe fn splat(value: u32) -> Self;
+    /// Lane i is `values[i]`.
+    unsafe fn load(values: &[u32]) -> Self;
+    unsafe fn store(self, values: &mut [u32]);
+
+    unsafe fn add(self, other: Self) -> Self;
+    unsafe fn and(self, other: Self) -> Self;
+    /// `!self & other`
+    unsafe fn andnot(self, other: Self) -> Self;
+    unsafe fn or(self, other: Self) -> Self;
+    unsafe fn xor(self, other: Self) -> Self;
+    unsafe fn rotate_left(self, s: u32) -> Self;
+}
+
+/// `md5::compress` on every lane, with `states` and `blocks` holding
+/// `V::LANES` entries.
+#[inline(always)]
+unsafe fn compress_lanes<V: Lanes>(
+    states: &[[u32; 4]],
+    blocks: &[[u32; 16]],
+    output: &mut [[u32; 4]],
+) {
+    let transpose = |column: &dyn Fn(usize) -> u32| {
+        let mut values = [0; 8];
+        for (lane, value) in values.iter_mut().take(V::LANES).enumerate() {
+            *value = column(lane);
+        }
+        V::load(&values)
+    };
+
+    let state: [V; 4] = std::array::from_fn(|r| transpose(&|lane| states[lane][r]));
+    let words: [V; 16] = std::array::from_fn(|w| transpose(&|lane| blocks[lane][w]));
+    let [mut a, mut b, mut c, mut d] = state;
+
+    for t in 0..64 {
+        let f = match t / 16 {
+            0 => b.and(c).or(b.andnot(d)),
+            1 => b.and(d).or(d.andnot(c)),
+            2 => b.xor(c).xor(d),
+            _ => c.xor(b.or(d.xor(V::splat(u32::MAX)))),
+        };
+
+        let sum = a.add(f).add(words[word_index(t)]).add(V::splat(K[t]));
+        (a, b, c, d) = (d, b.add(sum.rotate_left(SHIFTS[t])), b, c);
+    }
+
+    for (r, value) in [a, b, c, d].into_iter().enumerate() {
+        let mut values = [0; 8];
+        value.add(state[r]).store(&mut values);
+
+        for (lane, output) in output.iter_mut().enumerate() {
+            output[r] = values[lane];
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::{compress_lanes, Lanes};
+
+    impl Lanes for __m128i {
+        const LANES: usize = 4;
+
+        #[inline(always)]
+        unsafe fn splat(value: u32) -> Self {
+            _mm_set1_epi32(value as i32)
+        }
+
+        #[inline(always)]
+        unsafe fn load(values: &[u32]) -> Self {
+            _mm_loadu_si128(values.as_ptr() as *const __m128i)
+        }
+
+        #[inline(always)]
+        unsafe fn store(self, values: &mut [u32]) {
+            _mm_storeu_si128(values.as_mut_ptr() as *mut __m128i, self)
+        }
+
+        #[inline(always)]
+        unsafe fn add(self, other: Self) -> Self {
+            _mm_add_epi32(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn and(self, other: Self) -> Self {
+            _mm_and_si128(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn andnot(self, other: Self) -> Self {
+            _mm_andnot_si128(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn or(self, other: Self) -> Self {
+            _mm_or_si128(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn xor(self, other: Self) -> Self {
+            _mm_xor_si128(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn rotate_left(self, s: u32) -> Self {
+            let (left, right) = (
+                _mm_cvtsi32_si128(s as i32),
+                _mm_cvtsi32_si128(32 - s as i32),
+            );
+            _mm_or_si128(_mm_sll_epi32(self, left), _mm_srl_epi32(self, right))
+        }
+    }
+
+    impl Lanes for __m256i {
+        const LANES: usize = 8;
+
+        #[inline(always)]
+        unsafe fn splat(value: u32) -> Self {
+            _mm256_set1_epi32(value as i32)
+        }
+
+        #[inline(always)]
+        unsafe fn load(values: &[u32]) -> Self {
+            _mm256_loadu_si256(values.as_ptr() as *const __m256i)
+        }
+
+        #[inline(always)]
+        unsafe fn store(self, values: &mut [u32]) {
+            _mm256_storeu_si256(values.as_mut_ptr() as *mut __m256i, self)
+        }
+
+        #[inline(always)]
+        unsafe fn add(self, other: Self) -> Self {
+            _mm256_add_epi32(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn and(self, other: Self) -> Self {
+            _mm256_and_si256(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn andnot(self, other: Self) -> Self {
+            _mm256_andnot_si256(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn or(self, other: Self) -> Self {
+            _mm256_or_si256(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn xor(self, other: Self) -> Self {
+            _mm256_xor_si256(self, other)
+        }
+
+        #[inline(always)]
+        unsafe fn rotate_left(self, s: u32) -> Self {
+            let (left, right) = (
+                _mm_cvtsi32_si128(s as i32),
+                _mm_cvtsi32_si128(32 - s as i32),
+            );
+            _mm256_or_si256(_mm256_sll_epi32(self, left), _mm256_srl_epi32(self, right))
+        }
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn compress_sse2(
+        states: &[[u32; 4]],
+        blocks: &[[u32; 16]],
+        output: &mut [[u32; 4]],
+    ) {
+        compress_lanes::<__m128i>(states, blocks, output)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn compress_avx2(
+        states: &[[u32; 4]],
+        blocks: &[[u32; 16]],
+        output: &mut [[u32; 4]],
+    ) {
+        compress_lanes::<__m256i>(states, blocks, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::md5::INITIAL_STATE;
+
+    #[test]
+    fn kernels_match_the_scalar_compression() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // counts that leave every possible tail after full sets of lanes
+        for count in [0, 1, 3, 4, 7, 8, 9, 17, 100] {
+            let states = (0..count).map(|_| rng.gen()).collect::<Vec<[u32; 4]>>();
+            let blocks = (0..count).map(|_| rng.gen()).collect::<Vec<[u32; 16]>>();
+            let expected = states
+                .iter()
+                .zip(&blocks)
+                .map(|(state, block)| md5::compress(state, block))
+                .collect::<Vec<_>>();
+
+            for kernel in Kernel::available() {
+                assert_eq!(
+                    kernel.compress_many(&states, &blocks),
+                    expected,
+                    "{:?} {}",
+                    kernel,
+                    count
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hashes_the_empty_message() {
+        let mut block = [0; 16];
+        block[0] = 0x80;
+
+        let output = compress_many(&[INITIAL_STATE; 11], &[block; 11]);
+        assert!(output
+            .iter()
+            .all(|&state| state == [0xd98c1dd4, 0x04b2008f, 0x980980e9, 0x7e42f8ec]));
+    }
+}
@@ -0,0 +1,282 @@
+// What a running search reports about itself: throughput, how many of the
+// trials that got past round 1 survive each later step, and the time to a
+// collision those numbers predict. Reports are drawn as a status line on
+// stderr and can be logged one JSON object per line, to compare strategies
+// and settings afterwards.
+//
+// Round 1 conditions are met by message modification, so a trial is counted
+// once it has reached b4 (Q[16]); its depth is the last step up to which all
+// conditions held. Steps too deep for enough trials to have reached them are
+// extrapolated with one half per condition, which is what a sufficient
+// condition costs on average.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::conditions::table::{ConditionTable, Variable};
+
+/// The step round 1 message modification gets every trial to.
+pub const ROUND_1: usize = 16;
+
+/// Steps of MD5.
+const STEPS: usize = 64;
+
+/// Trials a step needs to have been reached by before its pass rate counts
+/// as measured.
+const MIN_SAMPLES: u64 = 16;
+
+/// How deep trials got: `depths[t]` counts the trials whose values met every
+/// condition up to Q[t] but not Q[t + 1]. `depths[64]` counts candidates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Survival {
+    pub depths: Vec<u64>,
+}
+
+impl Default for Survival {
+    fn default() -> Self {
+        Survival {
+            depths: vec![0; STEPS + 1],
+        }
+    }
+}
+
+impl Survival {
+    pub fn record(&mut self, depth: usize) {
+        self.depths[depth] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Survival) {
+        for (depth, count) in self.depths.iter_mut().zip(&other.depths) {
+            *depth += count;
+        }
+    }
+
+    /// Trials that met every condition up to Q[t].
+    pub fn reached(&self, t: usize) -> u64 {
+        self.depths[t..].iter().sum()
+    }
+
+    /// Trials past round 1.
+    pub fn trials(&self) -> u64 {
+        self.reached(ROUND_1)
+    }
+
+    /// Fraction of the trials past round 1 that reached Q[t], for every t
+    /// after b4; empty before the first trial.
+    pub fn fractions(&self) -> Vec<f64> {
+        if self.trials() == 0 {
+            return Vec::new();
+        }
+
+        (ROUND_1 + 1..=STEPS)
+            .map(|t| self.reached(t) as f64 / self.trials() as f64)
+            .collect()
+    }
+
+    /// Probability that a trial past round 1 becomes a candidate: measured
+    /// up to the deepest step enough trials reached, and extrapolated from
+    /// the conditions `table` puts on the steps after it.
+    pub fn candidate_probability(&self, table: &ConditionTable) -> Option<f64> {
+        let measured = (ROUND_1..=STEPS)
+            .rev()
+            .find(|&t| self.reached(t) >= MIN_SAMPLES)?;
+
+        let remaining = table
+            .conditions
+            .iter()
+            .filter(|condition| matches!(condition.variable, Variable::Q(t) if t > measured as i32))
+            .count();
+
+        let fraction = self.reached(measured) as f64 / self.trials() as f64;
+        Some(fraction * 0.5f64.powi(remaining as i32))
+    }
+}
+
+/// A snapshot of a search after one batch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    /// 1 while searching for the first block, 2 for the second.
+    pub block: usize,
+    pub batches: u64,
+    /// Seconds spent in the search, over all runs.
+    pub elapsed: f64,
+    pub candidates: u64,
+    pub candidates_per_second: f64,
+    /// Trials past round 1 while searching for this block.
+    pub trials: u64,
+    /// Trials past round 1 per second, in the last batch.
+    pub trials_per_second: f64,
+    /// Fraction of the trials past round 1 that met every condition up to
+    /// a5, d5, ..., b16; see `Survival::fractions`.
+    pub survival: Vec<f64>,
+    /// Seconds the current block is expected to take from now on, at the
+    /// last batch's speed; `None` until enough trials were made.
+    pub expected: Option<f64>,
+    pub found: bool,
+}
+
+impl Report {
+    /// A status line for the terminal.
+    pub fn line(&self) -> String {
+        let mut line = format!(
+            "[{:.1}s] block {}, batches: {}, candidates: {} ({:.1}/s), trials: {} ({:.0}/s)",
+            self.elapsed,
+            self.block,
+            self.batches,
+            self.candidates,
+            self.candidates_per_second,
+            self.trials,
+            self.trials_per_second,
+        );
+
+        // the step the fewest trials survived into, as a sense of depth
+        if let Some(deepest) = self.survival.iter().rposition(|&fraction| fraction > 0.0) {
+            line += &format!(", deepest: Q{}", ROUND_1 + 1 + deepest);
+        }
+        if let Some(expected) = self.expected {
+            line += &format!(", expected: {}", duration(expected));
+        }
+
+        line
+    }
+}
+
+/// `seconds` in the largest unit that keeps it above one.
+fn duration(seconds: f64) -> String {
+    match seconds {
+        s if s < 60.0 => format!("{:.0}s", s),
+        s if s < 3600.0 => format!("{:.1}min", s / 60.0),
+        s if s < 86400.0 => format!("{:.1}h", s / 3600.0),
+        s => format!("{:.1}d", s / 86400.0),
+    }
+}
+
+/// Where reports go: a status line on stderr, redrawn with every report,
+/// and optionally a log with a JSON object per report.
+pub struct Telemetry {
+    live: bool,
+    log: Option<BufWriter<File>>,
+}
+
+impl Telemetry {
+    /// Reports nowhere.
+    pub fn silent() -> Telemetry {
+        Telemetry {
+            live: false,
+            log: None,
+        }
+    }
+
+    /// Draws the status line if `live`, and appends to `log` if given.
+    pub fn new(live: bool, log: Option<&Path>) -> io::Result<Telemetry> {
+        let log = match log {
+            Some(path) => Some(BufWriter::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            None => None,
+        };
+
+        Ok(Telemetry { live, log })
+    }
+
+    pub fn record(&mut self, report: &Report) -> io::Result<()> {
+        if self.live {
+            // pad over the rest of a longer previous line
+            eprint!("\r{:<120}", report.line());
+            if report.found {
+                eprintln!();
+            }
+        }
+
+        if let Some(log) = &mut self.log {
+            serde_json::to_writer(&mut *log, report)?;
+            writeln!(log)?;
+            log.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conditions::second_block;
+
+    #[test]
+    fn survival_counts_trials_by_depth() {
+        let mut survival = Survival::default();
+        for depth in [0, 16, 16, 20, 64] {
+            survival.record(depth);
+        }
+
+        assert_eq!(survival.trials(), 4);
+        assert_eq!(survival.reached(17), 2);
+
+        let fractions = survival.fractions();
+        assert_eq!(fractions.len(), 48);
+        assert_eq!(fractions[0], 0.5);
+        assert_eq!(fractions[20 - 17], 0.5);
+        assert_eq!(fractions[21 - 17], 0.25);
+
+        let mut twice = survival.clone();
+        twice.merge(&survival);
+        assert_eq!(twice.reached(64), 2);
+    }
+
+    #[test]
+    fn extrapolates_unmeasured_steps() {
+        let table = second_block::table();
+        let mut survival = Survival::default();
+        assert_eq!(survival.candidate_probability(&table), None);
+
+        // every trial fails at a5
+        survival.depths[ROUND_1] = 1000;
+        let all = table
+            .conditions
+            .iter()
+            .filter(|condition| matches!(condition.variable, Variable::Q(t) if t > 16))
+            .count();
+        assert_eq!(
+            survival.candidate_probability(&table),
+            Some(0.5f64.powi(all as i32))
+        );
+
+        // once candidates are plenty the rate is measured outright
+        survival.depths[STEPS] = 1000;
+        assert_eq!(survival.candidate_probability(&table), Some(0.5));
+    }
+
+    #[test]
+    fn logs_one_json_object_per_report() {
+        let path = std::env::temp_dir().join(format!("telemetry-{}.jsonl", std::process::id()));
+        let report = Report {
+            block: 2,
+            batches: 3,
+            elapsed: 1.5,
+            candidates: 4,
+            candidates_per_second: 2.0,
+            trials: 100,
+            trials_per_second: 50.0,
+            survival: vec![0.5; 48],
+            expected: Some(90.0),
+            found: false,
+        };
+        assert!(report.line().contains("expected: 1.5min"));
+
+        let mut telemetry = Telemetry::new(false, Some(&path)).unwrap();
+        telemetry.record(&report).unwrap();
+        telemetry.record(&report).unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(log.lines().count(), 2);
+
+        let line: serde_json::Value = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(line["block"], 2);
+        assert_eq!(line["expected"], 90.0);
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::conditions::table::{ConditionTable, Variable};
+use crate::family::{Block, MdFamily};
+use crate::md5::Md5;
+
+/// Difference of one working state value Q[t] between the two messages.
+#[derive(Debug, Clone, Serialize)]
+pub struct Step {
+    /// Step that computed the value; -3..=0 is the chaining value.
+    pub t: i32,
+    /// Name of the value in Wang's notation (`a1`, `d1`, ...).
+    pub name: String,
+    pub value: u32,
+    pub value_prim: u32,
+    /// `value ^ value_prim`.
+    pub xor: u32,
+    /// `value_prim - value` modulo 2^32.
+    pub modular: u32,
+    /// Signed bitwise difference in Wang's notation: `+i` if bit i
+    /// (1-based) flips from 0 to 1, `-i` if it flips from 1 to 0.
+    pub signed: Vec<i32>,
+}
+
+/// The first sufficient condition of the path that does not hold.
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenCondition {
+    /// Step that computed the offending value.
+    pub t: i32,
+    pub name: String,
+    /// The bits (1-based) whose conditions do not hold.
+    pub bits: Vec<u32>,
+}
+
+/// The differential behaviour of a message pair through one compression.
+#[derive(Debug, Clone, Serialize)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+    /// Checked on the first message of the pair, `None` if all hold.
+    pub first_broken: Option<BrokenCondition>,
+    pub output: Vec<u32>,
+    pub output_prim: Vec<u32>,
+}
+
+impl Trace {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "  t  Q    xor        modular    signed")?;
+
+        for step in &self.steps {
+            let signed = step
+                .signed
+                .iter()
+                .map(|bit| format!("{:+}", bit))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            writeln!(
+                f,
+                "{:>3}  {:<4} {:#010x} {:#010x} {}",
+                step.t, step.name, step.xor, step.modular, signed
+            )?;
+        }
+
+        match &self.first_broken {
+            Some(broken) => write!(
+                f,
+                "first broken condition: {} (step {}), bits {:?}",
+                broken.name, broken.t, broken.bits
+            ),
+            None => write!(f, "all conditions hold"),
+        }
+    }
+}
+
+/// Working state of one compression, with the boolean function outputs
+/// computed on demand.
+struct Run<F> {
+    block: Block<F>,
+    output: Vec<u32>,
+}
+
+impl<F: MdFamily> Run<F> {
+    fn new(state: &[u32], m: &[u32; 16]) -> Run<F> {
+        let mut block = Block::new(state, m);
+        for t in 1..=F::STEPS {
+            block.compute(t);
+        }
+        let output = F::output(state, &block.values(F::STEPS)[F::STEPS..]);
+
+        Run { block, output }
+    }
+
+    fn q(&self, t: i32) -> u32 {
+        self.block.get(Variable::Q(t))
+    }
+}
+
+/// Traces the pair `(state, m)`, `(state_prim, m_prim)` through one MD5
+/// compression and checks the conditions of `table` on the first message.
+pub fn trace(
+    state: &[u32; 4],
+    m: &[u32; 16],
+    state_prim: &[u32; 4],
+    m_prim: &[u32; 16],
+    table: &ConditionTable,
+) -> Trace {
+    trace_with::<Md5>(state, m, state_prim, m_prim, table)
+}
+
+/// `trace` for any member of the MD family.
+pub fn trace_with<F: MdFamily>(
+    state: &[u32],
+    m: &[u32; 16],
+    state_prim: &[u32],
+    m_prim: &[u32; 16],
+    table: &ConditionTable,
+) -> Trace {
+    let run = Run::<F>::new(state, m);
+    let run_prim = Run::<F>::new(state_prim, m_prim);
+
+    let steps = (1 - F::REGISTERS as i32..=F::STEPS as i32)
+        .map(|t| {
+            let (value, value_prim) = (run.q(t), run_prim.q(t));
+            let xor = value ^ value_prim;
+
+            Step {
+                t,
+                name: Variable::Q(t).name(),
+                value,
+                value_prim,
+                xor,
+                modular: value_prim.wrapping_sub(value),
+                signed: (0..32)
+                    .filter(|bit| xor >> bit & 1 == 1)
+                    .map(|bit| {
+                        let bit = bit + 1;
+                        if value_prim >> (bit - 1) & 1 == 1 {
+                            bit
+                        } else {
+                            -bit
+                        }
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Trace {
+        steps,
+        first_broken: first_broken(&run, table),
+        output: run.output,
+        output_prim: run_prim.output,
+    }
+}
+
+fn first_broken<F: MdFamily>(run: &Run<F>, table: &ConditionTable) -> Option<BrokenCondition> {
+    // φ of step t (0-based) is computed together with Q[t + 1]
+    let variables = (1 - F::REGISTERS as i32..=F::STEPS as i32).flat_map(|t| {
+        let phi = (t >= 1).then(|| Variable::Phi((t - 1) as u32));
+        phi.into_iter().chain([Variable::Q(t)])
+    });
+
+    variables.into_iter().find_map(|variable| {
+        let broken = table
+            .masks(variable)
+            .broken_bits(run.block.get(variable), |other| run.block.get(other));
+
+        (broken != 0).then(|| BrokenCondition {
+            t: match variable {
+                Variable::Q(t) => t,
+                Variable::Phi(t) => t as i32 + 1,
+            },
+            name: variable.name(),
+            bits: (0..32)
+                .filter(|bit| broken >> bit & 1 == 1)
+                .map(|bit| bit + 1)
+                .collect(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conditions::{first_block, md4, second_block};
+    use crate::md4::Md4;
+    use crate::md5::{compress, INITIAL_STATE};
+    use crate::published::wang;
+
+    #[test]
+    fn published_blocks_follow_the_paths() {
+        let first = trace(
+            &INITIAL_STATE,
+            &wang().m0,
+            &INITIAL_STATE,
+            &wang().m0_prim,
+            &first_block::table(),
+        );
+        assert!(first.first_broken.is_none());
+        assert_eq!(first.output, compress(&INITIAL_STATE, &wang().m0));
+        assert_eq!(first.steps[4].name, "a1");
+        // a2 is the first value with a difference, -2^6
+        assert_eq!(first.steps[8].modular, 0xffffffc0);
+
+        let second = trace_with::<Md5>(
+            &first.output,
+            &wang().m1,
+            &first.output_prim,
+            &wang().m1_prim,
+            &second_block::table(),
+        );
+        // M1 breaks c16,26 = 1 and collides all the same: the conditions
+        // are sufficient, not necessary
+        let broken = second.first_broken.unwrap();
+        assert_eq!((broken.name.as_str(), broken.bits), ("c16", vec![26]));
+        assert_eq!(second.output, second.output_prim);
+    }
+
+    #[test]
+    fn traces_md4() {
+        let collision = crate::md4::find_collision(1);
+        let trace = trace_with::<Md4>(
+            &INITIAL_STATE,
+            &collision.m,
+            &INITIAL_STATE,
+            &collision.m_prim,
+            &md4::table(),
+        );
+
+        assert!(trace.first_broken.is_none());
+        assert_eq!(trace.output, trace.output_prim);
+        assert_eq!(trace.steps.len(), 52);
+        // the last difference, in bit 32 of b9 and a10, cancels in round 3
+        let last = trace.steps.iter().rfind(|step| step.xor != 0).unwrap();
+        assert_eq!((last.name.as_str(), last.xor), ("a10", 0x80000000));
+    }
+
+    #[test]
+    fn signed_differences_add_up_to_modular() {
+        let trace = trace(
+            &INITIAL_STATE,
+            &wang().m0,
+            &INITIAL_STATE,
+            &wang().m0_prim,
+            &first_block::table(),
+        );
+
+        for step in trace.steps {
+            let sum = step.signed.iter().fold(0u32, |sum, bit| {
+                let power = 1u32 << (bit.unsigned_abs() - 1);
+                if *bit > 0 {
+                    sum.wrapping_add(power)
+                } else {
+                    sum.wrapping_sub(power)
+                }
+            });
+            assert_eq!(sum, step.modular, "{}", step.name);
+        }
+    }
+
+    #[test]
+    fn reports_the_first_broken_condition() {
+        let state = compress(&INITIAL_STATE, &wang().m0);
+        let state_prim = compress(&INITIAL_STATE, &wang().m0_prim);
+
+        // adds 2^5 to a1 after the rotation by 7, breaking a1,6 = 0
+        let mut m1 = wang().m1;
+        let mut m1_prim = wang().m1_prim;
+        m1[0] = m1[0].wrapping_add(1 << 30);
+        m1_prim[0] = m1[0];
+
+        let trace = trace(&state, &m1, &state_prim, &m1_prim, &second_block::table());
+        let broken = trace.first_broken.as_ref().unwrap();
+        assert_eq!((broken.t, broken.name.as_str()), (1, "a1"));
+        assert_eq!(broken.bits, [6]);
+
+        let json: serde_json::Value = serde_json::from_str(&trace.to_json()).unwrap();
+        assert_eq!(json["steps"].as_array().unwrap().len(), 68);
+        assert_eq!(json["first_broken"]["name"], "a1");
+    }
+}
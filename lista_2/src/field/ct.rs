@@ -0,0 +1,296 @@
+// Constant-time selection, after the `subtle` crate: a secret bit is a
+// `Choice`, and values are picked or swapped by it with masks over a fixed
+// number of limbs rather than with branches. The Montgomery ladder is built
+// on it: every exponent bit costs one product and one square, whatever its
+// value, and the bit only decides which of the two running values is which.
+
+use num::BigUint;
+
+use super::uint::Uint;
+
+/// A secret bit, 0 or 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Choice(u64);
+
+impl Choice {
+    pub fn from_bit(bit: u64) -> Self {
+        debug_assert!(bit <= 1, "a choice is 0 or 1");
+        Choice(bit)
+    }
+
+    pub fn unwrap_u64(self) -> u64 {
+        self.0
+    }
+
+    /// All ones for 1, zero for 0.
+    fn mask(self) -> u64 {
+        mask(self.0)
+    }
+}
+
+/// All ones if `bit` is 1, zero if it is 0. The optimizer cannot see
+/// through it, so it cannot turn the masked operations back into branches.
+pub fn mask(bit: u64) -> u64 {
+    std::hint::black_box(bit).wrapping_neg()
+}
+
+pub trait ConditionallySelectable: Sized {
+    /// `b` if `choice` is 1, `a` if it is 0.
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self;
+
+    /// Swaps `a` and `b` if `choice` is 1.
+    fn conditional_swap(a: &mut Self, b: &mut Self, choice: Choice) {
+        let new_a = Self::conditional_select(a, b, choice);
+        *b = Self::conditional_select(b, a, choice);
+        *a = new_a;
+    }
+}
+
+impl ConditionallySelectable for u64 {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        a ^ (choice.mask() & (a ^ b))
+    }
+}
+
+/// Limbs of the same length, as in `Montgomery`.
+impl ConditionallySelectable for Vec<u64> {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        assert_eq!(a.len(), b.len(), "selecting between different lengths");
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| u64::conditional_select(a, b, choice))
+            .collect()
+    }
+}
+
+impl<const N: usize> ConditionallySelectable for Uint<N> {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Uint::select(a, b, choice.unwrap_u64())
+    }
+}
+
+/// `a` or `b`, both less than 2^(64 `limbs`), selected limb by limb.
+pub fn select_biguint(a: &BigUint, b: &BigUint, limbs: usize, choice: Choice) -> BigUint {
+    let padded = |value: &BigUint| {
+        let mut digits = value.to_u64_digits();
+        digits.resize(limbs, 0);
+        digits
+    };
+
+    let limbs = Vec::conditional_select(&padded(a), &padded(b), choice);
+    BigUint::new(
+        limbs
+            .iter()
+            .flat_map(|&limb| [limb as u32, (limb >> 32) as u32])
+            .collect(),
+    )
+}
+
+/// x^exp by the Montgomery ladder over `bits` bits, starting from `one`.
+/// `exp` must be below 2^`bits`; the bits are read from a copy padded to
+/// that length, so its actual length does not show.
+pub fn ladder<T: ConditionallySelectable + Clone>(
+    x: &T,
+    one: T,
+    exp: &BigUint,
+    bits: u64,
+    mul: impl Fn(&T, &T) -> T,
+    square: impl Fn(&T) -> T,
+) -> T {
+    assert!(
+        exp.bits() <= bits,
+        "the exponent has more than {} bits",
+        bits
+    );
+    let mut limbs = exp.to_u64_digits();
+    limbs.resize(bits.div_ceil(64) as usize, 0);
+
+    // r1 = r0 * x throughout
+    let (mut r0, mut r1) = (one, x.clone());
+    for i in (0..bits as usize).rev() {
+        let choice = Choice::from_bit((limbs[i / 64] >> (i % 64)) & 1);
+
+        T::conditional_swap(&mut r0, &mut r1, choice);
+        r1 = mul(&r0, &r1);
+        r0 = square(&r0);
+        T::conditional_swap(&mut r0, &mut r1, choice);
+    }
+    r0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::f2_poly::F2PolynomialElement;
+    use crate::field::fp::FpElement;
+    use crate::field::fp_ct::Fp256Element;
+    use crate::field::fp_poly::FpPolynomialElement;
+    use crate::leakage::{measure, Report};
+    use crate::{FieldContext, FieldElement};
+    use num::bigint::RandBigInt;
+    use num::{BigInt, One};
+    use std::hint::black_box;
+
+    #[test]
+    fn test_select_and_swap() {
+        let (mut a, mut b) = (vec![1u64, 2], vec![3u64, 4]);
+        Vec::conditional_swap(&mut a, &mut b, Choice::from_bit(0));
+        assert_eq!((&a, &b), (&vec![1, 2], &vec![3, 4]));
+        Vec::conditional_swap(&mut a, &mut b, Choice::from_bit(1));
+        assert_eq!((&a, &b), (&vec![3, 4], &vec![1, 2]));
+
+        let big = BigUint::one() << 100u32;
+        let small = BigUint::from(7u32);
+        assert_eq!(select_biguint(&big, &small, 2, Choice::from_bit(1)), small);
+        assert_eq!(select_biguint(&big, &small, 2, Choice::from_bit(0)), big);
+    }
+
+    #[test]
+    fn test_ladder_matches_pow() {
+        let mul = |a: &u64, b: &u64| a * b % 1009;
+        let square = |a: &u64| a * a % 1009;
+
+        for exp in [0u32, 1, 2, 5, 255, 1000] {
+            let expected = (0..exp).fold(1, |power, _| power * 3 % 1009);
+            let exp = BigUint::from(exp);
+            assert_eq!(ladder(&3u64, 1, &exp, 10, mul, square), expected);
+        }
+    }
+
+    /// The leakage test on a fixed exponent against random ones of the
+    /// same length. Its verdicts depend on how busy the machine is, so the
+    /// tests that use it are ignored by default; the `security` subcommand
+    /// runs the same test on `pow_secure`.
+    fn exponent_leakage(samples: usize, pow: impl Fn(&BigUint)) -> Report {
+        let mut rng = rand::thread_rng();
+        let fixed = BigUint::one() << 255u32;
+        measure(
+            "pow",
+            samples,
+            &mut rng,
+            fixed,
+            |rng| rng.gen_biguint(256),
+            pow,
+        )
+    }
+
+    #[test]
+    #[ignore = "times the wall clock; run in a release build with --ignored"]
+    fn test_ladder_does_not_leak_the_exponent() {
+        let p: BigInt = (BigInt::one() << 255) - 19;
+        let ctx = FieldContext::new_prime(p).unwrap().with_montgomery();
+        let x = Fp256Element::new(&ctx, 5.into());
+
+        // square-and-multiply multiplies once for the fixed exponent and
+        // about 128 times for random ones: the test must see that
+        let report = exponent_leakage(500, |exp| {
+            black_box(x.pow(exp));
+        });
+        assert!(report.leaks(), "{}", report);
+
+        let report = exponent_leakage(2000, |exp| {
+            black_box(x.pow_ct(exp, 256));
+        });
+        assert!(!report.leaks(), "{}", report);
+
+        // and FpElement's ladder on Montgomery limbs
+        let y = FpElement::new(&ctx, 5.into());
+        let report = exponent_leakage(2000, |exp| {
+            black_box(y.pow_ct(exp, 256));
+        });
+        assert!(!report.leaks(), "{}", report);
+
+        // and on the limbs it makes for a context without Montgomery form
+        let ctx = FieldContext::new_prime(ctx.p.clone()).unwrap();
+        let y = FpElement::new(&ctx, 5.into());
+        let report = exponent_leakage(2000, |exp| {
+            black_box(y.pow_ct(exp, 256));
+        });
+        assert!(!report.leaks(), "{}", report);
+    }
+
+    #[test]
+    #[ignore = "times the wall clock; run in a release build with --ignored"]
+    fn test_fp_poly_ladder_does_not_leak() {
+        let poly = [3, 1, 1].map(BigInt::from).to_vec();
+        let ctx = FieldContext::new_poly(BigInt::from(17), poly).unwrap();
+        let x = FpPolynomialElement::from_vec(&ctx, vec![2, 5]);
+
+        let report = exponent_leakage(2000, |exp| {
+            black_box(x.pow_ct(exp, 256));
+        });
+        assert!(!report.leaks(), "{}", report);
+    }
+
+    #[test]
+    #[ignore = "times the wall clock; run in a release build with --ignored"]
+    fn test_f2_poly_ladder_does_not_leak() {
+        let ctx = FieldContext::new_binary(BigUint::from(0b10011u32)).unwrap();
+        let x = F2PolynomialElement::new(&ctx, BigUint::from(0b110u32));
+
+        let report = exponent_leakage(2000, |exp| {
+            black_box(x.pow_ct(exp, 256));
+        });
+        assert!(!report.leaks(), "{}", report);
+    }
+
+    #[test]
+    fn test_pow_ct_matches_pow() {
+        let exps = [0u32, 1, 2, 1000, 65535].map(BigUint::from);
+
+        let ctx = FieldContext::new_prime(BigInt::from(1009)).unwrap();
+        let montgomery_ctx = ctx.clone().with_montgomery();
+        for ctx in [&ctx, &montgomery_ctx] {
+            let x = FpElement::new(ctx, 3.into());
+            for exp in &exps {
+                assert_eq!(x.pow_ct(exp, 16), x.pow(exp));
+            }
+        }
+
+        let poly = [3, 1, 1].map(BigInt::from).to_vec();
+        let ctx = FieldContext::new_poly(BigInt::from(17), poly).unwrap();
+        let x = FpPolynomialElement::from_vec(&ctx, vec![2, 5]);
+        for exp in &exps {
+            assert_eq!(x.pow_ct(exp, 16), x.pow(exp));
+        }
+
+        let ctx = FieldContext::new_binary(BigUint::from(0b10011u32)).unwrap();
+        let x = F2PolynomialElement::new(&ctx, BigUint::from(0b110u32));
+        for exp in &exps {
+            assert_eq!(x.pow_ct(exp, 16), x.pow(exp));
+        }
+    }
+
+    #[test]
+    fn test_pow_ct_matches_pow_in_larger_fields() {
+        let mut rng = rand::thread_rng();
+        let exps = (0..8).map(|_| rng.gen_biguint(200)).collect::<Vec<_>>();
+
+        // degree 7 over F_11, and over F_2, which has no Montgomery form
+        let poly = [1, 0, 5, 3, 1, 4, 4, 1].map(BigInt::from).to_vec();
+        let ctx = FieldContext::new_poly(BigInt::from(11), poly).unwrap();
+        let x = FpPolynomialElement::from_vec(&ctx, vec![8, 6, 7, 7, 3, 9, 1]);
+        let poly = [1, 1, 1].map(BigInt::from).to_vec();
+        let ctx_2 = FieldContext::new_poly(BigInt::from(2), poly).unwrap();
+        let y = FpPolynomialElement::from_vec(&ctx_2, vec![0, 1]);
+        for exp in &exps {
+            assert_eq!(x.pow_ct(exp, 200), x.pow(exp));
+            assert_eq!(y.pow_ct(exp, 200), y.pow(exp));
+        }
+
+        // x^163 + x^7 + x^6 + x^3 + 1, over three limbs
+        let modulus = (BigUint::one() << 163u32) + 0b11001001u32;
+        let ctx = FieldContext::new_binary(modulus).unwrap();
+        let x = F2PolynomialElement::new(&ctx, rng.gen_biguint(163));
+        for exp in &exps {
+            assert_eq!(x.pow_ct(exp, 200), x.pow(exp));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "more than 8 bits")]
+    fn test_pow_ct_rejects_long_exponents() {
+        let ctx = FieldContext::new_prime(BigInt::from(1009)).unwrap();
+        FpElement::new(&ctx, 3.into()).pow_ct(&BigUint::from(256u32), 8);
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return, clippy::let_and_return)]
+
 use std::{
     fmt::Display,
     ops::{Add, Div, Mul, Neg, Sub},
@@ -5,7 +7,8 @@ use std::{
 
 use num::{BigUint, One, Zero};
 
-use crate::{get_binary_poly_degree, FieldContext, FieldElement};
+use super::ct::{ladder, mask, select_biguint, Choice, ConditionallySelectable};
+use crate::{get_binary_poly_degree, FieldContext, FieldElement, FieldError};
 
 /// Represents a polynomial over a finite field F2.
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -14,7 +17,21 @@ pub struct F2PolynomialElement<'a> {
     pub coeffs: BigUint,
 }
 
+impl ConditionallySelectable for F2PolynomialElement<'_> {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        let limbs = a.context.irreducible_binary_poly.bits().div_ceil(64) as usize;
+        F2PolynomialElement {
+            context: a.context,
+            coeffs: select_biguint(&a.coeffs, &b.coeffs, limbs, choice),
+        }
+    }
+}
+
 impl<'a> FieldElement<'a> for F2PolynomialElement<'a> {
+    fn context(&self) -> &'a FieldContext {
+        self.context
+    }
+
     fn zero(ctx: &'a FieldContext) -> Self {
         return F2PolynomialElement {
             context: ctx,
@@ -33,14 +50,14 @@ impl<'a> FieldElement<'a> for F2PolynomialElement<'a> {
         return self.coeffs.is_zero();
     }
 
-    fn inverse(&self) -> Self {
+    fn checked_inverse(&self) -> Result<Self, FieldError> {
         let ctx = self.context;
-        let inv_poly = Self::poly_inv(ctx, &self.coeffs).unwrap();
+        let inv_poly = Self::poly_inv(ctx, &self.coeffs)?;
         let res = F2PolynomialElement {
             context: ctx,
             coeffs: Self::poly_mod(ctx, &inv_poly),
         };
-        res
+        Ok(res)
     }
 
     fn pow(&self, exp: &BigUint) -> Self {
@@ -59,23 +76,27 @@ impl<'a> FieldElement<'a> for F2PolynomialElement<'a> {
         result
     }
 
-    fn pow_secure(&self, exp: &BigUint, subgroup_order: &BigUint) -> Self {
+    /// The ladder on the coefficients as limbs of the width of the field,
+    /// multiplied and reduced by `ct_mul`.
+    fn pow_ct(&self, exp: &BigUint, bits: u64) -> Self {
         let ctx = self.context;
-        let mut base = self.clone();
-        let mut result = Self::one(ctx);
-        let mut dummy = Self::one(ctx);
+        let degree = get_binary_poly_degree(&ctx.irreducible_binary_poly);
+        let modulus = ctx.irreducible_binary_poly.to_u64_digits();
 
-        let exp = exp % subgroup_order;
+        let base = to_limbs(&Self::poly_mod(ctx, &self.coeffs), degree.div_ceil(64));
+        let one = to_limbs(&BigUint::one(), base.len());
+        let mul = |a: &Vec<u64>, b: &Vec<u64>| Self::ct_mul(a, b, &modulus, degree);
+        let result = ladder(&base, one, exp, bits, mul, |a| mul(a, a));
 
-        for shift in 0..subgroup_order.bits() {
-            if ((&exp >> shift) & BigUint::one()) == BigUint::one() {
-                result = &result * &base;
-            } else {
-                dummy = &dummy * &base;
-            }
-            base = &base * &base;
+        F2PolynomialElement {
+            context: ctx,
+            coeffs: BigUint::from_slice(
+                &result
+                    .iter()
+                    .flat_map(|&limb| [limb as u32, (limb >> 32) as u32])
+                    .collect::<Vec<_>>(),
+            ),
         }
-        result
     }
 }
 
@@ -110,11 +131,20 @@ impl<'a> Display for F2PolynomialElement<'a> {
 
 impl<'a> F2PolynomialElement<'a> {
     pub fn new(ctx: &'a FieldContext, coeffs: BigUint) -> Self {
-        assert!(ctx.is_binary());
-        return F2PolynomialElement {
+        Self::try_new(ctx, coeffs).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Like `new`, but fails instead of panicking if `ctx` is not a binary
+    /// field.
+    pub fn try_new(ctx: &'a FieldContext, coeffs: BigUint) -> Result<Self, FieldError> {
+        if !ctx.is_binary() {
+            return Err(FieldError::WrongField);
+        }
+
+        Ok(F2PolynomialElement {
             context: ctx,
             coeffs,
-        };
+        })
     }
 
     fn poly_add(a: &BigUint, b: &BigUint) -> BigUint {
@@ -134,7 +164,7 @@ impl<'a> F2PolynomialElement<'a> {
         let mut v1 = BigUint::one();
 
         while !r1.is_zero() {
-            let (q, r) = Self::poly_div(&r0, &r1);
+            let (q, r) = Self::poly_div(&r0, &r1).expect("r1 is not zero");
 
             r0 = r1;
             r1 = r;
@@ -151,9 +181,9 @@ impl<'a> F2PolynomialElement<'a> {
         (r0, u0, v0)
     }
 
-    fn poly_div(a: &BigUint, b: &BigUint) -> (BigUint, BigUint) {
+    fn poly_div(a: &BigUint, b: &BigUint) -> Result<(BigUint, BigUint), FieldError> {
         if b.is_zero() {
-            panic!("Division by zero polynomial");
+            return Err(FieldError::DivisionByZero);
         }
 
         let mut quotient = BigUint::zero();
@@ -163,7 +193,7 @@ impl<'a> F2PolynomialElement<'a> {
         // Handle special cases
         if divisor_degree == 0 {
             // Divisor is 1 (b = 1)
-            return (a.clone(), BigUint::zero());
+            return Ok((a.clone(), BigUint::zero()));
         }
 
         while get_binary_poly_degree(&remainder) >= divisor_degree {
@@ -182,17 +212,17 @@ impl<'a> F2PolynomialElement<'a> {
             }
         }
 
-        (quotient, remainder)
+        Ok((quotient, remainder))
     }
 
-    fn poly_inv(ctx: &FieldContext, a: &BigUint) -> Option<BigUint> {
+    fn poly_inv(ctx: &FieldContext, a: &BigUint) -> Result<BigUint, FieldError> {
         let irreducible = &ctx.irreducible_binary_poly;
         let (gcd, u, _) = Self::poly_extended_gcd(a, irreducible);
 
         if gcd == BigUint::one() {
-            Some(u)
+            Ok(u)
         } else {
-            None
+            Err(FieldError::NotInvertible)
         }
     }
 
@@ -204,16 +234,32 @@ impl<'a> F2PolynomialElement<'a> {
         while get_binary_poly_degree(&remainder) >= divisor_degree {
             let remainder_degree = get_binary_poly_degree(&remainder);
             let shift = remainder_degree - divisor_degree;
-            for i in 0..divisor.bits() as usize {
-                if i + shift / 64 < remainder.bits() as usize {
-                    remainder ^= divisor << shift;
-                }
-            }
+            remainder ^= divisor << shift;
         }
 
         remainder
     }
 
+    /// ab mod the `modulus` of degree `degree`, for a and b below that
+    /// degree and given in the same number of limbs. Every bit of a adds
+    /// b shifted under a mask, and every bit of the product above the
+    /// degree is cleared by adding the modulus under a mask, so the steps
+    /// depend on the number of limbs only.
+    fn ct_mul(a: &[u64], b: &[u64], modulus: &[u64], degree: usize) -> Vec<u64> {
+        let mut product = vec![0u64; 2 * a.len()];
+        for i in 0..64 * a.len() {
+            xor_shifted(&mut product, b, i, mask((a[i / 64] >> (i % 64)) & 1));
+        }
+
+        for i in (degree..64 * product.len()).rev() {
+            let bit = (product[i / 64] >> (i % 64)) & 1;
+            xor_shifted(&mut product, modulus, i - degree, mask(bit));
+        }
+
+        product.truncate(a.len());
+        product
+    }
+
     pub fn poly_mul(a: &BigUint, b: &BigUint) -> BigUint {
         let mut result = BigUint::zero();
         let mut b = b.clone();
@@ -227,6 +273,28 @@ impl<'a> F2PolynomialElement<'a> {
     }
 }
 
+/// `a` padded to `limbs` limbs, least significant first.
+fn to_limbs(a: &BigUint, limbs: usize) -> Vec<u64> {
+    let mut digits = a.to_u64_digits();
+    digits.resize(limbs, 0);
+    digits
+}
+
+/// t ^= (a << shift) & mask, dropping the bits shifted past the end of t.
+fn xor_shifted(t: &mut [u64], a: &[u64], shift: usize, mask: u64) {
+    let (limbs, bits) = (shift / 64, shift % 64);
+    for (j, &limb) in a.iter().enumerate() {
+        if let Some(t) = t.get_mut(j + limbs) {
+            *t ^= (limb << bits) & mask;
+        }
+        if bits > 0 {
+            if let Some(t) = t.get_mut(j + limbs + 1) {
+                *t ^= (limb >> (64 - bits)) & mask;
+            }
+        }
+    }
+}
+
 impl<'a> Add for F2PolynomialElement<'a> {
     type Output = Self;
 
@@ -258,6 +326,7 @@ impl<'a> Add for &F2PolynomialElement<'a> {
 impl<'a> Sub for F2PolynomialElement<'a> {
     type Output = Self;
 
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn sub(self, rhs: Self) -> Self::Output {
         return self + rhs;
     }
@@ -266,6 +335,7 @@ impl<'a> Sub for F2PolynomialElement<'a> {
 impl<'a> Sub for &F2PolynomialElement<'a> {
     type Output = F2PolynomialElement<'a>;
 
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn sub(self, rhs: Self) -> Self::Output {
         return self + rhs;
     }
@@ -319,7 +389,7 @@ impl<'a> Div for F2PolynomialElement<'a> {
     type Output = Self;
 
     fn div(self, rhs: Self) -> Self::Output {
-        return self * rhs.inverse();
+        &self / &rhs
     }
 }
 
@@ -327,18 +397,20 @@ impl<'a> Div for &F2PolynomialElement<'a> {
     type Output = F2PolynomialElement<'a>;
 
     fn div(self, rhs: Self) -> Self::Output {
-        return self * &F2PolynomialElement::inverse(rhs);
+        self.checked_div(rhs)
+            .unwrap_or_else(|error| panic!("{}", error))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num::BigInt;
 
     #[test]
     fn test_binary_polynomial_addition() {
         let irreducible_poly = BigUint::from(0b11111101111101001u64);
-        let ctx = FieldContext::new_binary(irreducible_poly);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
         let poly_a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
         let poly_b = F2PolynomialElement::new(&ctx, BigUint::from(0b1010011011000101u64));
@@ -353,7 +425,7 @@ mod tests {
     #[test]
     fn test_binary_polynomial_subtraction() {
         let irreducible_poly = BigUint::from(0b11111101111101001u64);
-        let ctx = FieldContext::new_binary(irreducible_poly);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
         let poly_a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
         let poly_b = F2PolynomialElement::new(&ctx, BigUint::from(0b1010011011000101u64));
@@ -373,7 +445,7 @@ mod tests {
     #[test]
     fn test_binary_polynomial_multiplication() {
         let irreducible_poly = BigUint::from(0b11111101111101001u64);
-        let ctx = FieldContext::new_binary(irreducible_poly);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
         let poly_a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
         let poly_b = F2PolynomialElement::new(&ctx, BigUint::from(0b1010011011000101u64));
@@ -388,7 +460,7 @@ mod tests {
     #[test]
     fn test_binary_polynomial_negation() {
         let irreducible_poly = BigUint::from(0b11111101111101001u64);
-        let ctx = FieldContext::new_binary(irreducible_poly);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
         let poly_a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
         let poly_b = F2PolynomialElement::new(&ctx, BigUint::from(0b1010011011000101u64));
@@ -409,7 +481,7 @@ mod tests {
     #[test]
     fn test_binary_polynomial_inverse() {
         let irreducible_poly = BigUint::from(0b11111101111101001u64);
-        let ctx = FieldContext::new_binary(irreducible_poly);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
         let poly_a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
         let poly_b = F2PolynomialElement::new(&ctx, BigUint::from(0b1010011011000101u64));
@@ -430,12 +502,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_binary_polynomial_checked_inverse() {
+        let irreducible_poly = BigUint::from(0b11111101111101001u64);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
+
+        let zero = F2PolynomialElement::zero(&ctx);
+        let one = F2PolynomialElement::one(&ctx);
+        assert_eq!(zero.checked_inverse(), Err(FieldError::NotInvertible));
+        assert_eq!(one.checked_div(&zero), Err(FieldError::DivisionByZero));
+        assert_eq!(
+            F2PolynomialElement::poly_div(&BigUint::one(), &BigUint::zero()),
+            Err(FieldError::DivisionByZero)
+        );
+
+        // x^2 + 1 = (x + 1)^2 and x^4 + x^2 + 1 = (x^2 + x + 1)^2 do not
+        // define fields, x + 1 and x^2 + x + 1 do
+        for (poly, irreducible) in [
+            (0b101u32, false),
+            (0b10101, false),
+            (0b11, true),
+            (0b111, true),
+        ] {
+            let ctx = FieldContext::new_binary(BigUint::from(poly));
+            assert_eq!(ctx.is_ok(), irreducible, "{:b}", poly);
+        }
+
+        let prime_ctx = FieldContext::new_prime(BigInt::from(17)).unwrap();
+        assert_eq!(
+            F2PolynomialElement::try_new(&prime_ctx, BigUint::one()),
+            Err(FieldError::WrongField)
+        );
+    }
+
     #[test]
     fn test_binary_polynomial_division_internals() {
         let a = BigUint::from(0b1011010u64);
         let b = BigUint::from(0b101u64);
 
-        let (quotient, remainder) = F2PolynomialElement::poly_div(&a, &b);
+        let (quotient, remainder) = F2PolynomialElement::poly_div(&a, &b).unwrap();
 
         assert_eq!(quotient, BigUint::from(0b10010u64));
         assert_eq!(remainder, BigUint::from(0b0u64));
@@ -443,7 +548,7 @@ mod tests {
         let a = BigUint::from(0b1011010u64);
         let b = BigUint::from(0b11u64);
 
-        let (quotient, remainder) = F2PolynomialElement::poly_div(&a, &b);
+        let (quotient, remainder) = F2PolynomialElement::poly_div(&a, &b).unwrap();
 
         assert_eq!(quotient, BigUint::from(0b110110u64));
         assert_eq!(remainder, BigUint::from(0b0u64));
@@ -451,7 +556,7 @@ mod tests {
         let a = BigUint::from(0b1011010u64);
         let b = BigUint::from(0b1011u64);
 
-        let (quotient, remainder) = F2PolynomialElement::poly_div(&a, &b);
+        let (quotient, remainder) = F2PolynomialElement::poly_div(&a, &b).unwrap();
 
         assert_eq!(quotient, BigUint::from(0b1000u64));
         assert_eq!(remainder, BigUint::from(0b10u64));
@@ -462,7 +567,7 @@ mod tests {
         assert_eq!(get_binary_poly_degree(&a), 6);
         assert_eq!(get_binary_poly_degree(&b), 8);
 
-        let (quotient, remainder) = F2PolynomialElement::poly_div(&a, &b);
+        let (quotient, remainder) = F2PolynomialElement::poly_div(&a, &b).unwrap();
 
         assert_eq!(quotient, BigUint::zero());
         assert_eq!(remainder, BigUint::from(0b1011010u64));
@@ -534,7 +639,7 @@ mod tests {
     #[test]
     fn test_binary_polynomial_division() {
         let irreducible_poly = BigUint::from(0b11111101111101001u64);
-        let ctx = FieldContext::new_binary(irreducible_poly);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
         let poly_a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
         let poly_b = F2PolynomialElement::new(&ctx, BigUint::from(0b1010011011000101u64));
@@ -555,7 +660,7 @@ mod tests {
     #[test]
     fn test_binary_polynomial_display() {
         let irreducible_poly = BigUint::from(0b11111101111101001u64);
-        let ctx = FieldContext::new_binary(irreducible_poly);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
         let poly_a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
         let poly_b = F2PolynomialElement::new(&ctx, BigUint::from(0b1010011011000101u64));
@@ -573,7 +678,7 @@ mod tests {
     #[test]
     fn test_binary_polynomial_exponentiation() {
         let irreducible_poly = BigUint::from(0b11111101111101001u64);
-        let ctx = FieldContext::new_binary(irreducible_poly);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
         let poly_a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
         let poly_b = F2PolynomialElement::new(&ctx, BigUint::from(0b1010011011000101u64));
@@ -601,7 +706,7 @@ mod tests {
     #[test]
     fn test_binary_polynomial_degree() {
         let irreducible_poly = BigUint::from(0b11111101111101001u64);
-        let ctx = FieldContext::new_binary(irreducible_poly);
+        let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
         let poly_a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
         let poly_b = F2PolynomialElement::new(&ctx, BigUint::from(0b1010011011000101u64));
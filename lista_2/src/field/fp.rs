@@ -1,30 +1,81 @@
 use num::bigint::BigInt;
-use num::traits::{One, Zero};
-use num::BigUint;
+use num::traits::{One, Signed, Zero};
+use num::{BigUint, Integer};
 use std::fmt;
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
-use crate::{FieldContext, FieldElement};
+use super::ct::{ladder, select_biguint, Choice, ConditionallySelectable};
+use super::montgomery::Montgomery;
+use crate::{FieldContext, FieldElement, FieldError};
 
 /// An element in the prime field Fp, referencing a `FieldContext`.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct FpElement<'a> {
     context: &'a FieldContext,
-    pub val: BigInt,
+    val: Repr,
+}
+
+/// How an element is kept, decided by its context; `value` gives a
+/// either way.
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Repr {
+    /// The element a, in 0..p.
+    Plain(BigInt),
+    /// aR mod p, as the limbs of the context's `Montgomery`.
+    Montgomery(Vec<u64>),
 }
 
 impl fmt::Display for FpElement<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Just display the value field
-        write!(f, "{}", self.val)
+        write!(f, "{}", self.value())
     }
 }
 
 impl<'a> FpElement<'a> {
     pub fn new(context: &'a FieldContext, val: BigInt) -> Self {
+        let val = match &context.montgomery {
+            Some(montgomery) => {
+                Repr::Montgomery(montgomery.to_montgomery(val.mod_floor(&context.p).magnitude()))
+            }
+            None => Repr::Plain(val.mod_floor(&context.p)),
+        };
+        Self { context, val }
+    }
+
+    fn with_val(&self, val: Repr) -> Self {
         Self {
-            context,
-            val: val % &context.p,
+            context: self.context,
+            val,
+        }
+    }
+
+    /// The Montgomery constants of `context`; only elements kept as
+    /// `Repr::Montgomery` ask for them.
+    fn constants(context: &FieldContext) -> &Montgomery {
+        context
+            .montgomery
+            .as_ref()
+            .expect("the context keeps elements in Montgomery form")
+    }
+
+    /// The element as the least non-negative residue, out of Montgomery
+    /// form if it is in it.
+    pub fn value(&self) -> BigInt {
+        match &self.val {
+            Repr::Plain(val) => val.clone(),
+            Repr::Montgomery(val) => FpElement::constants(self.context)
+                .from_montgomery(val)
+                .into(),
+        }
+    }
+
+    /// self * self, with a Montgomery squaring if the context has one.
+    pub fn square(&self) -> Self {
+        match &self.val {
+            Repr::Plain(val) => self.with_val(Repr::Plain((val * val) % &self.context.p)),
+            Repr::Montgomery(val) => self.with_val(Repr::Montgomery(
+                FpElement::constants(self.context).square(val),
+            )),
         }
     }
 
@@ -56,96 +107,150 @@ impl<'a> FpElement<'a> {
 
         (old_r, old_s, old_t)
     }
+
+    /// self + other, reusing the storage of `self`: both are below p, so
+    /// the sum needs at most one subtraction of p.
+    fn add_assign(&mut self, other: &Self) {
+        let context = self.context;
+        match (&mut self.val, &other.val) {
+            (Repr::Plain(val), Repr::Plain(other)) => {
+                *val += other;
+                if *val >= context.p {
+                    *val -= &context.p;
+                }
+            }
+            (Repr::Montgomery(val), Repr::Montgomery(other)) => {
+                FpElement::constants(context).add_assign(val, other)
+            }
+            _ => panic!("{}", FieldError::WrongField),
+        }
+    }
+
+    /// self - other, reusing the storage of `self`, with p added back if
+    /// the difference is negative.
+    fn sub_assign(&mut self, other: &Self) {
+        let context = self.context;
+        match (&mut self.val, &other.val) {
+            (Repr::Plain(val), Repr::Plain(other)) => {
+                *val -= other;
+                if val.is_negative() {
+                    *val += &context.p;
+                }
+            }
+            (Repr::Montgomery(val), Repr::Montgomery(other)) => {
+                FpElement::constants(context).sub_assign(val, other)
+            }
+            _ => panic!("{}", FieldError::WrongField),
+        }
+    }
 }
 
 impl<'a> Add for FpElement<'a> {
     type Output = FpElement<'a>;
-    fn add(self, other: FpElement<'a>) -> FpElement<'a> {
-        FpElement::new(self.context, self.val + other.val)
+    fn add(mut self, other: FpElement<'a>) -> FpElement<'a> {
+        self.add_assign(&other);
+        self
     }
 }
 
 impl<'a> Add for &FpElement<'a> {
     type Output = FpElement<'a>;
     fn add(self, other: &FpElement<'a>) -> FpElement<'a> {
-        FpElement::new(self.context, &self.val + &other.val)
+        self.clone() + other.clone()
     }
 }
 
 impl<'a> Sub for FpElement<'a> {
     type Output = FpElement<'a>;
-    fn sub(self, other: FpElement<'a>) -> FpElement<'a> {
-        FpElement::new(
-            self.context,
-            (self.val + &self.context.p - other.val) % &self.context.p,
-        )
+    fn sub(mut self, other: FpElement<'a>) -> FpElement<'a> {
+        self.sub_assign(&other);
+        self
     }
 }
 
 impl<'a> Sub for &FpElement<'a> {
     type Output = FpElement<'a>;
     fn sub(self, other: &FpElement<'a>) -> FpElement<'a> {
-        FpElement::new(
-            self.context,
-            (&self.val + &self.context.p - &other.val) % &self.context.p,
-        )
+        let mut difference = self.clone();
+        difference.sub_assign(other);
+        difference
     }
 }
 
 impl<'a> Neg for FpElement<'a> {
     type Output = FpElement<'a>;
     fn neg(self) -> FpElement<'a> {
-        if self.val == BigInt::zero() {
-            self
-        } else {
-            FpElement::new(self.context, &self.context.p - self.val)
-        }
+        -&self
     }
 }
 
 impl<'a> Neg for &FpElement<'a> {
     type Output = FpElement<'a>;
     fn neg(self) -> FpElement<'a> {
-        if self.val == BigInt::zero() {
-            self.clone()
-        } else {
-            FpElement::new(self.context, &self.context.p - &self.val)
-        }
+        &FpElement::zero(self.context) - self
     }
 }
 
 impl<'a> Mul for FpElement<'a> {
     type Output = FpElement<'a>;
     fn mul(self, other: FpElement<'a>) -> FpElement<'a> {
-        let res = (self.val * other.val) % &self.context.p;
-        FpElement::new(self.context, res)
+        &self * &other
     }
 }
 
 impl<'a> Mul for &FpElement<'a> {
     type Output = FpElement<'a>;
     fn mul(self, other: &FpElement<'a>) -> FpElement<'a> {
-        let res = (&self.val * &other.val) % &self.context.p;
-        FpElement::new(self.context, res)
+        match (&self.val, &other.val) {
+            (Repr::Plain(a), Repr::Plain(b)) => {
+                self.with_val(Repr::Plain((a * b) % &self.context.p))
+            }
+            (Repr::Montgomery(a), Repr::Montgomery(b)) => self.with_val(Repr::Montgomery(
+                FpElement::constants(self.context).mul(a, b),
+            )),
+            _ => panic!("{}", FieldError::WrongField),
+        }
     }
 }
 
 impl<'a> Div for FpElement<'a> {
     type Output = FpElement<'a>;
     fn div(self, other: FpElement<'a>) -> FpElement<'a> {
-        self * other.inverse()
+        &self / &other
     }
 }
 
 impl<'a> Div for &FpElement<'a> {
     type Output = FpElement<'a>;
     fn div(self, other: &FpElement<'a>) -> FpElement<'a> {
-        let inv = other.inverse();
-        self * &inv
+        self.checked_div(other)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+}
+
+impl ConditionallySelectable for FpElement<'_> {
+    /// Selects the limbs of the values, which are in 0..p for both, over
+    /// the width of p; only plain elements convert from and to `BigInt`.
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        match (&a.val, &b.val) {
+            (Repr::Plain(x), Repr::Plain(y)) => {
+                let limbs = a.context.p.bits().div_ceil(64) as usize;
+                let val = select_biguint(x.magnitude(), y.magnitude(), limbs, choice);
+                a.with_val(Repr::Plain(val.into()))
+            }
+            (Repr::Montgomery(x), Repr::Montgomery(y)) => {
+                a.with_val(Repr::Montgomery(Vec::conditional_select(x, y, choice)))
+            }
+            _ => panic!("{}", FieldError::WrongField),
+        }
     }
 }
 
 impl<'a> FieldElement<'a> for FpElement<'a> {
+    fn context(&self) -> &'a FieldContext {
+        self.context
+    }
+
     fn zero(ctx: &'a FieldContext) -> Self {
         FpElement::new(ctx, BigInt::zero())
     }
@@ -154,23 +259,43 @@ impl<'a> FieldElement<'a> for FpElement<'a> {
         FpElement::new(ctx, BigInt::one())
     }
 
+    /// aR mod p is zero only for a = 0.
     fn is_zero(&self) -> bool {
-        self.val == BigInt::zero()
+        match &self.val {
+            Repr::Plain(val) => val.is_zero(),
+            Repr::Montgomery(val) => val.iter().all(|&limb| limb == 0),
+        }
     }
 
-    fn inverse(&self) -> Self {
-        let a = &self.val;
+    fn checked_inverse(&self) -> Result<Self, FieldError> {
+        let a = &self.value();
         let m = &self.context.p;
         let (g, x, _) = Self::extended_gcd(a, m);
         if g != BigInt::one() {
-            panic!("No inverse exists!");
+            return Err(FieldError::NotInvertible);
         }
         let inv = ((x % m) + m) % m;
-        FpElement::new(self.context, inv)
+        Ok(FpElement::new(self.context, inv))
     }
 
     fn pow(&self, exp: &BigUint) -> Self {
-        let mut base = self.val.clone();
+        if let Repr::Montgomery(val) = &self.val {
+            let montgomery = FpElement::constants(self.context);
+            let mut base = val.clone();
+            let mut result = montgomery.one();
+            let mut product = montgomery.one();
+
+            for shift in 0..exp.bits() {
+                if exp.bit(shift) {
+                    montgomery.mul_into(&result, &base, &mut product);
+                    std::mem::swap(&mut result, &mut product);
+                }
+                base = montgomery.square(&base);
+            }
+            return self.with_val(Repr::Montgomery(result));
+        }
+
+        let mut base = self.value();
         let mut result = BigInt::one();
         let mut e_val = exp.clone();
 
@@ -184,23 +309,46 @@ impl<'a> FieldElement<'a> for FpElement<'a> {
         FpElement::new(self.context, result)
     }
 
-    fn pow_secure(&self, exp: &BigUint, subgroup_order: &BigUint) -> Self {
-        let mut base = self.val.clone();
-        let mut result = BigInt::one();
-        let mut dummy = BigInt::one();
-
-        let exp = exp % subgroup_order;
-
-        for shift in 0..subgroup_order.bits() {
-            if ((&exp >> shift) & BigUint::one()) == BigUint::one() {
-                result = (&result * &base) % &self.context.p;
-            } else {
-                // do dummy multiplication to not leak information about the exponent
-                dummy = (&dummy * &base) % &self.context.p;
+    /// The ladder on Montgomery limbs. A context without Montgomery
+    /// constants gets them for the call, since `BigInt` products take time
+    /// that depends on the values; only p = 2, which has none, stays on
+    /// `BigInt`.
+    fn pow_ct(&self, exp: &BigUint, bits: u64) -> Self {
+        let constants;
+        let (montgomery, val) = match &self.val {
+            Repr::Montgomery(val) => (FpElement::constants(self.context), val.clone()),
+            Repr::Plain(val) => match Montgomery::new(self.context.p.magnitude()) {
+                Some(montgomery) => {
+                    constants = montgomery;
+                    (&constants, constants.to_montgomery(val.magnitude()))
+                }
+                None => {
+                    return ladder(
+                        self,
+                        Self::one(self.context),
+                        exp,
+                        bits,
+                        |a, b| a * b,
+                        |a| a * a,
+                    )
+                }
+            },
+        };
+
+        let result = ladder(
+            &val,
+            montgomery.one(),
+            exp,
+            bits,
+            |a, b| montgomery.mul(a, b),
+            |a| montgomery.square(a),
+        );
+        match self.val {
+            Repr::Montgomery(_) => self.with_val(Repr::Montgomery(result)),
+            Repr::Plain(_) => {
+                self.with_val(Repr::Plain(montgomery.from_montgomery(&result).into()))
             }
-            base = (&base * &base) % &self.context.p;
         }
-        FpElement::new(self.context, result)
     }
 }
 
@@ -212,96 +360,96 @@ mod tests {
     #[test]
     fn test_fp_addition() {
         let p = 17.to_bigint().unwrap();
-        let ctx = FieldContext::new_prime(p);
+        let ctx = FieldContext::new_prime(p).unwrap();
 
         let a = FpElement::new(&ctx, 2.to_bigint().unwrap());
         let b = FpElement::new(&ctx, 3.to_bigint().unwrap());
         let c = FpElement::new(&ctx, 0.to_bigint().unwrap());
 
-        assert_eq!((&a + &b).val, 5.to_bigint().unwrap());
-        assert_eq!((&a + &c).val, a.val); // a+0 = a
-                                          // Check wrap-around
+        assert_eq!((&a + &b).value(), 5.to_bigint().unwrap());
+        assert_eq!((&a + &c).value(), a.value()); // a+0 = a
+                                                  // Check wrap-around
         let x = FpElement::new(&ctx, 16.to_bigint().unwrap());
         let y = FpElement::new(&ctx, 5.to_bigint().unwrap());
         // 16+5=21 mod 17=4
-        assert_eq!((x + y).val, 4.to_bigint().unwrap());
+        assert_eq!((x + y).value(), 4.to_bigint().unwrap());
     }
 
     #[test]
     fn test_fp_subtraction() {
         let p = 17.to_bigint().unwrap();
-        let ctx = FieldContext::new_prime(p);
+        let ctx = FieldContext::new_prime(p).unwrap();
 
         let a = FpElement::new(&ctx, 2.to_bigint().unwrap());
         let b = FpElement::new(&ctx, 3.to_bigint().unwrap());
-        assert_eq!((b - a).val, 1.to_bigint().unwrap());
+        assert_eq!((b - a).value(), 1.to_bigint().unwrap());
         // Check wrap-around
         let x = FpElement::new(&ctx, 1.to_bigint().unwrap());
         let y = FpElement::new(&ctx, 0.to_bigint().unwrap());
         // 0-1 = -1 mod 17 = 16
-        assert_eq!((y - x).val, 16.to_bigint().unwrap());
+        assert_eq!((y - x).value(), 16.to_bigint().unwrap());
     }
 
     #[test]
     fn test_fp_multiplication() {
         let p = 17.to_bigint().unwrap();
-        let ctx = FieldContext::new_prime(p);
+        let ctx = FieldContext::new_prime(p).unwrap();
 
         let a = FpElement::new(&ctx, 2.to_bigint().unwrap());
         let b = FpElement::new(&ctx, 3.to_bigint().unwrap());
-        assert_eq!((&a * &b).val, 6.to_bigint().unwrap());
+        assert_eq!((&a * &b).value(), 6.to_bigint().unwrap());
         // Check multiplication by zero
         let zero = FpElement::new(&ctx, 0.to_bigint().unwrap());
-        assert_eq!((a * zero).val, 0.to_bigint().unwrap());
+        assert_eq!((a * zero).value(), 0.to_bigint().unwrap());
         // Check wrap-around
         let big = FpElement::new(&ctx, 20.to_bigint().unwrap());
         // 20 mod 17 = 3, 3 * 3 = 9 mod 17
-        assert_eq!((big * b).val, 9.to_bigint().unwrap());
+        assert_eq!((big * b).value(), 9.to_bigint().unwrap());
     }
 
     #[test]
     fn test_fp_negation() {
         let p = 17.to_bigint().unwrap();
-        let ctx = FieldContext::new_prime(p);
+        let ctx = FieldContext::new_prime(p).unwrap();
 
         let a = FpElement::new(&ctx, 2.to_bigint().unwrap());
-        assert_eq!((-a).val, 15.to_bigint().unwrap()); // since 17-2=15
+        assert_eq!((-a).value(), 15.to_bigint().unwrap()); // since 17-2=15
         let zero = FpElement::new(&ctx, 0.to_bigint().unwrap());
-        assert_eq!((-zero).val, 0.to_bigint().unwrap());
+        assert_eq!((-zero).value(), 0.to_bigint().unwrap());
     }
 
     #[test]
     fn test_fp_inverse() {
         let p = 17.to_bigint().unwrap();
-        let ctx = FieldContext::new_prime(p);
+        let ctx = FieldContext::new_prime(p).unwrap();
 
         let a = FpElement::new(&ctx, 3.to_bigint().unwrap());
         let inv_a = a.inverse();
         // Check a*inv_a=1
-        assert_eq!((a * inv_a).val, 1.to_bigint().unwrap());
+        assert_eq!((a * inv_a).value(), 1.to_bigint().unwrap());
         // Try another element
         let b = FpElement::new(&ctx, 5.to_bigint().unwrap());
         let inv_b = b.inverse();
-        assert_eq!((b * inv_b).val, 1.to_bigint().unwrap());
+        assert_eq!((b * inv_b).value(), 1.to_bigint().unwrap());
     }
 
     #[test]
     fn test_fp_division() {
         let p = 17.to_bigint().unwrap();
-        let ctx = FieldContext::new_prime(p);
+        let ctx = FieldContext::new_prime(p).unwrap();
 
         let a = FpElement::new(&ctx, 2.to_bigint().unwrap());
         let b = FpElement::new(&ctx, 3.to_bigint().unwrap());
         // a/b = a * b^-1
         let div = a / b;
         // 3^-1 mod17=6, so a/b=2*6=12 mod17
-        assert_eq!(div.val, 12.to_bigint().unwrap());
+        assert_eq!(div.value(), 12.to_bigint().unwrap());
     }
 
     #[test]
     fn test_fp_exponentiation() {
         let p = 17.to_bigint().unwrap();
-        let ctx = FieldContext::new_prime(p);
+        let ctx = FieldContext::new_prime(p).unwrap();
         let order = 8.to_biguint().unwrap();
 
         let a = FpElement::new(&ctx, 2.to_bigint().unwrap());
@@ -310,23 +458,133 @@ mod tests {
 
         let res = a.pow(&exp);
         let secure_res = a.pow_secure(&exp, &order);
-        assert_eq!(res.val, 15.to_bigint().unwrap());
-        assert_eq!(secure_res.val, 15.to_bigint().unwrap());
+        assert_eq!(res.value(), 15.to_bigint().unwrap());
+        assert_eq!(secure_res.value(), 15.to_bigint().unwrap());
 
         // Check a bigger exponent
         let exp_big = 16.to_biguint().unwrap(); // a^(16)=2^16=65536 mod17
                                                 // 2^16 = (2^4)^4 = (16)^4 = (16 mod17=16)^2=256 mod17=256-255=1 again and again => actually 2^16 mod17= (2^(17-1))=1 by Fermat's little theorem
         let res_big = a.pow(&exp_big);
-        let secure_res_big = a.pow_secure(&exp_big, &order);
+        // pow_secure wants the exponent reduced modulo the order of 2
+        let secure_res_big = a.pow_secure(&(&exp_big % &order), &order);
 
-        assert_eq!(res_big.val, 1.to_bigint().unwrap());
-        assert_eq!(secure_res_big.val, 1.to_bigint().unwrap());
+        assert_eq!(res_big.value(), 1.to_bigint().unwrap());
+        assert_eq!(secure_res_big.value(), 1.to_bigint().unwrap());
+    }
+
+    #[test]
+    fn test_fp_montgomery_arithmetic() {
+        let p: BigInt = (BigInt::one() << 255) - 19;
+        let ctx = FieldContext::new_prime(p.clone()).unwrap();
+        let montgomery_ctx = ctx.clone().with_montgomery();
+        assert!(montgomery_ctx.montgomery.is_some());
+
+        let order = p.to_biguint().unwrap() - 1u32;
+        let exps = [
+            0.to_biguint().unwrap(),
+            1.to_biguint().unwrap(),
+            65537.to_biguint().unwrap(),
+            &order - 1u32,
+            order.clone(),
+        ];
+
+        for val in [0, 2, 5, -7] {
+            let a = FpElement::new(&ctx, val.to_bigint().unwrap());
+            let b = FpElement::new(&montgomery_ctx, val.to_bigint().unwrap());
+
+            let c = FpElement::new(&montgomery_ctx, 3.to_bigint().unwrap());
+            let product = &a * &ctx.to_fp(3.to_bigint().unwrap());
+            assert_eq!((&b * &c).value(), product.value());
+            assert_eq!(b.square().value(), a.square().value());
+            assert_eq!((&b + &c).value(), (&a + &ctx.to_fp(3.into())).value());
+            assert_eq!((-&b).value(), (-&a).value());
+            if !b.is_zero() {
+                assert_eq!((&b * &b.inverse()).value(), BigInt::one());
+            }
+            assert_eq!(b.to_string(), a.value().to_string());
+
+            for exp in &exps {
+                assert_eq!(b.pow(exp).value(), a.pow(exp).value());
+                if exp < &order {
+                    assert_eq!(
+                        b.pow_secure(exp, &order).value(),
+                        a.pow_secure(exp, &order).value()
+                    );
+                }
+            }
+        }
+
+        // the Montgomery form of 1 is R mod p
+        let one = FpElement::one(&montgomery_ctx);
+        assert_eq!(one.value(), BigInt::one());
+        assert_eq!(
+            one.val,
+            Repr::Montgomery(montgomery_ctx.montgomery.as_ref().unwrap().one())
+        );
+
+        // p = 2 has no Montgomery form
+        let ctx = FieldContext::new_prime(2.to_bigint().unwrap()).unwrap();
+        assert!(ctx.with_montgomery().montgomery.is_none());
+    }
+
+    #[test]
+    fn test_fp_checked_inverse() {
+        let p = 17.to_bigint().unwrap();
+        let ctx = FieldContext::new_prime(p).unwrap();
+
+        let zero = FpElement::zero(&ctx);
+        let a = FpElement::new(&ctx, 3.to_bigint().unwrap());
+        assert_eq!(zero.checked_inverse(), Err(FieldError::NotInvertible));
+        assert_eq!(a.checked_div(&zero), Err(FieldError::DivisionByZero));
+        assert_eq!(a.checked_div(&a), Ok(FpElement::one(&ctx)));
+        assert_eq!(zero.checked_div(&a), Ok(zero));
+    }
+
+    #[test]
+    #[should_panic(expected = "division by zero")]
+    fn test_fp_division_by_zero_panics() {
+        let p = 17.to_bigint().unwrap();
+        let ctx = FieldContext::new_prime(p).unwrap();
+
+        let _ = FpElement::one(&ctx) / FpElement::zero(&ctx);
+    }
+
+    #[test]
+    #[should_panic(expected = "not reduced")]
+    fn test_fp_pow_secure_wants_a_reduced_exponent() {
+        let ctx = FieldContext::new_prime(17.to_bigint().unwrap()).unwrap();
+        let order = 8.to_biguint().unwrap();
+
+        let _ = FpElement::new(&ctx, 2.to_bigint().unwrap()).pow_secure(&order, &order);
+    }
+
+    #[test]
+    fn test_fp_conditional_select() {
+        let p = 17.to_bigint().unwrap();
+        let ctx = FieldContext::new_prime(p).unwrap();
+        let montgomery_ctx = ctx.clone().with_montgomery();
+
+        for ctx in [&ctx, &montgomery_ctx] {
+            // negative values are kept as their least non-negative residue
+            let a = FpElement::new(ctx, (-7).to_bigint().unwrap());
+            let b = FpElement::new(ctx, 3.to_bigint().unwrap());
+            assert_eq!(a, FpElement::new(ctx, 10.to_bigint().unwrap()));
+
+            assert_eq!(
+                FpElement::conditional_select(&a, &b, Choice::from_bit(0)),
+                a
+            );
+            assert_eq!(
+                FpElement::conditional_select(&a, &b, Choice::from_bit(1)),
+                b
+            );
+        }
     }
 
     // #[test]
     // fn test_fp_exponentiation_security() {
     //     let p = 17.to_bigint().unwrap();
-    //     let ctx = FieldContext::new_prime(p);
+    //     let ctx = FieldContext::new_prime(p).unwrap();
     //
     //     let a = FpElement::new(&ctx, 2.to_bigint().unwrap());
     //     let exp_ones = 0b111111111111111111111111111;
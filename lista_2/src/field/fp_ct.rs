@@ -0,0 +1,385 @@
+// Elements of Fp on fixed-width integers: values are kept in Montgomery
+// form in a `Uint<N>`, and addition, subtraction, multiplication and the
+// reduction after them run in time independent of the values and without
+// allocating. The constants come from the context's `Montgomery`, so the
+// context must be built `with_montgomery()` and p must take exactly N limbs.
+//
+// Only `new`, `value` and `Display` go through `BigInt`, as do the
+// exponents of `pow` and `pow_secure`, which are passed as `BigUint`.
+
+use num::bigint::BigInt;
+use num::{BigUint, Integer};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use super::ct::{Choice, ConditionallySelectable};
+use super::montgomery::Montgomery;
+use super::uint::{adc, mac, Uint};
+use crate::{FieldContext, FieldElement, FieldError};
+
+/// An element of Fp for a p of N limbs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FpCtElement<'a, const N: usize> {
+    context: &'a FieldContext,
+    /// aR mod p for the element a.
+    val: Uint<N>,
+}
+
+/// Elements of the fields of P-256 or Curve25519.
+pub type Fp256Element<'a> = FpCtElement<'a, 4>;
+/// Elements of the field of P-384.
+pub type Fp384Element<'a> = FpCtElement<'a, 6>;
+/// Elements of the field of P-521.
+pub type Fp521Element<'a> = FpCtElement<'a, 9>;
+
+impl<const N: usize> fmt::Display for FpCtElement<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value())
+    }
+}
+
+impl<'a, const N: usize> FpCtElement<'a, N> {
+    /// `val` mod p; panics if the context does not fit, see `try_new`.
+    pub fn new(context: &'a FieldContext, val: BigInt) -> Self {
+        Self::try_new(context, &val).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// `val` mod p, or an error if `context` has no Montgomery constants or
+    /// its p does not take N limbs.
+    pub fn try_new(context: &'a FieldContext, val: &BigInt) -> Result<Self, FieldError> {
+        let montgomery = Self::constants(context)?;
+
+        let val = Uint::from_biguint(val.mod_floor(&context.p).magnitude()).unwrap();
+        let r2 = Uint::from_limbs(montgomery.r2());
+        let element = Self { context, val };
+
+        Ok(Self {
+            context,
+            val: element.mul_reduce(&val, &r2),
+        })
+    }
+
+    /// The Montgomery constants of `context`, checked to fit N limbs.
+    fn constants(context: &FieldContext) -> Result<&Montgomery, FieldError> {
+        let montgomery = match &context.montgomery {
+            Some(montgomery) if !context.is_poly() && !context.is_binary() => montgomery,
+            _ => {
+                return Err(FieldError::InvalidModulus(
+                    "the context has no Montgomery constants".to_string(),
+                ))
+            }
+        };
+        if montgomery.p().len() != N {
+            return Err(FieldError::InvalidModulus(format!(
+                "p takes {} limbs, not {}",
+                montgomery.p().len(),
+                N
+            )));
+        }
+
+        Ok(montgomery)
+    }
+
+    /// The element whose Montgomery form is `val`, which is below p; panics
+    /// if the context does not fit, like `new`.
+    fn from_montgomery(
+        context: &'a FieldContext,
+        val: impl FnOnce(&Montgomery) -> Uint<N>,
+    ) -> Self {
+        let montgomery = Self::constants(context).unwrap_or_else(|error| panic!("{}", error));
+        Self {
+            context,
+            val: val(montgomery),
+        }
+    }
+
+    /// The value as the least non-negative residue.
+    pub fn value(&self) -> BigInt {
+        self.mul_reduce(&self.val, &Uint::ONE).to_biguint().into()
+    }
+
+    /// p and -p^-1 mod 2^64.
+    fn modulus(&self) -> (Uint<N>, u64) {
+        let montgomery = self.context.montgomery.as_ref().unwrap();
+        (Uint::from_limbs(montgomery.p()), montgomery.p_inv())
+    }
+
+    fn with_val(&self, val: Uint<N>) -> Self {
+        Self {
+            context: self.context,
+            val,
+        }
+    }
+
+    /// abR^-1 mod p for a, b < p: the product, reduced word by word as it
+    /// is accumulated (CIOS).
+    fn mul_reduce(&self, a: &Uint<N>, b: &Uint<N>) -> Uint<N> {
+        let (p, p_inv) = self.modulus();
+        let mut t = Uint::<N>::ZERO;
+        // limb N of t
+        let mut top = 0;
+
+        for i in 0..N {
+            let mut carry = 0;
+            for j in 0..N {
+                (t.limbs[j], carry) = mac(t.limbs[j], a.limbs[j], b.limbs[i], carry);
+            }
+            let (sum, overflow) = adc(top, carry, 0);
+
+            // t = (t + m * p) / 2^64, where m makes the low limb zero
+            let m = t.limbs[0].wrapping_mul(p_inv);
+            let (_, mut carry) = mac(t.limbs[0], m, p.limbs[0], 0);
+            for j in 1..N {
+                (t.limbs[j - 1], carry) = mac(t.limbs[j], m, p.limbs[j], carry);
+            }
+            (t.limbs[N - 1], carry) = adc(sum, carry, 0);
+            top = overflow + carry;
+        }
+
+        Self::reduce_once(&t, top, &p)
+    }
+
+    /// t mod p for t < 2p, with `top` the bit of t above the N limbs.
+    fn reduce_once(t: &Uint<N>, top: u64, p: &Uint<N>) -> Uint<N> {
+        let (difference, borrow) = t.overflowing_sub(p);
+        Uint::select(t, &difference, top | (borrow ^ 1))
+    }
+}
+
+impl<'a, const N: usize> Add for FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn add(self, other: FpCtElement<'a, N>) -> FpCtElement<'a, N> {
+        let (p, _) = self.modulus();
+        let (sum, carry) = self.val.overflowing_add(&other.val);
+        self.with_val(FpCtElement::reduce_once(&sum, carry, &p))
+    }
+}
+
+impl<'a, const N: usize> Add for &FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn add(self, other: &FpCtElement<'a, N>) -> FpCtElement<'a, N> {
+        *self + *other
+    }
+}
+
+impl<'a, const N: usize> Sub for FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn sub(self, other: FpCtElement<'a, N>) -> FpCtElement<'a, N> {
+        let (p, _) = self.modulus();
+        let (difference, borrow) = self.val.overflowing_sub(&other.val);
+        let (wrapped, _) = difference.overflowing_add(&p);
+        self.with_val(Uint::select(&difference, &wrapped, borrow))
+    }
+}
+
+impl<'a, const N: usize> Sub for &FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn sub(self, other: &FpCtElement<'a, N>) -> FpCtElement<'a, N> {
+        *self - *other
+    }
+}
+
+impl<'a, const N: usize> Neg for FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn neg(self) -> FpCtElement<'a, N> {
+        self.with_val(Uint::ZERO) - self
+    }
+}
+
+impl<'a, const N: usize> Neg for &FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn neg(self) -> FpCtElement<'a, N> {
+        -*self
+    }
+}
+
+impl<'a, const N: usize> Mul for FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn mul(self, other: FpCtElement<'a, N>) -> FpCtElement<'a, N> {
+        self.with_val(self.mul_reduce(&self.val, &other.val))
+    }
+}
+
+impl<'a, const N: usize> Mul for &FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn mul(self, other: &FpCtElement<'a, N>) -> FpCtElement<'a, N> {
+        *self * *other
+    }
+}
+
+impl<'a, const N: usize> Div for FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn div(self, other: FpCtElement<'a, N>) -> FpCtElement<'a, N> {
+        self.checked_div(&other)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+}
+
+impl<'a, const N: usize> Div for &FpCtElement<'a, N> {
+    type Output = FpCtElement<'a, N>;
+    fn div(self, other: &FpCtElement<'a, N>) -> FpCtElement<'a, N> {
+        *self / *other
+    }
+}
+
+impl<const N: usize> ConditionallySelectable for FpCtElement<'_, N> {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        a.with_val(Uint::conditional_select(&a.val, &b.val, choice))
+    }
+}
+
+impl<'a, const N: usize> FieldElement<'a> for FpCtElement<'a, N> {
+    fn context(&self) -> &'a FieldContext {
+        self.context
+    }
+
+    fn zero(ctx: &'a FieldContext) -> Self {
+        FpCtElement::from_montgomery(ctx, |_| Uint::ZERO)
+    }
+
+    /// R mod p, straight from the Montgomery constants.
+    fn one(ctx: &'a FieldContext) -> Self {
+        FpCtElement::from_montgomery(ctx, |montgomery| Uint::from_limbs(montgomery.r()))
+    }
+
+    fn is_zero(&self) -> bool {
+        self.val.ct_eq(&Uint::ZERO) == 1
+    }
+
+    fn checked_inverse(&self) -> Result<Self, FieldError> {
+        if self.is_zero() {
+            return Err(FieldError::NotInvertible);
+        }
+        // a^(p - 2) = a^-1 by Fermat's little theorem
+        let exp = self.context.p.magnitude() - 2u32;
+        Ok(self.pow_secure(&exp, self.context.p.magnitude()))
+    }
+
+    fn pow(&self, exp: &BigUint) -> Self {
+        let mut base = *self;
+        let mut result = Self::one(self.context);
+
+        for shift in 0..exp.bits() {
+            if exp.bit(shift) {
+                result = result * base;
+            }
+            base = base * base;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::fp::FpElement;
+    use num::bigint::RandBigInt;
+    use num::{One, Zero};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn p_256() -> BigInt {
+        (BigInt::one() << 256) - (BigInt::one() << 224)
+            + (BigInt::one() << 192)
+            + (BigInt::one() << 96)
+            - 1
+    }
+
+    fn p_384() -> BigInt {
+        (BigInt::one() << 384) - (BigInt::one() << 128) - (BigInt::one() << 96)
+            + (BigInt::one() << 32)
+            - 1
+    }
+
+    fn p_521() -> BigInt {
+        (BigInt::one() << 521) - 1
+    }
+
+    /// Checks every operation against `FpElement` on random values.
+    fn check_against_fp<const N: usize>(p: BigInt) {
+        let ctx = FieldContext::new_prime(p.clone())
+            .unwrap()
+            .with_montgomery();
+        let mut rng = StdRng::seed_from_u64(N as u64);
+
+        let (zero, one) = (FpCtElement::<N>::zero(&ctx), FpCtElement::<N>::one(&ctx));
+        assert_eq!(zero, FpCtElement::new(&ctx, BigInt::zero()));
+        assert_eq!(one, FpCtElement::new(&ctx, BigInt::one()));
+        assert_eq!(one.value(), BigInt::one());
+
+        let mut values = vec![BigInt::zero(), BigInt::one(), &p - 1];
+        values.extend((0..8).map(|_| rng.gen_bigint_range(&BigInt::zero(), &p)));
+
+        for a in &values {
+            let (x, x_ct) = (
+                FpElement::new(&ctx, a.clone()),
+                FpCtElement::<N>::new(&ctx, a.clone()),
+            );
+            assert_eq!(x_ct.value(), *a);
+            assert_eq!((-x_ct).value(), (-&x).value());
+
+            for b in &values[..4] {
+                let (y, y_ct) = (
+                    FpElement::new(&ctx, b.clone()),
+                    FpCtElement::<N>::new(&ctx, b.clone()),
+                );
+                assert_eq!((x_ct + y_ct).value(), (&x + &y).value());
+                assert_eq!((x_ct - y_ct).value(), (&x - &y).value());
+                assert_eq!((x_ct * y_ct).value(), (&x * &y).value());
+                if !y.is_zero() {
+                    assert_eq!((x_ct / y_ct).value(), (&x / &y).value());
+                }
+            }
+
+            let order = p.magnitude() - 1u32;
+            let exp = rng.gen_biguint_below(&order);
+            assert_eq!(x_ct.pow(&exp).value(), x.pow(&exp).value());
+            assert_eq!(
+                x_ct.pow_secure(&exp, &order).value(),
+                x.pow_secure(&exp, &order).value()
+            );
+        }
+    }
+
+    #[test]
+    fn test_fp_ct_matches_fp() {
+        check_against_fp::<4>(p_256());
+        check_against_fp::<4>((BigInt::one() << 255) - 19);
+        check_against_fp::<6>(p_384());
+        check_against_fp::<9>(p_521());
+    }
+
+    #[test]
+    fn test_fp_ct_diffie_hellman() {
+        let ctx = FieldContext::new_prime(p_384()).unwrap().with_montgomery();
+        let order = p_384().magnitude() - 1u32;
+        let g = Fp384Element::new(&ctx, 5.into());
+
+        let a = BigUint::from(0xdeadbeefu32) << 300u32;
+        let b = BigUint::from(0xcafebabeu32) << 200u32;
+        let a_secret = g.pow_secure(&b, &order).pow_secure(&a, &order);
+        let b_secret = g.pow_secure(&a, &order).pow_secure(&b, &order);
+
+        assert_eq!(a_secret, b_secret);
+        assert_eq!(g.pow(&order), Fp384Element::one(&ctx));
+        assert_eq!(
+            Fp384Element::zero(&ctx).checked_inverse(),
+            Err(FieldError::NotInvertible)
+        );
+    }
+
+    #[test]
+    fn test_fp_ct_context_must_fit() {
+        let ctx = FieldContext::new_prime(p_256()).unwrap();
+        assert!(Fp256Element::try_new(&ctx, &BigInt::one()).is_err());
+
+        assert!(std::panic::catch_unwind(|| Fp256Element::one(&ctx)).is_err());
+
+        let ctx = ctx.with_montgomery();
+        assert!(Fp256Element::try_new(&ctx, &BigInt::one()).is_ok());
+        assert!(matches!(
+            Fp384Element::try_new(&ctx, &BigInt::one()),
+            Err(FieldError::InvalidModulus(_))
+        ));
+    }
+}
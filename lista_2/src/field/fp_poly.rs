@@ -3,8 +3,10 @@ use num::{BigUint, One, Zero};
 use std::fmt;
 use std::ops::{Add, Div, Index, IndexMut, Mul, Neg, Sub};
 
+use super::ct::{ladder, Choice, ConditionallySelectable};
 use super::fp::FpElement;
-use crate::{FieldContext, FieldElement};
+use super::montgomery::Montgomery;
+use crate::{FieldContext, FieldElement, FieldError};
 
 /// Polynomial-based field extension element: F_{p^k}.
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -51,8 +53,14 @@ impl fmt::Display for FpPolynomialElement<'_> {
 
 impl<'a> FpPolynomialElement<'a> {
     pub fn new(ctx: &'a FieldContext, coeffs: Vec<FpElement<'a>>) -> Self {
+        Self::try_new(ctx, coeffs).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Like `new`, but fails instead of panicking if `ctx` is not a
+    /// polynomial field.
+    pub fn try_new(ctx: &'a FieldContext, coeffs: Vec<FpElement<'a>>) -> Result<Self, FieldError> {
         if !ctx.is_poly() {
-            panic!("Field context is not a polynomial field");
+            return Err(FieldError::WrongField);
         }
 
         let k = ctx.get_irreducible_poly_degree();
@@ -61,7 +69,7 @@ impl<'a> FpPolynomialElement<'a> {
             coeffs,
         };
         el.normalize(k);
-        el
+        Ok(el)
     }
 
     pub fn from_vec(context: &'a FieldContext, coeffs: Vec<i64>) -> Self {
@@ -167,15 +175,19 @@ impl<'a> FpPolynomialElement<'a> {
         ctx: &'a FieldContext,
         a: &[FpElement<'a>],
         b: &[FpElement<'a>],
-    ) -> (Vec<FpElement<'a>>, Vec<FpElement<'a>>) {
-        if Self::poly_is_zero(b) {
-            panic!("Division by zero polynomial");
-        }
+    ) -> Result<(Vec<FpElement<'a>>, Vec<FpElement<'a>>), FieldError> {
+        // the leading coefficient has to be the highest non-zero one
+        let Some(degree) = b.iter().rposition(|c| !c.is_zero()) else {
+            return Err(FieldError::DivisionByZero);
+        };
+        let b = &b[..=degree];
+        let lead_inv = b[degree].checked_inverse()?;
+
         let mut aa = a.to_vec();
         let mut qq = Vec::<FpElement<'a>>::new();
 
         while aa.len() >= b.len() && !Self::poly_is_zero(&aa) {
-            let factor = &aa[aa.len() - 1] * &b[b.len() - 1].inverse();
+            let factor = &aa[aa.len() - 1] * &lead_inv;
             let deg_diff = aa.len() - b.len();
             let mut temp = vec![FpElement::zero(ctx); deg_diff];
             for c in b {
@@ -198,10 +210,13 @@ impl<'a> FpPolynomialElement<'a> {
             aa.push(FpElement::zero(ctx));
         }
 
-        (qq, aa)
+        Ok((qq, aa))
     }
 
-    fn poly_inv(a: &[FpElement<'a>], ctx: &'a FieldContext) -> Vec<FpElement<'a>> {
+    fn poly_inv(
+        a: &[FpElement<'a>],
+        ctx: &'a FieldContext,
+    ) -> Result<Vec<FpElement<'a>>, FieldError> {
         let irreducible_poly = Self::poly_to_fp(ctx, &ctx.irreducible_poly);
         let mut r0 = irreducible_poly.clone();
         let mut r1 = a.to_vec();
@@ -211,7 +226,7 @@ impl<'a> FpPolynomialElement<'a> {
         let mut t1 = vec![FpElement::one(ctx)];
 
         while !Self::poly_is_zero(&r1) {
-            let (q, r) = Self::poly_div(ctx, &r0, &r1);
+            let (q, r) = Self::poly_div(ctx, &r0, &r1)?;
             let r2 = r.clone();
             let s2 = Self::poly_sub(ctx, &s0, &Self::poly_mul(ctx, &q, &s1));
             let t2 = Self::poly_sub(ctx, &t0, &Self::poly_mul(ctx, &q, &t1));
@@ -224,9 +239,13 @@ impl<'a> FpPolynomialElement<'a> {
             t1 = t2;
         }
 
-        let inv_lead = r0.last().unwrap().inverse();
+        // r0 is the gcd, a non-zero constant if `a` is invertible
+        if r0.iter().skip(1).any(|c| !c.is_zero()) {
+            return Err(FieldError::NotInvertible);
+        }
+        let inv_lead = r0[0].checked_inverse()?;
         let inv = Self::poly_mul(ctx, &t0, &[inv_lead]);
-        Self::poly_mod(&inv, ctx)
+        Ok(Self::poly_mod(&inv, ctx))
     }
 }
 
@@ -372,18 +391,41 @@ impl<'a> Mul for &FpPolynomialElement<'a> {
 impl<'a> Div for FpPolynomialElement<'a> {
     type Output = FpPolynomialElement<'a>;
     fn div(self, other: FpPolynomialElement<'a>) -> FpPolynomialElement<'a> {
-        self * other.inverse()
+        &self / &other
     }
 }
 
 impl<'a> Div for &FpPolynomialElement<'a> {
     type Output = FpPolynomialElement<'a>;
     fn div(self, other: &FpPolynomialElement<'a>) -> FpPolynomialElement<'a> {
-        self * &FpPolynomialElement::inverse(other)
+        self.checked_div(other)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+}
+
+impl ConditionallySelectable for FpPolynomialElement<'_> {
+    /// Coefficient by coefficient, over all k of them.
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        let ctx = a.context;
+        let k = ctx.get_irreducible_poly_degree();
+        let zero = FpElement::zero(ctx);
+        let coeff = |poly: &Self, i: usize| poly.coeffs.get(i).unwrap_or(&zero).clone();
+
+        let coeffs = (0..k.max(a.coeffs.len()).max(b.coeffs.len()))
+            .map(|i| FpElement::conditional_select(&coeff(a, i), &coeff(b, i), choice))
+            .collect();
+        FpPolynomialElement {
+            context: ctx,
+            coeffs,
+        }
     }
 }
 
 impl<'a> FieldElement<'a> for FpPolynomialElement<'a> {
+    fn context(&self) -> &'a FieldContext {
+        self.context
+    }
+
     fn zero(ctx: &'a FieldContext) -> Self {
         let k = ctx.get_irreducible_poly_degree();
         let coeffs = vec![FpElement::zero(ctx); k];
@@ -407,16 +449,16 @@ impl<'a> FieldElement<'a> for FpPolynomialElement<'a> {
         self.coeffs.iter().all(|c| c.is_zero())
     }
 
-    fn inverse(&self) -> Self {
+    fn checked_inverse(&self) -> Result<Self, FieldError> {
         let ctx = self.context;
-        let inv_poly = Self::poly_inv(&self.coeffs, ctx);
+        let inv_poly = Self::poly_inv(&self.coeffs, ctx)?;
         let k = ctx.get_irreducible_poly_degree();
         let mut res = FpPolynomialElement {
             context: ctx,
             coeffs: inv_poly,
         };
         res.normalize(k);
-        res
+        Ok(res)
     }
 
     fn pow(&self, exp: &BigUint) -> Self {
@@ -436,24 +478,111 @@ impl<'a> FieldElement<'a> for FpPolynomialElement<'a> {
         result
     }
 
-    fn pow_secure(&self, exp: &BigUint, subgroup_order: &BigUint) -> Self {
+    /// The ladder on `PolyLimbs`, whose products take the same steps for
+    /// every value. For p = 2, which has no Montgomery form, it runs on the
+    /// coefficients' `BigInt`s instead.
+    fn pow_ct(&self, exp: &BigUint, bits: u64) -> Self {
         let ctx = self.context;
-        let mut base = self.clone();
-        let mut result = Self::one(ctx);
-        let mut dummy = Self::one(ctx);
+        let Some(limbs) = PolyLimbs::new(ctx) else {
+            return ladder(self, Self::one(ctx), exp, bits, |a, b| a * b, |a| a * a);
+        };
+
+        let base = limbs.limbs_of(&self.coeffs);
+        let result = ladder(
+            &base,
+            limbs.limbs_of(&Self::one(ctx).coeffs),
+            exp,
+            bits,
+            |a, b| limbs.mul(a, b),
+            |a| limbs.mul(a, a),
+        );
+        FpPolynomialElement {
+            context: ctx,
+            coeffs: limbs.coeffs_of(ctx, &result),
+        }
+    }
+}
 
-        let exp = exp % subgroup_order;
+/// F_{p^k} on fixed-width limbs, for `pow_ct`: an element is its k
+/// coefficients in Montgomery form, n limbs each, lowest first.
+struct PolyLimbs {
+    montgomery: Montgomery,
+    /// The coefficients f_0..f_(k-1) of the monic modulus.
+    modulus: Vec<u64>,
+    k: usize,
+    n: usize,
+}
 
-        for shift in 0..subgroup_order.bits() {
-            if ((&exp >> shift) & BigUint::one()) == BigUint::one() {
-                result = &result * &base;
-            } else {
-                dummy = &dummy * &base;
+impl PolyLimbs {
+    /// The limbs of `ctx`, with its Montgomery constants or ones made for
+    /// them; `None` for p = 2.
+    fn new(ctx: &FieldContext) -> Option<Self> {
+        let montgomery = match &ctx.montgomery {
+            Some(montgomery) => montgomery.clone(),
+            None => Montgomery::new(ctx.p.magnitude())?,
+        };
+        let k = ctx.get_irreducible_poly_degree();
+        let n = montgomery.p().len();
+
+        let mut limbs = Self {
+            montgomery,
+            modulus: Vec::new(),
+            k,
+            n,
+        };
+        let modulus = FpPolynomialElement::poly_to_fp(ctx, &ctx.irreducible_poly[..k]);
+        limbs.modulus = limbs.limbs_of(&modulus);
+        Some(limbs)
+    }
+
+    /// The first k `coeffs`, zero-padded.
+    fn limbs_of(&self, coeffs: &[FpElement]) -> Vec<u64> {
+        let mut limbs = Vec::with_capacity(self.k * self.n);
+        for i in 0..self.k {
+            match coeffs.get(i) {
+                Some(c) => limbs.extend(self.montgomery.to_montgomery(c.value().magnitude())),
+                None => limbs.resize(limbs.len() + self.n, 0),
             }
+        }
+        limbs
+    }
 
-            base = &base * &base;
+    /// The elements of Fp that `limbs` holds.
+    fn coeffs_of<'a>(&self, ctx: &'a FieldContext, limbs: &[u64]) -> Vec<FpElement<'a>> {
+        limbs
+            .chunks(self.n)
+            .map(|c| FpElement::new(ctx, self.montgomery.from_montgomery(c).into()))
+            .collect()
+    }
+
+    /// ab mod the modulus: all k^2 coefficient products, then the top k - 1
+    /// coefficients folded down by x^k = -(f_0 + ... + f_(k-1) x^(k-1)),
+    /// every step on all n limbs whatever they hold.
+    fn mul(&self, a: &[u64], b: &[u64]) -> Vec<u64> {
+        let (k, n, montgomery) = (self.k, self.n, &self.montgomery);
+        // the limbs of coefficient i
+        let at = |i: usize| i * n..(i + 1) * n;
+        let mut t = vec![0u64; (2 * k - 1) * n];
+        let mut product = vec![0u64; n];
+
+        for i in 0..k {
+            for j in 0..k {
+                montgomery.mul_into(&a[at(i)], &b[at(j)], &mut product);
+                montgomery.add_assign(&mut t[at(i + j)], &product);
+            }
         }
-        result
+
+        let mut top = vec![0u64; n];
+        for i in (k..2 * k - 1).rev() {
+            top.copy_from_slice(&t[at(i)]);
+            for j in 0..k {
+                montgomery.mul_into(&top, &self.modulus[at(j)], &mut product);
+                montgomery.sub_assign(&mut t[at(i - k + j)], &product);
+            }
+        }
+
+        t.truncate(k * n);
+        t
     }
 }
 
@@ -470,7 +599,7 @@ mod tests {
             1.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
         // poly_a = 2+3x, poly_b=5+x
         let poly_a = FpPolynomialElement::new(
             &ctx,
@@ -490,8 +619,8 @@ mod tests {
 
         // a+b = (2+5)+(3+1)x=7+4x
         let sum = &poly_a + &poly_b;
-        assert_eq!(sum[0].val, 7.to_bigint().unwrap());
-        assert_eq!(sum[1].val, 4.to_bigint().unwrap());
+        assert_eq!(sum[0].value(), 7.to_bigint().unwrap());
+        assert_eq!(sum[1].value(), 4.to_bigint().unwrap());
     }
 
     #[test]
@@ -502,7 +631,7 @@ mod tests {
             1.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
         let poly_a = FpPolynomialElement::new(
             &ctx,
             vec![
@@ -521,8 +650,8 @@ mod tests {
 
         // b-a = (5-2) + (1-3)x = 3 + (-2)x =3+15x mod17
         let diff = &poly_b - &poly_a;
-        assert_eq!(diff[0].val, 3.to_bigint().unwrap());
-        assert_eq!(diff[1].val, 15.to_bigint().unwrap());
+        assert_eq!(diff[0].value(), 3.to_bigint().unwrap());
+        assert_eq!(diff[1].value(), 15.to_bigint().unwrap());
     }
 
     #[test]
@@ -533,7 +662,7 @@ mod tests {
             1.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
         let poly_a = FpPolynomialElement::new(
             &ctx,
             vec![
@@ -552,8 +681,8 @@ mod tests {
 
         let prod = &poly_a * &poly_b;
         // Expected result from previous reasoning: 7 + 0*x
-        assert_eq!(prod[0].val, 1.to_bigint().unwrap());
-        assert_eq!(prod[1].val, 14.to_bigint().unwrap());
+        assert_eq!(prod[0].value(), 1.to_bigint().unwrap());
+        assert_eq!(prod[1].value(), 14.to_bigint().unwrap());
 
         // Check multiplication by zero polynomial
         let zero_poly = FpPolynomialElement::zero(&ctx);
@@ -570,7 +699,7 @@ mod tests {
             1.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
         let poly_a = FpPolynomialElement::new(
             &ctx,
             vec![
@@ -581,8 +710,8 @@ mod tests {
 
         let neg_a = -&poly_a;
         // -2 mod17=15, -3mod17=14
-        assert_eq!(neg_a[0].val, 15.to_bigint().unwrap());
-        assert_eq!(neg_a[1].val, 14.to_bigint().unwrap());
+        assert_eq!(neg_a[0].value(), 15.to_bigint().unwrap());
+        assert_eq!(neg_a[1].value(), 14.to_bigint().unwrap());
 
         let zero_poly = FpPolynomialElement::zero(&ctx);
         assert!((-&zero_poly).is_zero());
@@ -596,7 +725,7 @@ mod tests {
             1.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
         let poly_b = FpPolynomialElement::new(
             &ctx,
             vec![
@@ -620,7 +749,7 @@ mod tests {
             1.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
         let poly_a = FpPolynomialElement::new(
             &ctx,
             vec![
@@ -642,6 +771,63 @@ mod tests {
         assert_eq!(check_div, poly_a);
     }
 
+    #[test]
+    fn test_polynomial_checked_inverse() {
+        let p = 17.to_bigint().unwrap();
+        let irreducible_poly = vec![
+            3.to_bigint().unwrap(),
+            1.to_bigint().unwrap(),
+            1.to_bigint().unwrap(),
+        ];
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
+
+        let zero = FpPolynomialElement::zero(&ctx);
+        let one = FpPolynomialElement::one(&ctx);
+        assert_eq!(zero.checked_inverse(), Err(FieldError::NotInvertible));
+        assert_eq!(one.checked_div(&zero), Err(FieldError::DivisionByZero));
+
+        // a constant, whose coefficient of x is a zero the inverse must skip
+        let five = FpPolynomialElement::from_vec(&ctx, vec![5]);
+        assert_eq!(&five * &five.checked_inverse().unwrap(), one);
+
+        let prime_ctx = FieldContext::new_prime(17.to_bigint().unwrap()).unwrap();
+        assert_eq!(
+            FpPolynomialElement::try_new(&prime_ctx, vec![]),
+            Err(FieldError::WrongField)
+        );
+    }
+
+    #[test]
+    fn test_polynomial_reducible_modulus() {
+        // x^2 - 1 = (x - 1)(x + 1) does not define a field
+        let p = 17.to_bigint().unwrap();
+        let irreducible_poly = vec![
+            16.to_bigint().unwrap(),
+            0.to_bigint().unwrap(),
+            1.to_bigint().unwrap(),
+        ];
+        assert!(matches!(
+            FieldContext::new_poly(p.clone(), irreducible_poly),
+            Err(FieldError::InvalidModulus(_))
+        ));
+
+        // (x^2 + x + 3)^2 has no roots, but is no more irreducible
+        let square = [3, 1, 1]
+            .iter()
+            .flat_map(|&a| [3, 1, 1].map(move |b| (a, b)))
+            .enumerate()
+            .fold(vec![0i64; 5], |mut square, (idx, (a, b))| {
+                square[idx / 3 + idx % 3] += a * b;
+                square
+            });
+        let square = square.iter().map(|c| c.to_bigint().unwrap()).collect();
+        assert!(FieldContext::new_poly(p.clone(), square).is_err());
+
+        // a polynomial of degree 1 always is
+        let linear = vec![5.to_bigint().unwrap(), 1.to_bigint().unwrap()];
+        assert!(FieldContext::new_poly(p, linear).is_ok());
+    }
+
     #[test]
     fn test_polynomial_exponentiation() {
         let p = 17.to_bigint().unwrap();
@@ -650,7 +836,7 @@ mod tests {
             1.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
 
         let poly_a = FpPolynomialElement::new(
             &ctx,
@@ -718,7 +904,7 @@ mod tests {
             1.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
 
         // poly = 0
         let zero_poly = FpPolynomialElement::zero(&ctx);
@@ -751,7 +937,7 @@ mod tests {
             1.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
 
         // Another polynomial
         let poly_x_only = FpPolynomialElement::new(
@@ -763,8 +949,8 @@ mod tests {
         ); // x
            // x*x = x^2 = -1 mod poly => = p-1=16
         let prod = &poly_x_only * &poly_x_only;
-        assert_eq!(prod[0].val, 14.to_bigint().unwrap());
-        assert_eq!(prod[1].val, 16.to_bigint().unwrap());
+        assert_eq!(prod[0].value(), 14.to_bigint().unwrap());
+        assert_eq!(prod[1].value(), 16.to_bigint().unwrap());
 
         // Check that a polynomial times its inverse gives one
         let poly_rand = FpPolynomialElement::new(
@@ -793,7 +979,7 @@ mod tests {
             4.to_bigint().unwrap(),
             1.to_bigint().unwrap(),
         ];
-        let ctx = FieldContext::new_poly(p, irreducible_poly);
+        let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
 
         let p1 = FpPolynomialElement::from_vec(&ctx, vec![8, 6, 7, 7, 3, 9, 1]);
         let p2 = FpPolynomialElement::from_vec(&ctx, vec![3, 7, 0, 3, 4, 2, 4]);
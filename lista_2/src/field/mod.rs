@@ -1,3 +1,7 @@
 pub mod fp;
 pub mod fp_poly;
 pub mod f2_poly;
+pub mod ct;
+pub mod fp_ct;
+pub mod montgomery;
+pub mod uint;
@@ -0,0 +1,321 @@
+// Montgomery arithmetic modulo an odd p. An element a is kept as aR mod p,
+// with R = 2^(64n) for the n 64-bit limbs of p, and the reduction after a
+// product is REDC: n multiply-and-add passes over the limbs and a shift,
+// instead of a BigInt division. Getting in and out of this form costs a
+// product each, so it is used for chains of products like exponentiation,
+// which convert only the base and the result, and for elements of Fp that
+// stay in Montgomery form from construction to output. For those, sums and
+// differences are taken on the limbs as well, with a conditional subtraction
+// of p, and a product allocates only the limbs of its result.
+
+use num::BigUint;
+
+use super::ct::mask;
+use super::uint::{adc, mac, sbb};
+
+/// The constants of Montgomery arithmetic for one odd modulus.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Montgomery {
+    modulus: BigUint,
+    /// p, least significant limb first.
+    p: Vec<u64>,
+    /// -p^-1 mod 2^64, the limb of -p^-1 mod R that REDC uses.
+    p_inv: u64,
+    /// R mod p, the Montgomery form of 1.
+    r: Vec<u64>,
+    /// R^2 mod p, which takes elements into Montgomery form.
+    r2: Vec<u64>,
+}
+
+impl Montgomery {
+    /// The constants for `p`, or `None` if `p` is even or less than 3.
+    pub fn new(p: &BigUint) -> Option<Self> {
+        if !p.bit(0) || p.bits() < 2 {
+            return None;
+        }
+
+        let p_limbs = p.to_u64_digits();
+        let n = p_limbs.len();
+        let r = BigUint::from(1u32) << (64 * n);
+
+        // Newton's iteration doubles the correct low bits of p^-1 mod 2^64,
+        // starting from the 3 that p * p = 1 (mod 8) gives
+        let mut inv = p_limbs[0];
+        for _ in 0..5 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(p_limbs[0].wrapping_mul(inv)));
+        }
+
+        let mut montgomery = Self {
+            modulus: p.clone(),
+            p: p_limbs,
+            p_inv: inv.wrapping_neg(),
+            r: Vec::new(),
+            r2: Vec::new(),
+        };
+        montgomery.r = montgomery.limbs(&(&r % p));
+        montgomery.r2 = montgomery.limbs(&(&r * &r % p));
+
+        Some(montgomery)
+    }
+
+    /// `a`, which is less than R, padded to the limbs of p.
+    pub fn limbs(&self, a: &BigUint) -> Vec<u64> {
+        let mut limbs = a.to_u64_digits();
+        limbs.resize(self.p.len(), 0);
+        limbs
+    }
+
+    /// The Montgomery form of 1.
+    pub fn one(&self) -> Vec<u64> {
+        self.r().to_vec()
+    }
+
+    /// R mod p, the Montgomery form of 1.
+    pub fn r(&self) -> &[u64] {
+        &self.r
+    }
+
+    /// The limbs of p, least significant first.
+    pub fn p(&self) -> &[u64] {
+        &self.p
+    }
+
+    /// -p^-1 mod 2^64.
+    pub fn p_inv(&self) -> u64 {
+        self.p_inv
+    }
+
+    /// R^2 mod p.
+    pub fn r2(&self) -> &[u64] {
+        &self.r2
+    }
+
+    /// aR mod p, for any `a`.
+    pub fn to_montgomery(&self, a: &BigUint) -> Vec<u64> {
+        self.mul(&self.limbs(&(a % &self.modulus)), &self.r2)
+    }
+
+    /// a mod p for the Montgomery form `a`.
+    pub fn from_montgomery(&self, a: &[u64]) -> BigUint {
+        let mut t = a.to_vec();
+        t.resize(2 * self.p.len(), 0);
+
+        self.value(&self.redc(t))
+    }
+
+    /// The number with the limbs `a`, least significant first.
+    pub fn value(&self, a: &[u64]) -> BigUint {
+        BigUint::new(
+            a.iter()
+                .flat_map(|&limb| [limb as u32, (limb >> 32) as u32])
+                .collect(),
+        )
+    }
+
+    /// abR^-1 mod p, the Montgomery form of the product.
+    pub fn mul(&self, a: &[u64], b: &[u64]) -> Vec<u64> {
+        let mut t = vec![0u64; self.p.len()];
+        self.mul_into(a, b, &mut t);
+        t
+    }
+
+    /// Like `mul`, into `t`, which must not be `a` or `b`: the product,
+    /// reduced limb by limb as it is accumulated (CIOS).
+    pub fn mul_into(&self, a: &[u64], b: &[u64], t: &mut [u64]) {
+        let n = self.p.len();
+        t.fill(0);
+        // limb n of t
+        let mut top = 0;
+
+        for &x in a {
+            let carry = mul_add(t, b, x);
+            let (sum, overflow) = adc(top, carry, 0);
+
+            // t = (t + m * p) / 2^64, where m makes the low limb zero
+            let m = t[0].wrapping_mul(self.p_inv);
+            let (_, mut carry) = mac(t[0], m, self.p[0], 0);
+            for j in 1..n {
+                (t[j - 1], carry) = mac(t[j], m, self.p[j], carry);
+            }
+            (t[n - 1], carry) = adc(sum, carry, 0);
+            top = overflow + carry;
+        }
+
+        self.reduce_once(t, top);
+    }
+
+    /// a + b mod p into `a`, for a, b < p.
+    pub fn add_assign(&self, a: &mut [u64], b: &[u64]) {
+        let mut carry = 0;
+        for (a, &b) in a.iter_mut().zip(b) {
+            (*a, carry) = adc(*a, b, carry);
+        }
+        self.reduce_once(a, carry);
+    }
+
+    /// a - b mod p into `a`, for a, b < p.
+    pub fn sub_assign(&self, a: &mut [u64], b: &[u64]) {
+        let mut borrow = 0;
+        for (a, &b) in a.iter_mut().zip(b) {
+            (*a, borrow) = sbb(*a, b, borrow);
+        }
+
+        // adds p back if the difference wrapped
+        let mask = mask(borrow);
+        let mut carry = 0;
+        for (a, &p) in a.iter_mut().zip(&self.p) {
+            (*a, carry) = adc(*a, p & mask, carry);
+        }
+    }
+
+    /// t mod p for t < 2p, with `top` the bit of t above its n limbs: p is
+    /// subtracted under a mask, so both cases take the same steps.
+    fn reduce_once(&self, t: &mut [u64], top: u64) {
+        let mut borrow = 0;
+        for (&t, &p) in t.iter().zip(&self.p) {
+            (_, borrow) = sbb(t, p, borrow);
+        }
+
+        let mask = mask(top | (borrow ^ 1));
+        let mut borrow = 0;
+        for (t, &p) in t.iter_mut().zip(&self.p) {
+            (*t, borrow) = sbb(*t, p & mask, borrow);
+        }
+    }
+
+    /// Like `mul(a, a)`, computing each cross product once.
+    pub fn square(&self, a: &[u64]) -> Vec<u64> {
+        let n = self.p.len();
+        let mut t = vec![0u64; 2 * n];
+
+        for i in 0..n {
+            t[i + n] = mul_add(&mut t[2 * i + 1..i + n], &a[i + 1..], a[i]);
+        }
+
+        // the cross products count twice
+        let mut high = 0;
+        for limb in t.iter_mut() {
+            let next = *limb >> 63;
+            *limb = (*limb << 1) | high;
+            high = next;
+        }
+
+        let mut carry = 0u128;
+        for i in 0..n {
+            let square = a[i] as u128 * a[i] as u128;
+            let low = t[2 * i] as u128 + (square as u64) as u128 + carry;
+            t[2 * i] = low as u64;
+            let high = t[2 * i + 1] as u128 + (square >> 64) + (low >> 64);
+            t[2 * i + 1] = high as u64;
+            carry = high >> 64;
+        }
+
+        self.redc(t)
+    }
+
+    /// tR^-1 mod p for t < pR, given as 2n limbs. The steps do not depend
+    /// on t, so exponentiation can be made constant-time on top of it.
+    fn redc(&self, mut t: Vec<u64>) -> Vec<u64> {
+        let n = self.p.len();
+        // t + mp stays below 2pR: the bit over 2n limbs
+        let mut top = 0;
+
+        for i in 0..n {
+            // makes limb i of t zero
+            let m = t[i].wrapping_mul(self.p_inv);
+            let carry = mul_add(&mut t[i..i + n], &self.p, m);
+            let sum = t[i + n] as u128 + carry as u128 + top as u128;
+            t[i + n] = sum as u64;
+            top = (sum >> 64) as u64;
+        }
+
+        // the low limbs are zero now
+        t.drain(..n);
+        self.reduce_once(&mut t, top);
+        t
+    }
+}
+
+/// t += a * x for `t` and `a` of the same length, returning the carry out.
+fn mul_add(t: &mut [u64], a: &[u64], x: u64) -> u64 {
+    let mut carry = 0u128;
+    for (t, &a) in t.iter_mut().zip(a) {
+        let sum = *t as u128 + a as u128 * x as u128 + carry;
+        *t = sum as u64;
+        carry = sum >> 64;
+    }
+    carry as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::bigint::RandBigInt;
+    use num::{One, Zero};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn moduli() -> Vec<BigUint> {
+        let m127 = (BigUint::one() << 127u32) - 1u32;
+        let p_3072 = (BigUint::one() << 3072u32) - 47u32;
+
+        vec![
+            BigUint::from(3u32),
+            BigUint::from(17u32),
+            BigUint::from(u64::MAX - 58),
+            m127,
+            (BigUint::one() << 255u32) - 19u32,
+            p_3072,
+        ]
+    }
+
+    #[test]
+    fn test_montgomery_products() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for p in moduli() {
+            let montgomery = Montgomery::new(&p).unwrap();
+
+            let mut values = vec![BigUint::zero(), BigUint::one(), &p - 1u32];
+            values.extend((0..20).map(|_| rng.gen_biguint_below(&p)));
+
+            for a in &values {
+                let a_m = montgomery.to_montgomery(a);
+                assert_eq!(&montgomery.from_montgomery(&a_m), a);
+                assert_eq!(&montgomery.value(&montgomery.limbs(a)), a);
+                assert_eq!(
+                    montgomery.from_montgomery(&montgomery.square(&a_m)),
+                    a * a % &p
+                );
+
+                for b in &values[..5] {
+                    let b_m = montgomery.to_montgomery(b);
+                    let product = montgomery.mul(&a_m, &b_m);
+                    assert_eq!(montgomery.from_montgomery(&product), a * b % &p);
+
+                    let mut sum = a_m.clone();
+                    montgomery.add_assign(&mut sum, &b_m);
+                    assert_eq!(montgomery.from_montgomery(&sum), (a + b) % &p);
+                    let mut difference = a_m.clone();
+                    montgomery.sub_assign(&mut difference, &b_m);
+                    assert_eq!(montgomery.from_montgomery(&difference), (a + &p - b) % &p);
+                }
+            }
+
+            assert_eq!(
+                montgomery.from_montgomery(&montgomery.one()),
+                BigUint::one()
+            );
+            // values are reduced on the way in
+            let above = montgomery.to_montgomery(&(&p * 3u32 + 2u32));
+            assert_eq!(montgomery.from_montgomery(&above), BigUint::from(2u32));
+        }
+    }
+
+    #[test]
+    fn test_montgomery_needs_odd_modulus() {
+        assert!(Montgomery::new(&BigUint::from(2u32)).is_none());
+        assert!(Montgomery::new(&BigUint::from(1u32)).is_none());
+        assert!(Montgomery::new(&(BigUint::one() << 64u32)).is_none());
+    }
+}
@@ -0,0 +1,163 @@
+// Fixed-width unsigned integers of N 64-bit limbs, least significant limb
+// first, for arithmetic that neither allocates nor branches on the values:
+// carries and borrows are returned as 0 or 1 and choices are made with
+// masks instead of `if`s. Conversions from and to `BigUint` are for the
+// edges only.
+
+use num::{BigUint, Zero};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Uint<const N: usize> {
+    pub limbs: [u64; N],
+}
+
+/// a + b + carry, and the carry out.
+#[inline(always)]
+pub fn adc(a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let sum = a as u128 + b as u128 + carry as u128;
+    (sum as u64, (sum >> 64) as u64)
+}
+
+/// a - b - borrow, and the borrow out.
+#[inline(always)]
+pub fn sbb(a: u64, b: u64, borrow: u64) -> (u64, u64) {
+    let difference = (a as u128).wrapping_sub(b as u128 + borrow as u128);
+    (difference as u64, ((difference >> 64) as u64) & 1)
+}
+
+/// t + a * b + carry, and the carry out.
+#[inline(always)]
+pub fn mac(t: u64, a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let sum = t as u128 + a as u128 * b as u128 + carry as u128;
+    (sum as u64, (sum >> 64) as u64)
+}
+
+impl<const N: usize> Uint<N> {
+    pub const ZERO: Self = Self { limbs: [0; N] };
+
+    pub const ONE: Self = {
+        let mut limbs = [0; N];
+        limbs[0] = 1;
+        Self { limbs }
+    };
+
+    /// `limbs`, zero-padded; panics if there are more than N.
+    pub fn from_limbs(limbs: &[u64]) -> Self {
+        assert!(
+            limbs.len() <= N,
+            "{} limbs do not fit in {}",
+            limbs.len(),
+            N
+        );
+
+        let mut value = Self::ZERO;
+        value.limbs[..limbs.len()].copy_from_slice(limbs);
+        value
+    }
+
+    /// `value`, or `None` if it does not fit in N limbs.
+    pub fn from_biguint(value: &BigUint) -> Option<Self> {
+        let limbs = value.to_u64_digits();
+        (limbs.len() <= N).then(|| Self::from_limbs(&limbs))
+    }
+
+    pub fn to_biguint(&self) -> BigUint {
+        self.limbs
+            .iter()
+            .rev()
+            .fold(BigUint::zero(), |value, &limb| (value << 64u32) + limb)
+    }
+
+    /// self + other mod 2^(64N), and the carry out.
+    pub fn overflowing_add(&self, other: &Self) -> (Self, u64) {
+        let mut sum = Self::ZERO;
+        let mut carry = 0;
+        for i in 0..N {
+            (sum.limbs[i], carry) = adc(self.limbs[i], other.limbs[i], carry);
+        }
+        (sum, carry)
+    }
+
+    /// self - other mod 2^(64N), and the borrow out.
+    pub fn overflowing_sub(&self, other: &Self) -> (Self, u64) {
+        let mut difference = Self::ZERO;
+        let mut borrow = 0;
+        for i in 0..N {
+            (difference.limbs[i], borrow) = sbb(self.limbs[i], other.limbs[i], borrow);
+        }
+        (difference, borrow)
+    }
+
+    /// `b` if `choice` is 1, `a` if it is 0.
+    pub fn select(a: &Self, b: &Self, choice: u64) -> Self {
+        let mask = choice.wrapping_neg();
+        let mut value = *a;
+        for i in 0..N {
+            value.limbs[i] ^= mask & (a.limbs[i] ^ b.limbs[i]);
+        }
+        value
+    }
+
+    /// 1 if the values are equal, 0 otherwise.
+    pub fn ct_eq(&self, other: &Self) -> u64 {
+        let mut difference = 0;
+        for i in 0..N {
+            difference |= self.limbs[i] ^ other.limbs[i];
+        }
+        // the top bit of x | -x is set iff x is not zero
+        ((difference | difference.wrapping_neg()) >> 63) ^ 1
+    }
+
+    pub fn bit(&self, idx: usize) -> u64 {
+        (self.limbs[idx / 64] >> (idx % 64)) & 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::One;
+
+    #[test]
+    fn test_uint_carries() {
+        let max = Uint::<2> {
+            limbs: [u64::MAX; 2],
+        };
+
+        assert_eq!(max.overflowing_add(&Uint::ONE), (Uint::ZERO, 1));
+        assert_eq!(Uint::ZERO.overflowing_sub(&Uint::ONE), (max, 1));
+
+        let (sum, carry) = Uint::<2>::from_limbs(&[u64::MAX]).overflowing_add(&Uint::ONE);
+        assert_eq!((sum.limbs, carry), ([0, 1], 0));
+        assert_eq!(
+            sum.overflowing_sub(&Uint::ONE),
+            (Uint::from_limbs(&[u64::MAX]), 0)
+        );
+    }
+
+    #[test]
+    fn test_uint_select_and_eq() {
+        let a = Uint::<3>::from_limbs(&[1, 2, 3]);
+        let b = Uint::<3>::from_limbs(&[4, 5, 6]);
+
+        assert_eq!(Uint::select(&a, &b, 0), a);
+        assert_eq!(Uint::select(&a, &b, 1), b);
+        assert_eq!(a.ct_eq(&a), 1);
+        assert_eq!(a.ct_eq(&b), 0);
+        assert_eq!(
+            Uint::<3>::ZERO.ct_eq(&Uint::from_limbs(&[0, 0, 1 << 63])),
+            0
+        );
+        assert_eq!((a.bit(0), a.bit(1), a.bit(65)), (1, 0, 1));
+    }
+
+    #[test]
+    fn test_uint_conversions() {
+        let value: BigUint = (BigUint::one() << 130u32) + 7u32;
+
+        let uint = Uint::<3>::from_biguint(&value).unwrap();
+        assert_eq!(uint.limbs, [7, 0, 4]);
+        assert_eq!(uint.to_biguint(), value);
+        assert!(Uint::<2>::from_biguint(&value).is_none());
+    }
+}
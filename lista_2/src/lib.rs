@@ -1,9 +1,44 @@
 pub mod field;
+pub use leakage;
 
+use field::ct::{ladder, ConditionallySelectable};
+use field::f2_poly::F2PolynomialElement;
 use field::fp::FpElement;
-use num::{bigint::BigInt, BigUint, Zero};
+use field::fp_poly::FpPolynomialElement;
+use field::montgomery::Montgomery;
+use num::bigint::RandBigInt;
+use num::{bigint::BigInt, BigUint, Integer, One, Signed, Zero};
+use std::fmt;
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
+/// Why a field operation or the parameters of a field were rejected.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FieldError {
+    /// The element shares a factor with the modulus, e.g. it is zero.
+    NotInvertible,
+    DivisionByZero,
+    /// The modulus `p` of a prime field is not a prime.
+    NotPrime(BigInt),
+    /// The reduction polynomial cannot define an extension field.
+    InvalidModulus(String),
+    /// The context is for a different kind of field than the element.
+    WrongField,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::NotInvertible => write!(f, "no inverse exists"),
+            FieldError::DivisionByZero => write!(f, "division by zero"),
+            FieldError::NotPrime(p) => write!(f, "{} is not a prime", p),
+            FieldError::InvalidModulus(reason) => write!(f, "invalid modulus: {}", reason),
+            FieldError::WrongField => write!(f, "the element does not belong to this field"),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
 /// Trait for FieldElements over some field.
 /// Now it is lifetime-parameterized to ensure elements don't outlive their context.
 pub trait FieldElement<'a>:
@@ -15,48 +50,140 @@ pub trait FieldElement<'a>:
     + Div<Self, Output = Self>
     + Eq
     + Clone
+    + ConditionallySelectable
 {
+    fn context(&self) -> &'a FieldContext;
     fn zero(ctx: &'a FieldContext) -> Self;
     fn one(ctx: &'a FieldContext) -> Self;
     fn is_zero(&self) -> bool;
-    fn inverse(&self) -> Self;
+    fn checked_inverse(&self) -> Result<Self, FieldError>;
+
+    /// Like `checked_inverse`, but panics if there is no inverse.
+    fn inverse(&self) -> Self {
+        self.checked_inverse()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    fn checked_div(&self, other: &Self) -> Result<Self, FieldError> {
+        if other.is_zero() {
+            return Err(FieldError::DivisionByZero);
+        }
+        Ok(self.clone() * other.checked_inverse()?)
+    }
     fn pow(&self, exp: &BigUint) -> Self;
-    fn pow_secure(&self, exp: &BigUint, subgroup_order: &BigUint) -> Self;
+
+    /// self^exp by the Montgomery ladder over exactly `bits` bits, with
+    /// `exp` below 2^`bits`: the same products for every exponent, in an
+    /// order that does not depend on it.
+    fn pow_ct(&self, exp: &BigUint, bits: u64) -> Self {
+        ladder(
+            self,
+            Self::one(self.context()),
+            exp,
+            bits,
+            |a, b| a.clone() * b.clone(),
+            |a| a.clone() * a.clone(),
+        )
+    }
+
+    /// `pow_ct` over the bits of `subgroup_order`. `exp` must already be
+    /// reduced modulo the order, since the reduction would not be
+    /// constant-time; panics if it is not.
+    fn pow_secure(&self, exp: &BigUint, subgroup_order: &BigUint) -> Self {
+        assert!(
+            exp < subgroup_order,
+            "the exponent is not reduced modulo the subgroup order"
+        );
+        self.pow_ct(exp, subgroup_order.bits())
+    }
 }
 
 /// Holds the parameters of the field:
 /// - `p`: Prime modulus for Fp
 /// - `irreducible_poly`: coefficients of the irreducible polynomial for extension fields.
+/// - `montgomery`: the constants for Montgomery arithmetic in Fp, if enabled.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct FieldContext {
     pub p: BigInt,
     pub irreducible_poly: Vec<BigInt>,
     pub irreducible_binary_poly: BigUint,
+    pub montgomery: Option<Montgomery>,
 }
 
 impl FieldContext {
-    pub fn new_poly(p: BigInt, irreducible_poly: Vec<BigInt>) -> Self {
-        Self {
+    /// F_{p^k} for the monic `irreducible_poly` of degree k, coefficients
+    /// from the lowest. Reducible polynomials are rejected.
+    pub fn new_poly(p: BigInt, irreducible_poly: Vec<BigInt>) -> Result<Self, FieldError> {
+        check_prime(&p)?;
+        match irreducible_poly.last() {
+            _ if irreducible_poly.len() < 2 => {
+                return Err(FieldError::InvalidModulus(
+                    "the degree is less than 1".to_string(),
+                ))
+            }
+            Some(leading) if !leading.mod_floor(&p).is_one() => {
+                return Err(FieldError::InvalidModulus("it is not monic".to_string()))
+            }
+            _ => {}
+        }
+
+        let ctx = Self {
             p,
             irreducible_poly,
             irreducible_binary_poly: BigUint::zero(),
-        }
+            montgomery: None,
+        };
+
+        let q = ctx.p.to_biguint().expect("checked to be a prime");
+        check_irreducible(
+            FpPolynomialElement::from_vec(&ctx, vec![0, 1]),
+            ctx.get_irreducible_poly_degree(),
+            |a| a.pow(&q),
+        )?;
+
+        Ok(ctx)
     }
 
-    pub fn new_binary(irreducible_binary_poly: BigUint) -> Self {
-        Self {
+    /// F_{2^m} for `irreducible_binary_poly` of degree m, bit i being the
+    /// coefficient of x^i. Reducible polynomials are rejected.
+    pub fn new_binary(irreducible_binary_poly: BigUint) -> Result<Self, FieldError> {
+        if irreducible_binary_poly <= BigUint::one() {
+            return Err(FieldError::InvalidModulus(
+                "the degree is less than 1".to_string(),
+            ));
+        }
+
+        let ctx = Self {
             p: BigInt::from(2),
             irreducible_poly: vec![],
             irreducible_binary_poly,
-        }
+            montgomery: None,
+        };
+
+        // x, reduced in case the modulus is of degree 1
+        let x = F2PolynomialElement::new(&ctx, BigUint::from(2u32)).pow(&BigUint::one());
+        check_irreducible(x, ctx.get_irreducible_poly_degree(), |a| a * a)?;
+
+        Ok(ctx)
     }
 
-    pub fn new_prime(p: BigInt) -> Self {
-        Self {
+    pub fn new_prime(p: BigInt) -> Result<Self, FieldError> {
+        check_prime(&p)?;
+
+        Ok(Self {
             p,
             irreducible_poly: vec![],
             irreducible_binary_poly: BigUint::zero(),
-        }
+            montgomery: None,
+        })
+    }
+
+    /// Keeps the elements of Fp in Montgomery form, so that products and
+    /// exponentiation use REDC instead of a division. For p = 2, which has
+    /// no Montgomery form, the context is left as it is.
+    pub fn with_montgomery(mut self) -> Self {
+        self.montgomery = self.p.to_biguint().and_then(|p| Montgomery::new(&p));
+        self
     }
 
     pub fn is_binary(&self) -> bool {
@@ -68,14 +195,14 @@ impl FieldContext {
     }
 
     pub fn is_poly(&self) -> bool {
-        self.irreducible_poly.len() > 0
+        !self.irreducible_poly.is_empty()
     }
 
     fn get_irreducible_poly_degree(&self) -> usize {
         if self.is_binary() {
             get_binary_poly_degree(&self.irreducible_binary_poly)
         } else {
-            return self.irreducible_poly.len() - 1;
+            self.irreducible_poly.len() - 1
         }
     }
 }
@@ -88,3 +215,132 @@ pub fn get_binary_poly_degree(a: &BigUint) -> usize {
 
     (a.bits() - 1) as usize
 }
+
+/// Rabin's test of the modulus of a field extension of degree `degree` over
+/// F_q: it is irreducible iff x^(q^degree) = x and x^(q^(degree / r)) - x
+/// is invertible for every prime r dividing `degree`. `frobenius` raises an
+/// element to the q-th power.
+fn check_irreducible<'a, F: FieldElement<'a>>(
+    x: F,
+    degree: usize,
+    frobenius: impl Fn(&F) -> F,
+) -> Result<(), FieldError> {
+    let reducible = || Err(FieldError::InvalidModulus("it is reducible".to_string()));
+
+    // x^(q^j) for j = 0..=degree
+    let mut powers = vec![x.clone()];
+    for _ in 0..degree {
+        powers.push(frobenius(powers.last().unwrap()));
+    }
+
+    if powers[degree] != x {
+        return reducible();
+    }
+
+    let is_prime = |r: &usize| (2..*r).take_while(|d| d * d <= *r).all(|d| !r.is_multiple_of(&d));
+    for r in (2..=degree).filter(|r| degree.is_multiple_of(*r)).filter(is_prime) {
+        if (powers[degree / r].clone() - x.clone())
+            .checked_inverse()
+            .is_err()
+        {
+            return reducible();
+        }
+    }
+
+    Ok(())
+}
+
+/// Miller-Rabin rounds with random bases, on top of the fixed ones.
+const RANDOM_ROUNDS: usize = 16;
+
+/// Checks that `p` is a prime: trial division and Miller-Rabin with the
+/// first twelve primes as bases, which is exact below 3.3 * 10^24, and with
+/// random bases above, since `p` may come from an adversary.
+fn check_prime(p: &BigInt) -> Result<(), FieldError> {
+    const SMALL_PRIMES: [u32; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    let not_prime = || Err(FieldError::NotPrime(p.clone()));
+
+    let n = match p.to_biguint() {
+        Some(n) if !p.is_negative() && n > BigUint::one() => n,
+        _ => return not_prime(),
+    };
+
+    for small in SMALL_PRIMES {
+        if n == BigUint::from(small) {
+            return Ok(());
+        }
+        if (&n % small).is_zero() {
+            return not_prime();
+        }
+    }
+
+    // n - 1 = d * 2^s with d odd
+    let n_minus_one = &n - 1u32;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+
+    let is_witness = |a: &BigUint| {
+        let mut x = a.modpow(&d, &n);
+        if x.is_one() || x == n_minus_one {
+            return false;
+        }
+        for _ in 1..s {
+            x = &x * &x % &n;
+            if x == n_minus_one {
+                return false;
+            }
+        }
+        true
+    };
+
+    let mut rng = rand::thread_rng();
+    let random_bases =
+        (0..RANDOM_ROUNDS).map(|_| rng.gen_biguint_range(&BigUint::from(2u32), &n_minus_one));
+    let mut bases = SMALL_PRIMES
+        .iter()
+        .map(|&a| BigUint::from(a))
+        .chain(random_bases);
+
+    if bases.any(|a| is_witness(&a)) {
+        return not_prime();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_validation() {
+        assert!(FieldContext::new_prime(BigInt::from(17)).is_ok());
+        assert!(FieldContext::new_prime(BigInt::from(2)).is_ok());
+        // a strong pseudoprime to the bases 2, 3, 5 and 7
+        assert_eq!(
+            FieldContext::new_prime(BigInt::from(3215031751u64)),
+            Err(FieldError::NotPrime(BigInt::from(3215031751u64)))
+        );
+        assert!(FieldContext::new_prime(BigInt::from(1)).is_err());
+        assert!(FieldContext::new_prime(BigInt::from(-17)).is_err());
+
+        // the Mersenne prime 2^127 - 1
+        let m127: BigInt = (BigInt::one() << 127) - 1;
+        assert!(FieldContext::new_prime(m127.clone()).is_ok());
+        assert!(FieldContext::new_prime(&m127 * &m127).is_err());
+
+        let poly = |coeffs: &[i64]| coeffs.iter().map(|&c| BigInt::from(c)).collect();
+        assert!(FieldContext::new_poly(BigInt::from(17), poly(&[3, 1, 1])).is_ok());
+        // the leading coefficient only has to be 1 modulo p
+        assert!(FieldContext::new_poly(BigInt::from(17), poly(&[3, 1, 18])).is_ok());
+        assert!(matches!(
+            FieldContext::new_poly(BigInt::from(17), poly(&[3, 1, 2])),
+            Err(FieldError::InvalidModulus(_))
+        ));
+        assert!(FieldContext::new_poly(BigInt::from(17), poly(&[1])).is_err());
+        assert!(FieldContext::new_poly(BigInt::from(15), poly(&[3, 1, 1])).is_err());
+
+        assert!(FieldContext::new_binary(BigUint::from(0b111u32)).is_ok());
+        assert!(FieldContext::new_binary(BigUint::one()).is_err());
+    }
+}
@@ -1,13 +1,18 @@
-use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, DecodeError, Engine as _};
 use clap::{Parser, Subcommand};
 use diffie_hellman::field::f2_poly::F2PolynomialElement;
 use diffie_hellman::field::fp::FpElement;
+use diffie_hellman::field::fp_ct::Fp256Element;
 use diffie_hellman::field::fp_poly::FpPolynomialElement;
-use diffie_hellman::{FieldContext, FieldElement};
+use diffie_hellman::leakage::{self, Report, Verdict};
+use diffie_hellman::{FieldContext, FieldElement, FieldError};
 use num::bigint::{RandBigInt, Sign, ToBigInt, ToBigUint};
 use num::{BigInt, BigUint, One};
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::hint::black_box;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -18,8 +23,22 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// demo of the security of the implemented field elements
-    Security,
+    /// test of pow_secure for timing leaks, fixed against random exponents
+    Security {
+        /// number of timed exponentiations per field
+        #[arg(
+            long,
+            default_value_t = 10000,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new()
+                .range(leakage::MIN_SAMPLES as u64..)
+        )]
+        samples: usize,
+        /// directory to write the measurements to, one CSV per field
+        #[arg(long)]
+        csv: Option<PathBuf>,
+    },
+    /// benchmark of Diffie-Hellman in Fp with and without Montgomery arithmetic
+    Bench,
     /// Diffie-Hellman key exchange demo using Fp field
     Fp,
     /// Diffie-Hellman key exchange demo using F2Poly field
@@ -32,97 +51,96 @@ enum Commands {
     Solution,
 }
 
-fn calcualte_average(times: &[std::time::Duration]) -> f64 {
-    times.iter().map(|x| x.as_nanos() as f64).sum::<f64>() / times.len() as f64
+/// Why parameters sent by the server cannot be used.
+#[derive(Debug)]
+enum ParamError {
+    Field(FieldError),
+    Decode(DecodeError),
+    /// The group order leaves no secret exponent in [2, order).
+    Order(BigUint),
+    /// The degree of a binary field is not in [1, MAX_EXTENSION].
+    Extension(i32),
 }
 
-fn calculate_stddev(times: &[std::time::Duration], avg: f64) -> f64 {
-    times
-        .iter()
-        .map(|x| (x.as_nanos() as f64 - avg).powi(2))
-        .sum::<f64>()
-        .sqrt()
-        / times.len() as f64
-}
-
-fn showcase_security<'a, F: FieldElement<'a>>(a: F, order: &BigUint, title: &str) {
-    let exp_ones = BigUint::parse_bytes(b"FFFFFFFFFFFFFFFFFFFFFFFFFFFFFF", 16).unwrap();
-    let exp_zeros = BigUint::parse_bytes(b"800000000000000000000000000000", 16).unwrap();
-    let exp_half = BigUint::parse_bytes(b"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAA", 16).unwrap();
-    let exp_quarter = BigUint::parse_bytes(b"888888888888888888888888888888", 16).unwrap();
-    let exp_short = BigUint::parse_bytes(b"1", 16).unwrap();
-    let exp_long = order.clone() - BigUint::one();
-
-    const RUNS: u32 = 1000;
-
-    let mut times_ones = Vec::with_capacity(RUNS as usize);
-    let mut times_zeros = Vec::with_capacity(RUNS as usize);
-    let mut times_half = Vec::with_capacity(RUNS as usize);
-    let mut times_quarter = Vec::with_capacity(RUNS as usize);
-    let mut times_short = Vec::with_capacity(RUNS as usize);
-    let mut times_long = Vec::with_capacity(RUNS as usize);
-
-    for _ in 0..RUNS {
-        let now = std::time::Instant::now();
-        let _ = a.pow_secure(&exp_ones, order);
-        times_ones.push(now.elapsed());
+/// Largest degree of a binary field accepted from the server.
+const MAX_EXTENSION: i32 = 1 << 16;
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamError::Field(error) => write!(f, "{}", error),
+            ParamError::Decode(error) => write!(f, "invalid base64: {}", error),
+            ParamError::Order(order) => write!(f, "group order {} is too small", order),
+            ParamError::Extension(extension) => write!(
+                f,
+                "extension {} is not in [1, {}]",
+                extension, MAX_EXTENSION
+            ),
+        }
+    }
+}
 
-        let now = std::time::Instant::now();
-        let _ = a.pow_secure(&exp_zeros, order);
-        times_zeros.push(now.elapsed());
+impl From<FieldError> for ParamError {
+    fn from(error: FieldError) -> Self {
+        ParamError::Field(error)
+    }
+}
 
-        let now = std::time::Instant::now();
-        let _ = a.pow_secure(&exp_half, order);
-        times_half.push(now.elapsed());
+impl From<DecodeError> for ParamError {
+    fn from(error: DecodeError) -> Self {
+        ParamError::Decode(error)
+    }
+}
 
-        let now = std::time::Instant::now();
-        let _ = a.pow_secure(&exp_quarter, order);
-        times_quarter.push(now.elapsed());
+fn calcualte_average(times: &[std::time::Duration]) -> f64 {
+    times.iter().map(|x| x.as_nanos() as f64).sum::<f64>() / times.len() as f64
+}
 
-        let now = std::time::Instant::now();
-        let _ = a.pow_secure(&exp_short, order);
-        times_short.push(now.elapsed());
+/// The leakage test of `pow_secure` in the group of `a`: exponent 1
+/// against random ones below `order`.
+fn check_pow_secure<'a, F: FieldElement<'a>>(
+    name: &str,
+    a: F,
+    order: &BigUint,
+    samples: usize,
+) -> Report {
+    let mut rng = rand::thread_rng();
+    leakage::measure(
+        name,
+        samples,
+        &mut rng,
+        BigUint::one(),
+        |rng| rng.gen_biguint_below(order),
+        |exp| {
+            black_box(a.pow_secure(exp, order));
+        },
+    )
+}
 
-        let now = std::time::Instant::now();
-        let _ = a.pow_secure(&exp_long, order);
-        times_long.push(now.elapsed());
-    }
+fn security_demo(samples: usize, csv: Option<PathBuf>) {
+    let mut reports = Vec::new();
 
-    let ones_nanos_avg = calcualte_average(&times_ones);
-    let zeros_nanos_avg = calcualte_average(&times_zeros);
-    let half_nanos_avg = calcualte_average(&times_half);
-    let quarter_nanos_avg = calcualte_average(&times_quarter);
-    let short_nanos_avg = calcualte_average(&times_short);
-    let long_nanos_avg = calcualte_average(&times_long);
-
-    let ones_nanos_stddev = calculate_stddev(&times_ones, ones_nanos_avg);
-    let zeros_nanos_stddev = calculate_stddev(&times_zeros, zeros_nanos_avg);
-    let half_nanos_stddev = calculate_stddev(&times_half, half_nanos_avg);
-    let quarter_nanos_stddev = calculate_stddev(&times_quarter, quarter_nanos_avg);
-    let short_nanos_stddev = calculate_stddev(&times_short, short_nanos_avg);
-    let long_nanos_stddev = calculate_stddev(&times_long, long_nanos_avg);
-
-    println!("{title}");
-
-    println!("Ones: {}ns ± {}ns", ones_nanos_avg, ones_nanos_stddev);
-    println!("Zeros: {}ns ± {}ns", zeros_nanos_avg, zeros_nanos_stddev);
-    println!("Half: {}ns ± {}ns", half_nanos_avg, half_nanos_stddev);
-    println!(
-        "Quarter: {}ns ± {}ns",
-        quarter_nanos_avg, quarter_nanos_stddev
-    );
-    println!("Short: {}ns ± {}ns", short_nanos_avg, short_nanos_stddev);
-    println!("Long: {}ns ± {}ns", long_nanos_avg, long_nanos_stddev);
-}
-
-fn security_demo() {
     let p = 17.to_bigint().unwrap();
-    let ctx = FieldContext::new_prime(p);
+    let ctx = FieldContext::new_prime(p).unwrap();
 
     let a = FpElement::new(&ctx, 7.to_bigint().unwrap());
     let order = 16.to_biguint().unwrap();
 
-    showcase_security(a, &order, "FP");
+    reports.push(check_pow_secure("FP", a, &order, samples));
+
+    let p: BigInt = (BigInt::one() << 255) - 19;
+    let ctx = FieldContext::new_prime(p.clone())
+        .unwrap()
+        .with_montgomery();
+
+    let a = FpElement::new(&ctx, 5.to_bigint().unwrap());
+    let order = p.to_biguint().unwrap() - 1u32;
+
+    reports.push(check_pow_secure("FP MONTGOMERY", a, &order, samples));
+
+    let a = Fp256Element::new(&ctx, 5.to_bigint().unwrap());
+
+    reports.push(check_pow_secure("FP 256", a, &order, samples));
 
     let p = 11.to_bigint().unwrap();
     let irreducible_poly = vec![
@@ -135,29 +153,184 @@ fn security_demo() {
         4.to_bigint().unwrap(),
         1.to_bigint().unwrap(),
     ];
-    let ctx = FieldContext::new_poly(p, irreducible_poly);
+    let ctx = FieldContext::new_poly(p, irreducible_poly).unwrap();
 
     let a = FpPolynomialElement::from_vec(&ctx, vec![8, 6, 7, 7, 3, 9, 1]);
 
     let order = BigUint::from(19487170u32);
 
-    showcase_security(a, &order, "FP POLY");
+    reports.push(check_pow_secure("FP POLY", a, &order, samples));
 
     let irreducible_poly = BigUint::from(0b11111101111101001u64);
-    let ctx = FieldContext::new_binary(irreducible_poly);
+    let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
     let a = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
 
     let order = BigUint::from(65535u32);
 
-    showcase_security(a, &order, "F2 POLY");
+    reports.push(check_pow_secure("F2 POLY", a, &order, samples));
+
+    for report in &reports {
+        println!("{}", report);
+    }
+
+    if let Some(dir) = csv {
+        std::fs::create_dir_all(&dir).unwrap();
+        for report in &reports {
+            let name = report.name.to_lowercase().replace(' ', "_");
+            let file = std::fs::File::create(dir.join(format!("{}.csv", name))).unwrap();
+            report.write_csv(std::io::BufWriter::new(file)).unwrap();
+        }
+    }
+
+    if reports
+        .iter()
+        .any(|report| report.verdict() != Verdict::NoLeak)
+    {
+        std::process::exit(1);
+    }
+}
+
+/// Times the exponentiation of a Diffie-Hellman key exchange, a full-size
+/// exponent modulo p - 1, in `ctx`. `pow_secure` runs on Montgomery limbs
+/// with or without the context's constants, so this times `pow`.
+fn time_diffie_hellman(ctx: &FieldContext, exps: &[BigUint]) -> f64 {
+    let g = FpElement::new(ctx, 5.to_bigint().unwrap());
+
+    let mut times = Vec::with_capacity(exps.len());
+    for exp in exps {
+        let now = std::time::Instant::now();
+        let _ = black_box(g.pow(exp));
+        times.push(now.elapsed());
+    }
+
+    calcualte_average(&times)
+}
+
+/// A point (X : Y : Z) in Jacobian coordinates, (X / Z^2, Y / Z^3) in affine.
+type Jacobian<'a> = (FpElement<'a>, FpElement<'a>, FpElement<'a>);
+
+/// 2P on y^2 = x^3 - 3x + b, the doubling of dbl-2001-b.
+fn double_jacobian<'a>((x, y, z): &Jacobian<'a>) -> Jacobian<'a> {
+    let delta = z.square();
+    let gamma = y.square();
+    let beta = x * &gamma;
+    let alpha = &(x - &delta) * &(x + &delta);
+    let alpha = &(&alpha + &alpha) + &alpha;
+
+    let beta_4 = &(&beta + &beta) + &(&beta + &beta);
+    let x_3 = &alpha.square() - &(&beta_4 + &beta_4);
+    let z_3 = &(&(y + z).square() - &gamma) - &delta;
+    let gamma_2 = gamma.square();
+    let gamma_8 = &(&gamma_2 + &gamma_2) + &(&gamma_2 + &gamma_2);
+    let y_3 = &(&alpha * &(&beta_4 - &x_3)) - &(&gamma_8 + &gamma_8);
+
+    (x_3, y_3, z_3)
+}
+
+/// P + (x_2, y_2), the mixed addition of madd-2007-bl. The points must
+/// differ and not be at infinity.
+fn add_affine<'a>(
+    (x_1, y_1, z_1): &Jacobian<'a>,
+    (x_2, y_2): &(FpElement<'a>, FpElement<'a>),
+) -> Jacobian<'a> {
+    let z_1_z_1 = z_1.square();
+    let u_2 = x_2 * &z_1_z_1;
+    let s_2 = &(y_2 * z_1) * &z_1_z_1;
+    let h = &u_2 - x_1;
+    let h_h = h.square();
+    let i = &(&h_h + &h_h) + &(&h_h + &h_h);
+    let j = &h * &i;
+    let r = &(&s_2 - y_1) + &(&s_2 - y_1);
+    let v = x_1 * &i;
+
+    let x_3 = &(&r.square() - &j) - &(&v + &v);
+    let y_1_j = y_1 * &j;
+    let y_3 = &(&r * &(&v - &x_3)) - &(&y_1_j + &y_1_j);
+    let z_3 = &(&(z_1 + &h).square() - &z_1_z_1) - &h_h;
+
+    (x_3, y_3, z_3)
+}
+
+/// Times the double-and-add scalar multiplication of a point on a curve
+/// y^2 = x^3 - 3x + b over `ctx`, for full-size scalars. The doublings and
+/// additions skip the point at infinity and equal points, which random
+/// scalars hit with negligible probability.
+fn time_scalar_multiplication(ctx: &FieldContext, scalars: &[BigUint]) -> f64 {
+    // b is whatever puts (5, 7) on the curve
+    let point = (ctx.to_fp(5.into()), ctx.to_fp(7.into()));
+
+    let mut times = Vec::with_capacity(scalars.len());
+    for k in scalars {
+        let now = std::time::Instant::now();
+        let mut result = (point.0.clone(), point.1.clone(), ctx.to_fp(1.into()));
+        for bit in (0..k.bits().saturating_sub(1)).rev() {
+            result = double_jacobian(&result);
+            if k.bit(bit) {
+                result = add_affine(&result, &point);
+            }
+        }
+        black_box(result);
+        times.push(now.elapsed());
+    }
+
+    calcualte_average(&times)
+}
+
+fn montgomery_benchmark() {
+    // the largest primes below 2^bits
+    let primes = [
+        (256, 189),
+        (512, 569),
+        (1024, 105),
+        (2048, 1557),
+        (3072, 47),
+    ];
+    let mut rng = rand::thread_rng();
+
+    println!("bits    operation    plain [ms]    montgomery [ms]    speedup");
+    for (bits, k) in primes {
+        let p: BigInt = (BigInt::one() << bits) - k;
+        let ctx = FieldContext::new_prime(p.clone()).unwrap();
+        let montgomery_ctx = ctx.clone().with_montgomery();
+
+        let runs = (1 << 16) / bits;
+        let exps = (0..runs)
+            .map(|_| rng.gen_biguint_below(&(p.to_biguint().unwrap() - 1u32)))
+            .collect::<Vec<_>>();
+
+        let timings = [
+            (
+                "DH",
+                time_diffie_hellman(&ctx, &exps),
+                time_diffie_hellman(&montgomery_ctx, &exps),
+            ),
+            (
+                "EC",
+                time_scalar_multiplication(&ctx, &exps[..runs / 4]),
+                time_scalar_multiplication(&montgomery_ctx, &exps[..runs / 4]),
+            ),
+        ];
+
+        for (operation, plain, montgomery) in timings {
+            println!(
+                "{:<8}{:<13}{:<14.3}{:<19.3}{:.2}x",
+                bits,
+                operation,
+                plain / 1e6,
+                montgomery / 1e6,
+                plain / montgomery
+            );
+        }
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Some(Commands::Security) => security_demo(),
+        Some(Commands::Security { samples, csv }) => security_demo(samples, csv),
+        Some(Commands::Bench) => montgomery_benchmark(),
         Some(Commands::Fp) => diffie_hellman_fp(),
         Some(Commands::F2Poly) => diffie_hellman_f2_poly(),
         Some(Commands::FpPoly) => diffie_hellman_fp_poly(),
@@ -190,11 +363,32 @@ fn submit_solution() {
     let session_id = response.session_id;
 
     println!("Calcualting for modp");
-    let (modp_public, modp_shared) = solution_fp(&response.modp_params, &response.modp_challenge);
+    let (modp_public, modp_shared) =
+        match solution_fp(&response.modp_params, &response.modp_challenge) {
+            Ok(solution) => solution,
+            Err(error) => {
+                println!("Invalid modp parameters: {}", error);
+                return;
+            }
+        };
     println!("Calcualting for fpk");
-    let (fpk_public, fpk_shared) = solution_fp_poly(&response.fpk_params, &response.fpk_challenge);
+    let (fpk_public, fpk_shared) =
+        match solution_fp_poly(&response.fpk_params, &response.fpk_challenge) {
+            Ok(solution) => solution,
+            Err(error) => {
+                println!("Invalid fpk parameters: {}", error);
+                return;
+            }
+        };
     println!("Calcualting for f2m");
-    let (f2m_public, f2m_shared) = solution_f2_poly(&response.f2m_params, &response.f2m_challenge);
+    let (f2m_public, f2m_shared) =
+        match solution_f2_poly(&response.f2m_params, &response.f2m_challenge) {
+            Ok(solution) => solution,
+            Err(error) => {
+                println!("Invalid f2m parameters: {}", error);
+                return;
+            }
+        };
 
     let request = SubmissionResponseRequest {
         session_id: session_id.clone(),
@@ -247,104 +441,110 @@ fn submit_solution() {
     }
 }
 
-fn solution_f2_poly(params: &F2mParams, challenge: &ChallageRequest) -> (String, String) {
-    let extension = params.extension;
-    let irreducible_poly = decode_base64_biguint_le(&params.modulus);
-
-    // add most significant bit
-    let irreducible_poly = irreducible_poly | (BigUint::one() << extension);
+fn solution_f2_poly(
+    params: &F2mParams,
+    challenge: &ChallageRequest,
+) -> Result<(String, String), ParamError> {
+    let irreducible_poly = binary_modulus(params.extension, &params.modulus)?;
 
-    let g = decode_base64_biguint_le(&params.generator);
-    let order = decode_base64_biguint(&params.order);
+    let g = decode_base64_biguint_le(&params.generator)?;
+    let order = decode_base64_biguint(&params.order)?;
 
-    let ctx = FieldContext::new_binary(irreducible_poly);
-    let g = F2PolynomialElement::new(&ctx, g);
+    let ctx = FieldContext::new_binary(irreducible_poly)?;
+    let g = F2PolynomialElement::try_new(&ctx, g)?;
 
-    let mut rng = rand::thread_rng();
-    let a = rng.gen_biguint_range(&BigUint::from(2u32), &order);
+    let a = secret_exponent(&order)?;
 
-    let a_pub = g.pow(&a);
+    let a_pub = g.pow_secure(&a, &order);
 
-    let b_pub = decode_base64_biguint_le(&challenge.public);
-    let b_pub = F2PolynomialElement::new(&ctx, b_pub);
+    let b_pub = decode_base64_biguint_le(&challenge.public)?;
+    let b_pub = F2PolynomialElement::try_new(&ctx, b_pub)?;
 
-    let a_shared = b_pub.pow(&a);
+    let a_shared = b_pub.pow_secure(&a, &order);
 
-    (
+    Ok((
         encode_base64_biguint_le(&a_pub.coeffs),
         encode_base64_biguint_le(&a_shared.coeffs),
-    )
+    ))
 }
 
 fn solution_fp_poly(
     params: &FpkParams,
     challenge: &ChallageFpkRequest,
-) -> (Vec<String>, Vec<String>) {
-    let p = decode_base64(&params.prime_base);
+) -> Result<(Vec<String>, Vec<String>), ParamError> {
+    let p = decode_base64(&params.prime_base)?;
     let mut irreducible_poly = params
         .modulus
         .iter()
         .map(|x| decode_base64(x))
-        .collect::<Vec<BigInt>>();
+        .collect::<Result<Vec<BigInt>, _>>()?;
 
     // add 1 at the end of vector
     irreducible_poly.push(BigInt::one());
 
-    let ctx = FieldContext::new_poly(p, irreducible_poly);
+    let ctx = FieldContext::new_poly(p, irreducible_poly)?;
 
     let g = params
         .generator
         .iter()
-        .map(|x| FpElement::new(&ctx, decode_base64(x)))
-        .collect::<Vec<FpElement>>();
+        .map(|x| Ok(FpElement::new(&ctx, decode_base64(x)?)))
+        .collect::<Result<Vec<FpElement>, ParamError>>()?;
 
-    let order = decode_base64_biguint(&params.order);
+    let order = decode_base64_biguint(&params.order)?;
 
-    let g = FpPolynomialElement::new(&ctx, g);
+    let g = FpPolynomialElement::try_new(&ctx, g)?;
 
-    let mut rng = rand::thread_rng();
-    let a = rng.gen_biguint_range(&BigUint::from(2u32), &order);
+    let a = secret_exponent(&order)?;
 
-    let a_pub = g.pow(&a);
+    let a_pub = g.pow_secure(&a, &order);
 
     let b_pub = challenge
         .public
         .iter()
-        .map(|x| FpElement::new(&ctx, decode_base64(x)))
-        .collect::<Vec<FpElement>>();
-    let b_pub = FpPolynomialElement::new(&ctx, b_pub);
+        .map(|x| Ok(FpElement::new(&ctx, decode_base64(x)?)))
+        .collect::<Result<Vec<FpElement>, ParamError>>()?;
+    let b_pub = FpPolynomialElement::try_new(&ctx, b_pub)?;
 
-    let a_shared = b_pub.pow(&a);
+    let a_shared = b_pub.pow_secure(&a, &order);
 
-    (
-        a_pub.coeffs.iter().map(|x| encode_base64(&x.val)).collect(),
+    Ok((
+        a_pub
+            .coeffs
+            .iter()
+            .map(|x| encode_base64(&x.value()))
+            .collect(),
         a_shared
             .coeffs
             .iter()
-            .map(|x| encode_base64(&x.val))
+            .map(|x| encode_base64(&x.value()))
             .collect(),
-    )
+    ))
 }
 
-fn solution_fp(params: &FpParams, challenge: &ChallageRequest) -> (String, String) {
-    let p = decode_base64(&params.modulus);
-    let g = decode_base64(&params.generator);
-    let order = decode_base64_biguint(&params.order);
+fn solution_fp(
+    params: &FpParams,
+    challenge: &ChallageRequest,
+) -> Result<(String, String), ParamError> {
+    let p = decode_base64(&params.modulus)?;
+    let g = decode_base64(&params.generator)?;
+    let order = decode_base64_biguint(&params.order)?;
 
-    let ctx = FieldContext::new_prime(p.clone());
+    let ctx = FieldContext::new_prime(p.clone())?;
     let g = FpElement::new(&ctx, g);
 
-    let mut rng = rand::thread_rng();
-    let a = rng.gen_biguint_range(&BigUint::from(2u32), &order);
+    let a = secret_exponent(&order)?;
 
-    let a_pub = g.pow(&a);
+    let a_pub = g.pow_secure(&a, &order);
 
-    let b_pub = decode_base64(&challenge.public);
+    let b_pub = decode_base64(&challenge.public)?;
     let b_pub = FpElement::new(&ctx, b_pub);
 
-    let a_shared = b_pub.pow(&a);
+    let a_shared = b_pub.pow_secure(&a, &order);
 
-    (encode_base64(&a_pub.val), encode_base64(&a_shared.val))
+    Ok((
+        encode_base64(&a_pub.value()),
+        encode_base64(&a_shared.value()),
+    ))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -453,7 +653,7 @@ struct ChallangeFpkResponse {
     shared: Vec<String>,
 }
 
-fn validate_solution_fp(base_url: &str, client: &Client) {
+fn validate_solution_fp(base_url: &str, client: &Client) -> Result<(), ParamError> {
     let params = client
         .get(format!("{}/validate/list2/modp/param", base_url))
         .send()
@@ -461,30 +661,29 @@ fn validate_solution_fp(base_url: &str, client: &Client) {
 
     if !params.status().is_success() {
         println!("Failed to fetch parameters for F_p");
-        return;
+        return Ok(());
     }
 
     let params: ParamFpResponse = params.json().unwrap();
 
     if params.r#type != "modp" {
         println!("Invalid type of parameters");
-        return;
+        return Ok(());
     }
 
-    let p = decode_base64(&params.params.modulus);
-    let g = decode_base64(&params.params.generator);
-    let order = decode_base64_biguint(&params.params.order);
+    let p = decode_base64(&params.params.modulus)?;
+    let g = decode_base64(&params.params.generator)?;
+    let order = decode_base64_biguint(&params.params.order)?;
 
-    let ctx = FieldContext::new_prime(p.clone());
+    let ctx = FieldContext::new_prime(p.clone())?;
     let g = FpElement::new(&ctx, g);
 
-    let mut rng = rand::thread_rng();
-    let a = rng.gen_biguint_range(&BigUint::from(2u32), &order);
+    let a = secret_exponent(&order)?;
 
-    let a_pub = g.pow(&a);
+    let a_pub = g.pow_secure(&a, &order);
 
     let challange = ChallageRequest {
-        public: encode_base64(&a_pub.val),
+        public: encode_base64(&a_pub.value()),
     };
 
     let challange = client
@@ -495,33 +694,41 @@ fn validate_solution_fp(base_url: &str, client: &Client) {
 
     if !challange.status().is_success() {
         println!("Failed to send challange");
-        return;
+        return Ok(());
     }
 
     let challange: ChallangeResponse = challange.json().unwrap();
 
-    let b_pub = decode_base64(&challange.public);
+    let b_pub = decode_base64(&challange.public)?;
     let b_pub = FpElement::new(&ctx, b_pub);
-    let b_shared = decode_base64(&challange.shared);
+    let b_shared = decode_base64(&challange.shared)?;
     let b_shared = FpElement::new(&ctx, b_shared);
 
-    let a_shared = b_pub.pow(&a);
+    let a_shared = b_pub.pow_secure(&a, &order);
 
     assert_eq!(a_shared, b_shared);
 
     println!("Fp Solution is correct");
+
+    Ok(())
 }
 
 fn validate_solution() {
     let base_url = "https://crypto24.random-oracle.xyz/";
     let client = Client::new();
 
-    validate_solution_fp(base_url, &client);
-    validate_solution_f2_poly(base_url, &client);
-    validate_solution_fp_poly(base_url, &client);
+    if let Err(error) = validate_solution_fp(base_url, &client) {
+        println!("Invalid parameters for F_p: {}", error);
+    }
+    if let Err(error) = validate_solution_f2_poly(base_url, &client) {
+        println!("Invalid parameters for F_2^m: {}", error);
+    }
+    if let Err(error) = validate_solution_fp_poly(base_url, &client) {
+        println!("Invalid parameters for F_p^k: {}", error);
+    }
 }
 
-fn validate_solution_fp_poly(base_url: &str, client: &Client) {
+fn validate_solution_fp_poly(base_url: &str, client: &Client) -> Result<(), ParamError> {
     let params = client
         .get(format!("{}/validate/list2/fpk/param", base_url))
         .send()
@@ -529,49 +736,52 @@ fn validate_solution_fp_poly(base_url: &str, client: &Client) {
 
     if !params.status().is_success() {
         println!("Failed to fetch parameters for F_p^k");
-        return;
+        return Ok(());
     }
 
     let params: ParamFpkREsponse = params.json().unwrap();
 
     if params.r#type != "fpk" {
         println!("Invalid type of parameters");
-        return;
+        return Ok(());
     }
 
     // let extension = params.params.extension;
 
-    let p = decode_base64(&params.params.prime_base);
+    let p = decode_base64(&params.params.prime_base)?;
     let mut irreducible_poly = params
         .params
         .modulus
         .iter()
         .map(|x| decode_base64(x))
-        .collect::<Vec<BigInt>>();
+        .collect::<Result<Vec<BigInt>, _>>()?;
 
     // add 1 at the end of vector
     irreducible_poly.push(BigInt::one());
 
-    let ctx = FieldContext::new_poly(p, irreducible_poly);
+    let ctx = FieldContext::new_poly(p, irreducible_poly)?;
 
     let g = params
         .params
         .generator
         .iter()
-        .map(|x| FpElement::new(&ctx, decode_base64(x)))
-        .collect::<Vec<FpElement>>();
+        .map(|x| Ok(FpElement::new(&ctx, decode_base64(x)?)))
+        .collect::<Result<Vec<FpElement>, ParamError>>()?;
 
-    let order = decode_base64_biguint(&params.params.order);
+    let order = decode_base64_biguint(&params.params.order)?;
 
     let g = FpPolynomialElement::new(&ctx, g);
 
-    let mut rng = rand::thread_rng();
-    let a = rng.gen_biguint_range(&BigUint::from(2u32), &order);
+    let a = secret_exponent(&order)?;
 
-    let a_pub = g.pow(&a);
+    let a_pub = g.pow_secure(&a, &order);
 
     let challange = ChallageFpkRequest {
-        public: a_pub.coeffs.iter().map(|x| encode_base64(&x.val)).collect(),
+        public: a_pub
+            .coeffs
+            .iter()
+            .map(|x| encode_base64(&x.value()))
+            .collect(),
     };
 
     let challange = client
@@ -583,7 +793,7 @@ fn validate_solution_fp_poly(base_url: &str, client: &Client) {
     if !challange.status().is_success() {
         println!("Failed to send challange");
         println!("{:?}", challange.text());
-        return;
+        return Ok(());
     }
 
     let challange: ChallangeFpkResponse = challange.json().unwrap();
@@ -591,24 +801,26 @@ fn validate_solution_fp_poly(base_url: &str, client: &Client) {
     let b_pub = challange
         .public
         .iter()
-        .map(|x| FpElement::new(&ctx, decode_base64(x)))
-        .collect::<Vec<FpElement>>();
+        .map(|x| Ok(FpElement::new(&ctx, decode_base64(x)?)))
+        .collect::<Result<Vec<FpElement>, ParamError>>()?;
     let b_pub = FpPolynomialElement::new(&ctx, b_pub);
     let b_shared = challange
         .shared
         .iter()
-        .map(|x| FpElement::new(&ctx, decode_base64(x)))
-        .collect::<Vec<FpElement>>();
+        .map(|x| Ok(FpElement::new(&ctx, decode_base64(x)?)))
+        .collect::<Result<Vec<FpElement>, ParamError>>()?;
     let b_shared = FpPolynomialElement::new(&ctx, b_shared);
 
-    let a_shared = b_pub.pow(&a);
+    let a_shared = b_pub.pow_secure(&a, &order);
 
     assert_eq!(a_shared, b_shared);
 
     println!("Fpk Solution is correct");
+
+    Ok(())
 }
 
-fn validate_solution_f2_poly(base_url: &str, client: &Client) {
+fn validate_solution_f2_poly(base_url: &str, client: &Client) -> Result<(), ParamError> {
     let params = client
         .get(format!("{}/validate/list2/f2m/param", base_url))
         .send()
@@ -616,32 +828,27 @@ fn validate_solution_f2_poly(base_url: &str, client: &Client) {
 
     if !params.status().is_success() {
         println!("Failed to fetch parameters for F_2^m");
-        return;
+        return Ok(());
     }
 
     let params: ParamF2mResponse = params.json().unwrap();
 
     if params.r#type != "f2m" {
         println!("Invalid type of parameters");
-        return;
+        return Ok(());
     }
 
-    let extension = params.params.extension;
-    let irreducible_poly = decode_base64_biguint_le(&params.params.modulus);
-
-    // add most significant bit
-    let irreducible_poly = irreducible_poly | (BigUint::one() << extension);
+    let irreducible_poly = binary_modulus(params.params.extension, &params.params.modulus)?;
 
-    let g = decode_base64_biguint_le(&params.params.generator);
-    let order = decode_base64_biguint(&params.params.order);
+    let g = decode_base64_biguint_le(&params.params.generator)?;
+    let order = decode_base64_biguint(&params.params.order)?;
 
-    let ctx = FieldContext::new_binary(irreducible_poly);
+    let ctx = FieldContext::new_binary(irreducible_poly)?;
     let g = F2PolynomialElement::new(&ctx, g);
 
-    let mut rng = rand::thread_rng();
-    let a = rng.gen_biguint_range(&BigUint::from(2u32), &order);
+    let a = secret_exponent(&order)?;
 
-    let a_pub = g.pow(&a);
+    let a_pub = g.pow_secure(&a, &order);
 
     let challange = ChallageRequest {
         public: encode_base64_biguint_le(&a_pub.coeffs),
@@ -656,36 +863,60 @@ fn validate_solution_f2_poly(base_url: &str, client: &Client) {
     if !challange.status().is_success() {
         println!("Failed to send challange");
         println!("{:?}", challange.text());
-        return;
+        return Ok(());
     }
 
     let challange: ChallangeResponse = challange.json().unwrap();
 
-    let b_pub = decode_base64_biguint_le(&challange.public);
+    let b_pub = decode_base64_biguint_le(&challange.public)?;
     let b_pub = F2PolynomialElement::new(&ctx, b_pub);
-    let b_shared = decode_base64_biguint_le(&challange.shared);
+    let b_shared = decode_base64_biguint_le(&challange.shared)?;
     let b_shared = F2PolynomialElement::new(&ctx, b_shared);
 
-    let a_shared = b_pub.pow(&a);
+    let a_shared = b_pub.pow_secure(&a, &order);
 
     assert_eq!(a_shared, b_shared);
 
     println!("F2m Solution is correct");
+
+    Ok(())
+}
+
+fn decode_base64(s: &str) -> Result<BigInt, DecodeError> {
+    let s = URL_SAFE_NO_PAD.decode(s.as_bytes())?;
+    Ok(BigInt::from_bytes_be(Sign::Plus, &s))
 }
 
-fn decode_base64(s: &str) -> BigInt {
-    let s = URL_SAFE_NO_PAD.decode(s.as_bytes()).unwrap();
-    BigInt::from_bytes_be(Sign::Plus, &s)
+fn decode_base64_biguint(s: &str) -> Result<BigUint, DecodeError> {
+    let s = URL_SAFE_NO_PAD.decode(s.as_bytes())?;
+    Ok(BigUint::from_bytes_be(&s))
 }
 
-fn decode_base64_biguint(s: &str) -> BigUint {
-    let s = URL_SAFE_NO_PAD.decode(s.as_bytes()).unwrap();
-    BigUint::from_bytes_be(&s)
+fn decode_base64_biguint_le(s: &str) -> Result<BigUint, DecodeError> {
+    let s = URL_SAFE_NO_PAD.decode(s.as_bytes())?;
+    Ok(BigUint::from_bytes_le(&s))
 }
 
-fn decode_base64_biguint_le(s: &str) -> BigUint {
-    let s = URL_SAFE_NO_PAD.decode(s.as_bytes()).unwrap();
-    BigUint::from_bytes_le(&s)
+/// The modulus of a binary field of degree `extension`, whose other bits
+/// `modulus` gives.
+fn binary_modulus(extension: i32, modulus: &str) -> Result<BigUint, ParamError> {
+    if !(1..=MAX_EXTENSION).contains(&extension) {
+        return Err(ParamError::Extension(extension));
+    }
+    let irreducible_poly = decode_base64_biguint_le(modulus)?;
+
+    // add most significant bit
+    Ok(irreducible_poly | (BigUint::one() << extension as usize))
+}
+
+/// A random secret exponent in [2, order).
+fn secret_exponent(order: &BigUint) -> Result<BigUint, ParamError> {
+    if *order <= BigUint::from(2u32) {
+        return Err(ParamError::Order(order.clone()));
+    }
+
+    let mut rng = rand::thread_rng();
+    Ok(rng.gen_biguint_range(&BigUint::from(2u32), order))
 }
 
 fn encode_base64(n: &BigInt) -> String {
@@ -710,7 +941,7 @@ fn encode_base64_biguint_le(n: &BigUint) -> String {
 
 fn diffie_hellman_fp() {
     let p = BigInt::parse_bytes(b"7441601072810284702464629351659524507907489347397523425173826419365612833915210446029303962789322887", 10).unwrap();
-    let ctx = FieldContext::new_prime(p.clone());
+    let ctx = FieldContext::new_prime(p.clone()).unwrap();
 
     let g = FpElement::new(&ctx, 5.to_bigint().unwrap());
     let order = BigUint::parse_bytes(b"7441601072810284702464629351659524507907489347397523425173826419365612833915210446029303962789322886", 10).unwrap();
@@ -719,10 +950,7 @@ fn diffie_hellman_fp() {
     println!("g: {}", g);
     println!("order: {}", order);
 
-    assert_eq!(
-        g.pow_secure(&order, &order),
-        FpElement::new(&ctx, BigInt::one())
-    );
+    assert_eq!(g.pow(&order), FpElement::new(&ctx, BigInt::one()));
 
     // random BigUint number from 2..order
     let mut rng = rand::thread_rng();
@@ -759,7 +987,7 @@ fn diffie_hellman_fp_poly() {
     .iter()
     .map(|x| x.to_bigint().unwrap())
     .collect();
-    let ctx = FieldContext::new_poly(p.clone(), irreducible_poly);
+    let ctx = FieldContext::new_poly(p.clone(), irreducible_poly).unwrap();
 
     let g_poly = vec![
         605205470, 603884639, 622982662, 865041543, 924786951, 162565113, 647335234, 475891684,
@@ -777,7 +1005,7 @@ fn diffie_hellman_fp_poly() {
     println!("g: {}", g);
     println!("order: {}", order);
 
-    assert_eq!(g.pow_secure(&order, &order), FpPolynomialElement::one(&ctx));
+    assert_eq!(g.pow(&order), FpPolynomialElement::one(&ctx));
 
     // random BigUint number from 2..order
     let mut rng = rand::thread_rng();
@@ -804,7 +1032,7 @@ fn diffie_hellman_fp_poly() {
 
 fn diffie_hellman_f2_poly() {
     let irreducible_poly = BigUint::from(0b11111101111101001u64);
-    let ctx = FieldContext::new_binary(irreducible_poly);
+    let ctx = FieldContext::new_binary(irreducible_poly).unwrap();
 
     let g = F2PolynomialElement::new(&ctx, BigUint::from(0b1000101000011101u64));
 
@@ -814,7 +1042,7 @@ fn diffie_hellman_f2_poly() {
     println!("g: {}", g);
     println!("order: {}", order);
 
-    assert_eq!(g.pow_secure(&order, &order), F2PolynomialElement::one(&ctx));
+    assert_eq!(g.pow(&order), F2PolynomialElement::one(&ctx));
 
     // random BigUint number from 2..order
     let mut rng = rand::thread_rng();
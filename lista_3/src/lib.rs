@@ -1,4 +1,5 @@
 pub mod field;
+pub use leakage;
 
 use field::fp::FpElement;
 use num::{bigint::BigInt, BigUint, Zero};
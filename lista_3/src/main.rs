@@ -5,12 +5,15 @@ use diffie_hellman::field::ec_binary::{BinaryEllipticCurve, BinaryPoint};
 use diffie_hellman::field::f2_poly::F2PolynomialElement;
 use diffie_hellman::field::fp::FpElement;
 use diffie_hellman::field::fp_poly::FpPolynomialElement;
+use diffie_hellman::leakage;
 use diffie_hellman::FieldContext;
 use num::bigint::{RandBigInt, Sign, ToBigInt};
 use num::{BigInt, BigUint, One};
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::hint::black_box;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -21,6 +24,20 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
+    /// test of scalar multiplication on P-256 for timing leaks, fixed against random scalars
+    Security {
+        /// number of timed multiplications
+        #[arg(
+            long,
+            default_value_t = 1000,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new()
+                .range(leakage::MIN_SAMPLES as u64..)
+        )]
+        samples: usize,
+        /// file to write the measurements to, as CSV
+        #[arg(long)]
+        csv: Option<PathBuf>,
+    },
     /// Validate solution with service provided by the university
     Validate,
     /// Submit solution to the service provided by the university
@@ -31,6 +48,7 @@ fn main() {
     let cli = Cli::parse();
 
     match cli.command {
+        Some(Commands::Security { samples, csv }) => security_demo(samples, csv),
         Some(Commands::Validate) => validate_solution(),
         Some(Commands::Solution) => submit_solution(),
         None => {
@@ -39,6 +57,54 @@ fn main() {
     }
 }
 
+/// The leakage test of `EllipticCurve::mul` on P-256: scalar 1 against
+/// random ones below the order of the generator.
+fn security_demo(samples: usize, csv: Option<PathBuf>) {
+    let hex = |s: &str| BigInt::parse_bytes(s.as_bytes(), 16).unwrap();
+
+    let p: BigInt = (BigInt::one() << 256) - (BigInt::one() << 224)
+        + (BigInt::one() << 192)
+        + (BigInt::one() << 96)
+        - 1;
+    let b = hex("5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b");
+    let x = hex("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296");
+    let y = hex("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5");
+    let order = BigUint::parse_bytes(
+        b"ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551",
+        16,
+    )
+    .unwrap();
+
+    let poly = vec![0.to_bigint().unwrap(), 1.to_bigint().unwrap()];
+    let ctx = FieldContext::new_poly(p, poly);
+    let a = FpPolynomialElement::from_fp(&ctx, FpElement::new(&ctx, (-3).into()));
+    let b = FpPolynomialElement::from_fp(&ctx, FpElement::new(&ctx, b));
+    let curve = EllipticCurve::new(a, b, &ctx);
+    let g = curve.point(x, y);
+
+    let mut rng = rand::thread_rng();
+    let report = leakage::measure(
+        "EC P-256",
+        samples,
+        &mut rng,
+        BigUint::one(),
+        |rng| rng.gen_biguint_below(&order),
+        |k| {
+            black_box(curve.mul(k, &g));
+        },
+    );
+    println!("{}", report);
+
+    if let Some(path) = csv {
+        let file = std::fs::File::create(path).unwrap();
+        report.write_csv(std::io::BufWriter::new(file)).unwrap();
+    }
+
+    if report.verdict() != leakage::Verdict::NoLeak {
+        std::process::exit(1);
+    }
+}
+
 fn submit_solution() {
     let base_url = "https://crypto24.random-oracle.xyz/";
     let student_id = "10000000000000000000000000000033";